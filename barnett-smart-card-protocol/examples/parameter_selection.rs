@@ -14,7 +14,8 @@ use ark_ec::ProjectiveCurve;
 use ark_ff::UniformRand;
 use ark_serialize::CanonicalSerialize;
 use barnett_smart_card_protocol::discrete_log_cards::MaskedCard;
-use barnett_smart_card_protocol::{discrete_log_cards, BarnettSmartProtocol};
+use barnett_smart_card_protocol::witness::Witness;
+use barnett_smart_card_protocol::{discrete_log_cards, DeckShuffle, ProtocolSetup};
 use byte_unit::Byte;
 use proof_essentials::utils::permutation::Permutation;
 use proof_essentials::utils::rand::sample_vector;
@@ -35,7 +36,7 @@ fn main() -> anyhow::Result<()> {
 
     let deck: Vec<MaskedCard<Curve>> = sample_vector(&mut rng, NUMBER_OF_CARDS);
     let shared_key = Curve::rand(&mut rng);
-    let blinding_factors: Vec<Scalar> = sample_vector(&mut rng, NUMBER_OF_CARDS);
+    let blinding_factors: Witness<Vec<Scalar>> = Witness::sample(&mut rng, NUMBER_OF_CARDS);
     let permutation = Permutation::new(&mut rng, NUMBER_OF_CARDS);
 
     let m_values: Vec<usize> = vec![2, 6, 10, 12, 30];
@@ -61,7 +62,7 @@ fn benchmark_parameters<R: Rng>(
     m: usize,
     n: usize,
     shared_key: &Curve,
-    masking_factors: &Vec<Scalar>,
+    masking_factors: &Witness<Vec<Scalar>>,
     permutation: &Permutation,
     rng: &mut R,
 ) -> anyhow::Result<()> {
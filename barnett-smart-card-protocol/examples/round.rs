@@ -1,11 +1,15 @@
 use barnett_smart_card_protocol::discrete_log_cards;
-use barnett_smart_card_protocol::BarnettSmartProtocol;
+use barnett_smart_card_protocol::playing_cards::ClassicPlayingCard;
+use barnett_smart_card_protocol::witness::Witness;
+use barnett_smart_card_protocol::{
+    CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification, ProtocolSetup,
+    RevealVerification, ShuffleVerification,
+};
 
 use anyhow;
 use ark_ff::{to_bytes, UniformRand};
 use ark_std::{rand::Rng, One};
 use proof_essentials::utils::permutation::Permutation;
-use proof_essentials::utils::rand::sample_vector;
 use proof_essentials::zkp::proofs::{chaum_pedersen_dl_equality, schnorr_identification};
 use rand::thread_rng;
 use std::collections::HashMap;
@@ -39,100 +43,11 @@ pub enum GameErrors {
     InvalidCard,
 }
 
-#[derive(PartialEq, Clone, Copy, Eq)]
-pub enum Suite {
-    Club,
-    Diamond,
-    Heart,
-    Spade,
-}
-
-impl Suite {
-    const VALUES: [Self; 4] = [Self::Club, Self::Diamond, Self::Heart, Self::Spade];
-}
-
-#[derive(PartialEq, PartialOrd, Clone, Copy, Eq)]
-pub enum Value {
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Ten,
-    Jack,
-    Queen,
-    King,
-    Ace,
-}
-
-impl Value {
-    const VALUES: [Self; 13] = [
-        Self::Two,
-        Self::Three,
-        Self::Four,
-        Self::Five,
-        Self::Six,
-        Self::Seven,
-        Self::Eight,
-        Self::Nine,
-        Self::Ten,
-        Self::Jack,
-        Self::Queen,
-        Self::King,
-        Self::Ace,
-    ];
-}
-
-#[derive(PartialEq, Clone, Eq, Copy)]
-pub struct ClassicPlayingCard {
-    value: Value,
-    suite: Suite,
-}
-
-impl ClassicPlayingCard {
-    pub fn new(value: Value, suite: Suite) -> Self {
-        Self { value, suite }
-    }
-}
-
-impl std::fmt::Debug for ClassicPlayingCard {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let suite = match self.suite {
-            Suite::Club => "♣",
-            Suite::Diamond => "♦",
-            Suite::Heart => "♥",
-            Suite::Spade => "♠",
-        };
-
-        let val = match self.value {
-            Value::Two => "2",
-            Value::Three => "3",
-            Value::Four => "4",
-            Value::Five => "5",
-            Value::Six => "6",
-            Value::Seven => "7",
-            Value::Eight => "8",
-            Value::Nine => "9",
-            Value::Ten => "10",
-            Value::Jack => "J",
-            Value::Queen => "Q",
-            Value::King => "K",
-            Value::Ace => "A",
-        };
-
-        write!(f, "{}{}", val, suite)
-    }
-}
-
-#[derive(Clone)]
 struct Player {
     name: Vec<u8>,
     sk: SecretKey,
     pk: PublicKey,
-    proof_key: ProofKeyOwnership,
+    proof_key: Option<ProofKeyOwnership>,
     cards: Vec<MaskedCard>,
     opened_cards: Vec<Option<ClassicPlayingCard>>,
 }
@@ -140,17 +55,36 @@ struct Player {
 impl Player {
     pub fn new<R: Rng>(rng: &mut R, pp: &CardParameters, name: &Vec<u8>) -> anyhow::Result<Self> {
         let (pk, sk) = CardProtocol::player_keygen(rng, pp)?;
-        let proof_key = CardProtocol::prove_key_ownership(rng, pp, &pk, &sk, name)?;
         Ok(Self {
             name: name.clone(),
             sk,
             pk,
-            proof_key,
+            proof_key: None,
             cards: vec![],
             opened_cards: vec![],
         })
     }
 
+    /// Prove ownership of the player's key for the game played by all the participants
+    pub fn prove_key_ownership<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        pp: &CardParameters,
+        participants_digest: &[u8],
+    ) -> anyhow::Result<ProofKeyOwnership> {
+        let proof_key = CardProtocol::prove_key_ownership_in_game(
+            rng,
+            pp,
+            &self.pk,
+            &self.sk,
+            &self.name,
+            participants_digest,
+        )?;
+        self.proof_key = Some(proof_key);
+
+        Ok(proof_key)
+    }
+
     pub fn receive_card(&mut self, card: MaskedCard) {
         self.cards.push(card);
         self.opened_cards.push(None);
@@ -172,7 +106,7 @@ impl Player {
         let own_reveal_token = self.compute_reveal_token(rng, parameters, card)?;
         reveal_tokens.push(own_reveal_token);
 
-        let unmasked_card = CardProtocol::unmask(&parameters, reveal_tokens, card)?;
+        let unmasked_card = CardProtocol::unmask(&parameters, reveal_tokens.iter(), card)?;
         let opened_card = card_mappings.get(&unmasked_card);
         let opened_card = opened_card.ok_or(GameErrors::InvalidCard)?;
 
@@ -196,7 +130,7 @@ impl Player {
 //Every player will have to calculate this function for cards that are in play
 pub fn open_card(
     parameters: &CardParameters,
-    reveal_tokens: &Vec<(RevealToken, RevealProof, PublicKey)>,
+    reveal_tokens: &[(RevealToken, RevealProof, PublicKey)],
     card_mappings: &HashMap<Card, ClassicPlayingCard>,
     card: &MaskedCard,
 ) -> Result<ClassicPlayingCard, anyhow::Error> {
@@ -213,13 +147,8 @@ fn encode_cards<R: Rng>(rng: &mut R, num_of_cards: usize) -> HashMap<Card, Class
         .map(|_| Card::rand(rng))
         .collect::<Vec<_>>();
 
-    let mut i = 0;
-    for value in Value::VALUES.iter().copied() {
-        for suite in Suite::VALUES.iter().copied() {
-            let current_card = ClassicPlayingCard::new(value, suite);
-            map.insert(plaintexts[i], current_card);
-            i += 1;
-        }
+    for (plaintext, card) in plaintexts.into_iter().zip(ClassicPlayingCard::deck()) {
+        map.insert(plaintext, card);
     }
 
     map
@@ -239,12 +168,26 @@ fn main() -> anyhow::Result<()> {
     let mut nico = Player::new(rng, &parameters, &to_bytes![b"Nico"].unwrap())?;
     let mut tom = Player::new(rng, &parameters, &to_bytes![b"Tom"].unwrap())?;
 
-    let players = vec![andrija.clone(), kobi.clone(), nico.clone(), tom.clone()];
-
-    let key_proof_info = players
-        .iter()
-        .map(|p| (p.pk, p.proof_key, p.name.clone()))
-        .collect::<Vec<_>>();
+    // Once every player has announced their key, each of them proves ownership of it for this game
+    let participants = vec![
+        (andrija.pk, andrija.name.clone()),
+        (kobi.pk, kobi.name.clone()),
+        (nico.pk, nico.name.clone()),
+        (tom.pk, tom.name.clone()),
+    ];
+    let participants_digest =
+        CardProtocol::participants_digest(participants.iter().map(|(pk, name)| (pk, name)))?;
+
+    let key_proof_info = vec![
+        andrija.prove_key_ownership(rng, &parameters, &participants_digest)?,
+        kobi.prove_key_ownership(rng, &parameters, &participants_digest)?,
+        nico.prove_key_ownership(rng, &parameters, &participants_digest)?,
+        tom.prove_key_ownership(rng, &parameters, &participants_digest)?,
+    ]
+    .into_iter()
+    .zip(participants)
+    .map(|(proof, (pk, name))| (pk, proof, name))
+    .collect::<Vec<_>>();
 
     // Each player should run this computation. Alternatively, it can be ran by a smart contract
     let joint_pk = CardProtocol::compute_aggregate_key(&parameters, &key_proof_info)?;
@@ -252,7 +195,15 @@ fn main() -> anyhow::Result<()> {
     // Each player should run this computation and verify that all players agree on the initial deck
     let deck_and_proofs: Vec<(MaskedCard, RemaskingProof)> = card_mapping
         .keys()
-        .map(|card| CardProtocol::mask(rng, &parameters, &joint_pk, &card, &Scalar::one()))
+        .map(|card| {
+            CardProtocol::mask(
+                rng,
+                &parameters,
+                &joint_pk,
+                &card,
+                &Witness::new(Scalar::one()),
+            )
+        })
         .collect::<Result<Vec<_>, _>>()?;
 
     let deck = deck_and_proofs
@@ -263,7 +214,7 @@ fn main() -> anyhow::Result<()> {
     // SHUFFLE TIME --------------
     // 1.a Andrija shuffles first
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
 
     let (a_shuffled_deck, a_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
@@ -285,7 +236,7 @@ fn main() -> anyhow::Result<()> {
 
     //2.a Kobi shuffles second
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
 
     let (k_shuffled_deck, k_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
@@ -307,7 +258,7 @@ fn main() -> anyhow::Result<()> {
 
     //3.a Nico shuffles third
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
 
     let (n_shuffled_deck, n_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
@@ -329,7 +280,7 @@ fn main() -> anyhow::Result<()> {
 
     //4.a Tom shuffles last
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
 
     let (final_shuffled_deck, final_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
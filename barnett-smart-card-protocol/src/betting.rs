@@ -0,0 +1,603 @@
+//! Chips, bets and pots of a poker hand.
+//!
+//! [`Betting`] follows the betting of a whole hand, street after street: it posts the blinds, checks that
+//! every action is legal and made in turn, and splits the chips put in by the players into a main pot
+//! and side pots when some of them are all-in. It knows nothing about cards: a
+//! [`Table`](crate::holdem::Table) runs the betting of its hand and folds the players that fold here in
+//! the protocol as well, after which they keep providing reveal tokens for the public cards but their
+//! own cards are never opened.
+
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::evaluator::HandRank;
+
+pub type Chips = u64;
+
+/// Action of a player in a betting round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Fold,
+    Check,
+    Call,
+    /// Bet or raise so that the player's total bet in this betting round is the given amount
+    Raise(Chips),
+    /// Put all remaining chips in, as a call or a raise
+    AllIn,
+}
+
+/// A pot and the players who can win it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pot {
+    pub amount: Chips,
+    pub eligible: Vec<usize>,
+}
+
+/// Betting state of a hand.
+pub struct Betting {
+    button: usize,
+    big_blind: Chips,
+    stacks: Vec<Chips>,
+    /// Chips put in by each player in the current betting round
+    street_bets: Vec<Chips>,
+    /// Chips put in by each player in the whole hand
+    contributions: Vec<Chips>,
+    folded: Vec<bool>,
+    /// Whether each player has acted since the last full raise
+    acted: Vec<bool>,
+    /// Whether each player may still raise: a short all-in does not reopen the betting for players who
+    /// already acted
+    can_raise: Vec<bool>,
+    current_bet: Chips,
+    min_raise: Chips,
+    to_act: Option<usize>,
+}
+
+impl Betting {
+    /// Start a hand with the given stacks, the dealer button at seat `button`, and post the blinds. With
+    /// two players the button posts the small blind.
+    pub fn new(
+        stacks: Vec<Chips>,
+        button: usize,
+        small_blind: Chips,
+        big_blind: Chips,
+    ) -> Result<Self, CardProtocolError> {
+        let num_players = stacks.len();
+        if num_players < 2 || button >= num_players {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!(
+                    "button at seat {} of a table of {} players",
+                    button, num_players
+                ),
+                context: ErrorContext::default(),
+            });
+        }
+        if big_blind == 0 || small_blind > big_blind {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!("invalid blinds {}/{}", small_blind, big_blind),
+                context: ErrorContext::default(),
+            });
+        }
+        if let Some(player) = stacks.iter().position(|&stack| stack == 0) {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("player sits without chips"),
+                context: ErrorContext::player(player),
+            });
+        }
+
+        let mut betting = Self {
+            button,
+            big_blind,
+            stacks,
+            street_bets: vec![0; num_players],
+            contributions: vec![0; num_players],
+            folded: vec![false; num_players],
+            acted: vec![false; num_players],
+            can_raise: vec![true; num_players],
+            current_bet: 0,
+            min_raise: big_blind,
+            to_act: None,
+        };
+
+        let small_blind_seat = if num_players == 2 {
+            button
+        } else {
+            (button + 1) % num_players
+        };
+        let big_blind_seat = (small_blind_seat + 1) % num_players;
+        betting.put_in(small_blind_seat, small_blind);
+        betting.put_in(big_blind_seat, big_blind);
+        betting.current_bet = big_blind;
+        betting.to_act = betting.next_to_act(big_blind_seat);
+
+        Ok(betting)
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.stacks.len()
+    }
+
+    pub fn stacks(&self) -> &[Chips] {
+        &self.stacks
+    }
+
+    /// Chips put in by each player in the whole hand
+    pub fn contributions(&self) -> &[Chips] {
+        &self.contributions
+    }
+
+    pub fn has_folded(&self, player: usize) -> bool {
+        self.folded[player]
+    }
+
+    /// Amount each player must match to stay in the current betting round
+    pub fn current_bet(&self) -> Chips {
+        self.current_bet
+    }
+
+    /// Player expected to act, or `None` once the betting round is over
+    pub fn to_act(&self) -> Option<usize> {
+        self.to_act
+    }
+
+    /// Players who have not folded
+    pub fn active_players(&self) -> Vec<usize> {
+        (0..self.num_players())
+            .filter(|&player| !self.folded[player])
+            .collect()
+    }
+
+    /// Whether all players but one have folded
+    pub fn is_hand_over(&self) -> bool {
+        self.active_players().len() == 1
+    }
+
+    /// Move up to `amount` chips from the stack of `player` to the pot
+    fn put_in(&mut self, player: usize, amount: Chips) {
+        let amount = amount.min(self.stacks[player]);
+        self.stacks[player] -= amount;
+        self.street_bets[player] += amount;
+        self.contributions[player] += amount;
+    }
+
+    fn needs_to_act(&self, player: usize) -> bool {
+        !self.folded[player]
+            && self.stacks[player] > 0
+            && (!self.acted[player] || self.street_bets[player] < self.current_bet)
+    }
+
+    /// First player after seat `seat` who still has to act in this betting round
+    fn next_to_act(&self, seat: usize) -> Option<usize> {
+        if self.is_hand_over() {
+            return None;
+        }
+
+        let num_players = self.num_players();
+        (1..=num_players)
+            .map(|offset| (seat + offset) % num_players)
+            .find(|&player| self.needs_to_act(player))
+    }
+
+    fn invalid(player: usize, reason: String) -> CardProtocolError {
+        CardProtocolError::InvalidBet {
+            reason,
+            context: ErrorContext::player(player),
+        }
+    }
+
+    /// Play `action` for `player`, who must be the player expected to act.
+    pub fn act(&mut self, player: usize, action: Action) -> Result<(), CardProtocolError> {
+        match self.to_act {
+            None => {
+                return Err(Self::invalid(
+                    player,
+                    String::from("the betting round is over"),
+                ))
+            }
+            Some(expected) if expected != player => {
+                return Err(CardProtocolError::OutOfTurn {
+                    expected,
+                    context: ErrorContext::player(player),
+                })
+            }
+            _ => {}
+        }
+
+        let all_in_to = self.street_bets[player] + self.stacks[player];
+        let action = match action {
+            Action::AllIn if all_in_to <= self.current_bet => Action::Call,
+            Action::AllIn => Action::Raise(all_in_to),
+            action => action,
+        };
+
+        match action {
+            Action::Fold => self.folded[player] = true,
+            Action::Check => {
+                if self.street_bets[player] < self.current_bet {
+                    return Err(Self::invalid(
+                        player,
+                        format!("cannot check facing a bet of {}", self.current_bet),
+                    ));
+                }
+            }
+            Action::Call => {
+                if self.street_bets[player] >= self.current_bet {
+                    return Err(Self::invalid(player, String::from("nothing to call")));
+                }
+                self.put_in(player, self.current_bet - self.street_bets[player]);
+            }
+            Action::Raise(to) => {
+                if !self.can_raise[player] {
+                    return Err(Self::invalid(
+                        player,
+                        String::from("betting was not reopened by the last all-in"),
+                    ));
+                }
+                if to <= self.current_bet {
+                    return Err(Self::invalid(
+                        player,
+                        format!("raise to {} does not exceed {}", to, self.current_bet),
+                    ));
+                }
+                if to > all_in_to {
+                    return Err(Self::invalid(
+                        player,
+                        format!("raise to {} exceeds the stack of the player", to),
+                    ));
+                }
+                let increment = to - self.current_bet;
+                let full_raise = increment >= self.min_raise;
+                if !full_raise && to < all_in_to {
+                    return Err(Self::invalid(
+                        player,
+                        format!(
+                            "raise by {} is below the minimum of {}",
+                            increment, self.min_raise
+                        ),
+                    ));
+                }
+
+                self.put_in(player, to - self.street_bets[player]);
+                self.current_bet = to;
+                for other in (0..self.num_players()).filter(|&other| other != player) {
+                    if full_raise {
+                        self.can_raise[other] = true;
+                    } else if self.acted[other] {
+                        self.can_raise[other] = false;
+                    }
+                    self.acted[other] = false;
+                }
+                if full_raise {
+                    self.min_raise = increment;
+                }
+            }
+            Action::AllIn => unreachable!("all-in is played as a call or a raise"),
+        }
+
+        self.acted[player] = true;
+        self.to_act = self.next_to_act(player);
+
+        Ok(())
+    }
+
+    /// Start the next betting round, once the current one is over. The first player after the button
+    /// acts first.
+    pub fn next_street(&mut self) -> Result<(), CardProtocolError> {
+        if let Some(player) = self.to_act {
+            return Err(Self::invalid(
+                player,
+                String::from("the betting round is not over"),
+            ));
+        }
+
+        self.street_bets = vec![0; self.num_players()];
+        self.acted = vec![false; self.num_players()];
+        self.can_raise = vec![true; self.num_players()];
+        self.current_bet = 0;
+        self.min_raise = self.big_blind;
+
+        // Betting only goes on if at least two players can still bet
+        let can_bet = (0..self.num_players())
+            .filter(|&player| !self.folded[player] && self.stacks[player] > 0)
+            .count();
+        self.to_act = if can_bet >= 2 {
+            self.next_to_act(self.button)
+        } else {
+            None
+        };
+
+        Ok(())
+    }
+
+    /// Split the chips put in the hand into the main pot and the side pots, from the main pot up. Each pot
+    /// can be won by the players who did not fold and put in at least the level of the pot.
+    pub fn pots(&self) -> Vec<Pot> {
+        let mut levels = self
+            .active_players()
+            .into_iter()
+            .map(|player| self.contributions[player])
+            .collect::<Vec<_>>();
+        levels.sort_unstable();
+        levels.dedup();
+
+        let mut pots: Vec<Pot> = Vec::new();
+        let mut previous_level = 0;
+        for level in levels {
+            let amount = self
+                .contributions
+                .iter()
+                .map(|&contribution| contribution.min(level) - contribution.min(previous_level))
+                .sum();
+            let eligible = self
+                .active_players()
+                .into_iter()
+                .filter(|&player| self.contributions[player] >= level)
+                .collect::<Vec<_>>();
+            previous_level = level;
+
+            match pots.last_mut() {
+                Some(pot) if pot.eligible == eligible => pot.amount += amount,
+                _ => pots.push(Pot { amount, eligible }),
+            }
+        }
+
+        pots
+    }
+
+    /// Award the pots and add the winnings to the stacks of the players. `hands` holds the rank of the
+    /// hand shown by each player at the showdown, `None` for players who folded or mucked; it is not
+    /// needed when all players but one have folded. Tied players split a pot, the odd chips going to the
+    /// first winners after the button. Returns the chips won by each player.
+    pub fn settle(&mut self, hands: &[Option<HandRank>]) -> Result<Vec<Chips>, CardProtocolError> {
+        let num_players = self.num_players();
+        let mut winnings = vec![0; num_players];
+
+        if self.is_hand_over() {
+            let winner = self.active_players()[0];
+            winnings[winner] = self.contributions.iter().sum();
+        } else {
+            if hands.len() != num_players {
+                return Err(CardProtocolError::ParameterMismatch {
+                    reason: format!("expected {} hands, found {}", num_players, hands.len()),
+                    context: ErrorContext::default(),
+                });
+            }
+            if let Some(player) = (0..num_players).find(|&p| self.folded[p] && hands[p].is_some()) {
+                return Err(CardProtocolError::ParameterMismatch {
+                    reason: String::from("folded players have no hand"),
+                    context: ErrorContext::player(player),
+                });
+            }
+
+            for pot in self.pots() {
+                let best = pot
+                    .eligible
+                    .iter()
+                    .filter_map(|&player| hands[player].as_ref())
+                    .max();
+                // In seat order starting after the button
+                let mut winners = (1..=num_players)
+                    .map(|offset| (self.button + offset) % num_players)
+                    .filter(|player| pot.eligible.contains(player))
+                    .filter(|&player| best.is_some() && hands[player].as_ref() == best)
+                    .collect::<Vec<_>>();
+                if winners.is_empty() {
+                    // Everyone eligible mucked: the pot goes back to its eligible players
+                    winners = pot.eligible.clone();
+                }
+
+                let share = pot.amount / winners.len() as Chips;
+                let odd_chips = (pot.amount % winners.len() as Chips) as usize;
+                for (i, &winner) in winners.iter().enumerate() {
+                    winnings[winner] += share + if i < odd_chips { 1 } else { 0 };
+                }
+            }
+        }
+
+        for (stack, won) in self.stacks.iter_mut().zip(&winnings) {
+            *stack += won;
+        }
+        self.contributions = vec![0; num_players];
+
+        Ok(winnings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Action, Betting, Pot};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::evaluator::evaluate;
+    use crate::playing_cards::{ClassicPlayingCard, Suite, Value};
+
+    #[test]
+    fn blinds_and_turn_order() {
+        // Heads-up: the button posts the small blind and acts first preflop, last after the flop
+        let mut betting = Betting::new(vec![100, 100], 0, 1, 2).unwrap();
+        assert_eq!(betting.contributions(), &[1, 2]);
+        assert_eq!(betting.to_act(), Some(0));
+        assert_eq!(
+            betting.act(1, Action::Check),
+            Err(CardProtocolError::OutOfTurn {
+                expected: 0,
+                context: ErrorContext::player(1),
+            })
+        );
+        betting.act(0, Action::Call).unwrap();
+        // The big blind has the option
+        assert_eq!(betting.to_act(), Some(1));
+        betting.act(1, Action::Check).unwrap();
+        assert_eq!(betting.to_act(), None);
+
+        betting.next_street().unwrap();
+        assert_eq!(betting.to_act(), Some(1));
+
+        // Four players: small blind after the button, the player after the big blind acts first
+        let mut betting = Betting::new(vec![100; 4], 1, 1, 2).unwrap();
+        assert_eq!(betting.contributions(), &[0, 0, 1, 2]);
+        assert_eq!(betting.to_act(), Some(0));
+        assert!(betting.next_street().is_err());
+    }
+
+    #[test]
+    fn raises() {
+        let mut betting = Betting::new(vec![100, 100, 100], 0, 5, 10).unwrap();
+
+        // The minimum raise is the big blind, then the size of the last raise
+        assert!(matches!(
+            betting.act(0, Action::Raise(15)),
+            Err(CardProtocolError::InvalidBet { .. })
+        ));
+        betting.act(0, Action::Raise(30)).unwrap();
+        assert!(betting.act(1, Action::Raise(45)).is_err());
+        betting.act(1, Action::Raise(50)).unwrap();
+        assert!(betting.act(2, Action::Check).is_err());
+        betting.act(2, Action::Fold).unwrap();
+        betting.act(0, Action::Call).unwrap();
+        assert_eq!(betting.to_act(), None);
+        assert_eq!(betting.contributions(), &[50, 50, 10]);
+
+        betting.next_street().unwrap();
+        assert_eq!(betting.to_act(), Some(1));
+        assert!(betting.act(1, Action::Raise(5)).is_err());
+        assert!(betting.act(1, Action::Raise(51)).is_err());
+        betting.act(1, Action::Raise(10)).unwrap();
+        betting.act(0, Action::Fold).unwrap();
+        assert!(betting.is_hand_over());
+        assert_eq!(betting.to_act(), None);
+
+        assert_eq!(betting.settle(&[]).unwrap(), vec![0, 120, 0]);
+        assert_eq!(betting.stacks(), &[50, 160, 90]);
+    }
+
+    #[test]
+    fn short_all_in_does_not_reopen_betting() {
+        let mut betting = Betting::new(vec![100, 100, 35], 2, 5, 10).unwrap();
+
+        betting.act(2, Action::Raise(20)).unwrap();
+        betting.act(0, Action::Call).unwrap();
+        betting.act(1, Action::Call).unwrap();
+        assert_eq!(betting.to_act(), None);
+
+        betting.next_street().unwrap();
+        assert_eq!(betting.to_act(), Some(0));
+        betting.act(0, Action::Raise(10)).unwrap();
+        betting.act(1, Action::Call).unwrap();
+        // All-in for less than a full raise: the players who already acted can only call or fold
+        betting.act(2, Action::AllIn).unwrap();
+        assert_eq!(betting.current_bet(), 15);
+        assert!(matches!(
+            betting.act(0, Action::Raise(40)),
+            Err(CardProtocolError::InvalidBet { .. })
+        ));
+        betting.act(0, Action::Call).unwrap();
+        betting.act(1, Action::Call).unwrap();
+        assert_eq!(betting.to_act(), None);
+
+        // The players who are not all-in keep betting against each other
+        betting.next_street().unwrap();
+        assert_eq!(betting.to_act(), Some(0));
+        betting.act(0, Action::Raise(40)).unwrap();
+    }
+
+    fn hand(cards: &[(Value, Suite)]) -> Option<crate::evaluator::HandRank> {
+        let cards = cards
+            .iter()
+            .map(|&(value, suite)| ClassicPlayingCard::new(value, suite))
+            .collect::<Vec<_>>();
+        Some(evaluate(&cards).unwrap())
+    }
+
+    #[test]
+    fn side_pots() {
+        // Player 0 is all-in for 20, player 1 for 50, players 2 and 3 bet 100 and player 4 folds after
+        // posting the big blind
+        let mut betting = Betting::new(vec![20, 50, 200, 200, 200], 2, 5, 10).unwrap();
+        betting.act(0, Action::AllIn).unwrap();
+        betting.act(1, Action::AllIn).unwrap();
+        betting.act(2, Action::Raise(100)).unwrap();
+        betting.act(3, Action::Call).unwrap();
+        betting.act(4, Action::Fold).unwrap();
+        assert_eq!(betting.to_act(), None);
+        assert_eq!(betting.contributions(), &[20, 50, 100, 100, 10]);
+
+        assert_eq!(
+            betting.pots(),
+            vec![
+                Pot {
+                    amount: 20 * 4 + 10,
+                    eligible: vec![0, 1, 2, 3],
+                },
+                Pot {
+                    amount: 30 * 3,
+                    eligible: vec![1, 2, 3],
+                },
+                Pot {
+                    amount: 50 * 2,
+                    eligible: vec![2, 3],
+                },
+            ]
+        );
+
+        use Suite::*;
+        use Value::*;
+        let board = [
+            (Two, Club),
+            (Seven, Diamond),
+            (Nine, Heart),
+            (Jack, Spade),
+            (Four, Club),
+        ];
+        let with_board = |hole: [(Value, Suite); 2]| {
+            let mut cards = board.to_vec();
+            cards.extend_from_slice(&hole);
+            hand(&cards)
+        };
+
+        // The short stack has the best hand, players 2 and 3 tie for the rest
+        let hands = vec![
+            with_board([(Ace, Club), (Ace, Diamond)]),
+            with_board([(King, Club), (Queen, Diamond)]),
+            with_board([(Jack, Club), (Three, Diamond)]),
+            with_board([(Jack, Heart), (Three, Heart)]),
+            None,
+        ];
+        let winnings = betting.settle(&hands).unwrap();
+        assert_eq!(winnings, vec![90, 0, 95, 95, 0]);
+        assert_eq!(betting.stacks(), &[90, 0, 195, 195, 190]);
+    }
+
+    #[test]
+    fn odd_chips() {
+        let mut betting = Betting::new(vec![100, 100, 100], 0, 1, 2).unwrap();
+        betting.act(0, Action::Call).unwrap();
+        betting.act(1, Action::Fold).unwrap();
+        betting.act(2, Action::Check).unwrap();
+        assert_eq!(betting.pots()[0].amount, 5);
+
+        // A tie splits the pot, the odd chip goes to the first winner after the button
+        use Suite::*;
+        use Value::*;
+        let tie = hand(&[
+            (Ace, Club),
+            (King, Club),
+            (Queen, Club),
+            (Jack, Club),
+            (Ten, Club),
+        ]);
+        let winnings = betting.settle(&[tie.clone(), None, tie]).unwrap();
+        assert_eq!(winnings, vec![2, 0, 3]);
+
+        // Folded players cannot show a hand
+        let mut betting = Betting::new(vec![100, 100, 100], 0, 1, 2).unwrap();
+        betting.act(0, Action::Fold).unwrap();
+        betting.act(1, Action::Call).unwrap();
+        betting.act(2, Action::Check).unwrap();
+        let shown = hand(&[
+            (Ace, Club),
+            (King, Club),
+            (Queen, Club),
+            (Jack, Club),
+            (Ten, Club),
+        ]);
+        assert!(betting.settle(&[shown.clone(), shown, None]).is_err());
+    }
+}
@@ -0,0 +1,505 @@
+//! A player of a hand of Texas Hold'em played over TCP, through the `relay` binary.
+//!
+//! All the players of a hand are started with the same seed, from which the public parameters, the
+//! session id and the open cards are derived, and each with their own index in the turn order. Players
+//! post signed messages on the relay in a fixed order, each waiting for all the messages due before their
+//! own, and every player checks every message on the board. The hand is checked down: there is no betting
+//! and every player shows their hole cards at the showdown.
+//!
+//! Players print their hole cards as soon as they see them, then the board, the hands shown and the
+//! winners, one line each, cards being given by their id.
+
+use barnett_smart_card_protocol::discrete_log_cards::message::{Message, MessageContext, DEALER};
+use barnett_smart_card_protocol::discrete_log_cards::{self, GameSession, SessionPhase};
+use barnett_smart_card_protocol::evaluator;
+use barnett_smart_card_protocol::holdem::{Table, DECK_SIZE, HOLE_CARDS, MAX_PLAYERS};
+use barnett_smart_card_protocol::net::RelayClient;
+use barnett_smart_card_protocol::playing_cards::ClassicPlayingCard;
+use barnett_smart_card_protocol::witness::Witness;
+use barnett_smart_card_protocol::{
+    CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification,
+};
+
+use anyhow::{anyhow, bail, ensure, Context};
+use ark_ff::{One, UniformRand};
+use clap::Parser;
+use proof_essentials::utils::permutation::Permutation;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::convert::TryInto;
+
+// Choose elliptic curve setting
+type Curve = starknet_curve::Projective;
+type Scalar = starknet_curve::Fr;
+
+// Instantiate concrete type for our card protocol
+type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+type Card = discrete_log_cards::Card<Curve>;
+type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+type MaskingProof = discrete_log_cards::MaskingProof<Curve>;
+type PublicKey = discrete_log_cards::PublicKey<Curve>;
+type PlayerSecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
+type RevealToken = discrete_log_cards::RevealToken<Curve>;
+type RevealProof = discrete_log_cards::RevealProof<Curve>;
+
+#[derive(Parser)]
+#[clap(
+    name = "holdem-player",
+    about = "Play a hand of Texas Hold'em through a relay"
+)]
+struct Cli {
+    /// Address of the relay
+    #[clap(long)]
+    relay: String,
+    /// Number of players at the table
+    #[clap(long)]
+    players: usize,
+    /// Index of this player in the turn order
+    #[clap(long)]
+    index: usize,
+    /// 32-byte seed shared by all the players of the hand, hex-encoded
+    #[clap(long)]
+    seed: String,
+}
+
+/// A message of the hand
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    /// Announce a key, with a proof of ownership that cannot be bound to the game yet
+    Announce,
+    /// Register for the game once the keys of all the players are known
+    Register,
+    /// Mask the open card with the given id
+    Mask(usize),
+    Shuffle,
+    /// Reveal token for the card at the given index of the final deck
+    Reveal(usize),
+}
+
+/// Index in the final deck of the hole cards of `player`, dealt one at a time to each player in turn
+fn hole_cards(num_players: usize, player: usize) -> Vec<usize> {
+    (0..HOLE_CARDS)
+        .map(|round| round * num_players + player)
+        .collect()
+}
+
+/// Index in the final deck of the flop, turn and river, each street coming after a burn card
+fn board_cards(num_players: usize) -> Vec<usize> {
+    let burn = HOLE_CARDS * num_players;
+    vec![burn + 1, burn + 2, burn + 3, burn + 5, burn + 7]
+}
+
+/// Messages of a hand in the order in which they are posted, with the index of their sender
+fn schedule(num_players: usize) -> Vec<(usize, Step)> {
+    let players = 0..num_players;
+    let mut steps = Vec::new();
+
+    steps.extend(players.clone().map(|player| (player, Step::Announce)));
+    steps.extend(players.clone().map(|player| (player, Step::Register)));
+    steps.extend((0..DECK_SIZE).map(|card_id| (DEALER, Step::Mask(card_id))));
+    steps.extend(players.clone().map(|player| (player, Step::Shuffle)));
+
+    // Everybody but their owner reveals the hole cards to the owner
+    for owner in players.clone() {
+        for card_index in hole_cards(num_players, owner) {
+            steps.extend(
+                players
+                    .clone()
+                    .filter(|&player| player != owner)
+                    .map(|player| (player, Step::Reveal(card_index))),
+            );
+        }
+    }
+    for card_index in board_cards(num_players) {
+        steps.extend(
+            players
+                .clone()
+                .map(|player| (player, Step::Reveal(card_index))),
+        );
+    }
+    // Owners show their hole cards at the showdown
+    for owner in players {
+        steps.extend(
+            hole_cards(num_players, owner)
+                .into_iter()
+                .map(|card_index| (owner, Step::Reveal(card_index))),
+        );
+    }
+
+    steps
+}
+
+fn ids(cards: &[usize]) -> String {
+    cards
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The hand as seen by one player
+struct Hand {
+    index: usize,
+    num_players: usize,
+    name: Vec<u8>,
+    pk: PublicKey,
+    sk: PlayerSecretKey,
+    context: MessageContext,
+    session: GameSession<Curve>,
+    /// Open cards, indexed by card id
+    encoding: Vec<Card>,
+    /// Keys and names announced by the players, in turn order
+    keys: Vec<PublicKey>,
+    names: Vec<Vec<u8>>,
+    /// Masked cards received from the dealer so far
+    initial_deck: Vec<(Card, MaskedCard, MaskingProof)>,
+    /// Hole cards of this player, seen in private
+    hole_cards: Vec<usize>,
+    board: Vec<usize>,
+    /// Hole cards shown by each player
+    shown: Vec<Vec<usize>>,
+}
+
+impl Hand {
+    fn new<R: Rng>(
+        rng: &mut R,
+        seed: [u8; 32],
+        index: usize,
+        num_players: usize,
+    ) -> anyhow::Result<Self> {
+        // Everything public is derived from the seed, in the same order by every player
+        let seeded = &mut ChaCha20Rng::from_seed(seed);
+        let pp = Table::<Curve>::setup(seeded)?;
+        let session_id = seeded.gen::<[u8; 32]>();
+        let encoding = (0..DECK_SIZE).map(|_| Card::rand(seeded)).collect();
+
+        let context = MessageContext::new(&pp, session_id, num_players as u32)?;
+        let (pk, sk) = CardProtocol::player_keygen(rng, &pp)?;
+
+        Ok(Self {
+            index,
+            num_players,
+            name: format!("player {}", index).into_bytes(),
+            pk,
+            sk,
+            context,
+            session: GameSession::new(pp),
+            encoding,
+            keys: Vec::new(),
+            names: Vec::new(),
+            initial_deck: Vec::new(),
+            hole_cards: Vec::new(),
+            board: Vec::new(),
+            shown: vec![Vec::new(); num_players],
+        })
+    }
+
+    fn shared_key(&self) -> anyhow::Result<PublicKey> {
+        self.session
+            .shared_key()
+            .copied()
+            .ok_or_else(|| anyhow!("the aggregate key is not known yet"))
+    }
+
+    /// Card id of an open card
+    fn decode(&self, card: &Card) -> anyhow::Result<usize> {
+        self.encoding
+            .iter()
+            .position(|c| c == card)
+            .ok_or_else(|| anyhow!("unknown card"))
+    }
+
+    /// Signed message of this player for `step`
+    fn message<R: Rng>(&self, rng: &mut R, step: Step) -> anyhow::Result<Vec<u8>> {
+        let pp = self.session.parameters();
+
+        let message = match step {
+            Step::Announce => Message::KeyOwnership {
+                pk: self.pk,
+                proof: CardProtocol::prove_key_ownership(rng, pp, &self.pk, &self.sk, &self.name)?,
+                player_public_info: self.name.clone(),
+            },
+            Step::Register => {
+                let participants_digest =
+                    CardProtocol::participants_digest(self.keys.iter().zip(self.names.iter()))?;
+                Message::KeyOwnership {
+                    pk: self.pk,
+                    proof: CardProtocol::prove_key_ownership_in_game(
+                        rng,
+                        pp,
+                        &self.pk,
+                        &self.sk,
+                        &self.name,
+                        &participants_digest,
+                    )?,
+                    player_public_info: self.name.clone(),
+                }
+            }
+            Step::Mask(card_id) => {
+                let card = self.encoding[card_id];
+                let (masked_card, proof) = CardProtocol::mask(
+                    rng,
+                    pp,
+                    &self.shared_key()?,
+                    &card,
+                    &Witness::new(Scalar::one()),
+                )?;
+                Message::Mask {
+                    card,
+                    masked_card,
+                    proof,
+                }
+            }
+            Step::Shuffle => {
+                let deck = self.session.deck();
+                let permutation = Permutation::new(rng, deck.len());
+                let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, deck.len());
+                let (deck, proof) = CardProtocol::shuffle_and_remask(
+                    rng,
+                    pp,
+                    &self.shared_key()?,
+                    deck,
+                    &masking_factors,
+                    &permutation,
+                )?;
+                Message::Shuffle { deck, proof }
+            }
+            Step::Reveal(card_index) => {
+                let (token, proof) = CardProtocol::compute_reveal_token(
+                    rng,
+                    pp,
+                    &self.sk,
+                    &self.pk,
+                    &self.session.deck()[card_index],
+                )?;
+                Message::Reveal {
+                    card_index: card_index as u64,
+                    token,
+                    proof,
+                }
+            }
+        };
+
+        Ok(self
+            .context
+            .sign(rng, pp, self.index as u32, &message, &self.pk, &self.sk)?)
+    }
+
+    /// Check the message posted by `sender` for `step` and apply it
+    fn receive<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        sender: usize,
+        step: Step,
+        bytes: &[u8],
+    ) -> anyhow::Result<()> {
+        let pp = self.session.parameters();
+        let (_, message) = match step {
+            Step::Announce => self.context.open_key_announcement(pp, bytes, &self.keys)?,
+            _ => self
+                .context
+                .open_in_turn(pp, bytes, &self.keys, sender as u32)?,
+        };
+
+        match (step, message) {
+            (
+                Step::Announce,
+                Message::KeyOwnership {
+                    pk,
+                    proof,
+                    player_public_info,
+                },
+            ) => {
+                CardProtocol::verify_key_ownership(pp, &pk, &player_public_info, &proof)?;
+                self.keys.push(pk);
+                self.names.push(player_public_info);
+            }
+            (
+                Step::Register,
+                Message::KeyOwnership {
+                    pk,
+                    proof,
+                    player_public_info,
+                },
+            ) => {
+                ensure!(
+                    pk == self.keys[sender] && player_public_info == self.names[sender],
+                    "player {} registered another key or name than announced",
+                    sender
+                );
+
+                self.session
+                    .register_player(pk, proof, player_public_info)?;
+                if self.session.num_players() == self.num_players {
+                    self.session.aggregate_keys()?;
+                }
+            }
+            (
+                Step::Mask(card_id),
+                Message::Mask {
+                    card,
+                    masked_card,
+                    proof,
+                },
+            ) => {
+                ensure!(
+                    card == self.encoding[card_id],
+                    "the dealer masked another card than card {}",
+                    card_id
+                );
+
+                self.initial_deck.push((card, masked_card, proof));
+                if self.initial_deck.len() == DECK_SIZE {
+                    let initial_deck = std::mem::take(&mut self.initial_deck);
+                    self.session.submit_initial_deck(&initial_deck)?;
+                }
+            }
+            (Step::Shuffle, Message::Shuffle { deck, proof }) => {
+                self.session.submit_shuffle(sender, deck, &proof)?;
+                if self.session.phase() == SessionPhase::Dealing {
+                    for owner in 0..self.num_players {
+                        for card_index in hole_cards(self.num_players, owner) {
+                            self.session.deal(card_index, owner)?;
+                        }
+                    }
+                }
+            }
+            (
+                Step::Reveal(card_index),
+                Message::Reveal {
+                    card_index: index,
+                    token,
+                    proof,
+                },
+            ) if index == card_index as u64 => {
+                self.reveal(rng, sender, card_index, token, proof)?
+            }
+            (step, message) => bail!("expected {:?}, found a {} message", step, message.kind()),
+        }
+
+        Ok(())
+    }
+
+    fn reveal<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        sender: usize,
+        card_index: usize,
+        token: RevealToken,
+        proof: RevealProof,
+    ) -> anyhow::Result<()> {
+        let on_board = board_cards(self.num_players).contains(&card_index);
+
+        // The first community card comes once all hole cards are dealt, and the first hole card shown
+        // once the board is complete
+        if on_board && self.session.phase() == SessionPhase::Dealing {
+            self.look_at_hole_cards(rng)?;
+            self.session.start_play()?;
+        }
+        if !on_board && self.session.phase() == SessionPhase::Play {
+            self.session.start_showdown()?;
+        }
+
+        self.session
+            .submit_reveal(card_index, sender, token, proof)?;
+        if self.session.reveal_tokens(card_index).len() < self.num_players {
+            return Ok(());
+        }
+
+        let card_id = self.decode(&self.session.open_card(card_index)?)?;
+        if on_board {
+            self.board.push(card_id);
+        } else {
+            let owner = self
+                .session
+                .owner(card_index)
+                .ok_or_else(|| anyhow!("card {} was not dealt", card_index))?;
+            self.shown[owner].push(card_id);
+        }
+
+        Ok(())
+    }
+
+    /// Combine the tokens of the other players with our own to open our hole cards in private
+    fn look_at_hole_cards<R: Rng>(&mut self, rng: &mut R) -> anyhow::Result<()> {
+        let pp = self.session.parameters();
+
+        for card_index in hole_cards(self.num_players, self.index) {
+            let masked_card = &self.session.deck()[card_index];
+            let (token, proof) =
+                CardProtocol::compute_reveal_token(rng, pp, &self.sk, &self.pk, masked_card)?;
+            let mut decryption_key = self.session.reveal_tokens(card_index);
+            decryption_key.push((token, proof, self.pk));
+
+            let card = CardProtocol::unmask(pp, &decryption_key, masked_card)?;
+            let card_id = self.decode(&card)?;
+            self.hole_cards.push(card_id);
+        }
+        println!("hole cards: {}", ids(&self.hole_cards));
+
+        Ok(())
+    }
+
+    fn finish(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.shown[self.index] == self.hole_cards,
+            "the cards shown are not the ones dealt to us"
+        );
+
+        println!("board: {}", ids(&self.board));
+        for (player, cards) in self.shown.iter().enumerate() {
+            println!("player {} shows: {}", player, ids(cards));
+        }
+
+        let playing_cards = |cards: &[usize]| {
+            cards
+                .iter()
+                .map(|&id| {
+                    ClassicPlayingCard::from_id(id).ok_or_else(|| anyhow!("no card with id {}", id))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        let board = playing_cards(&self.board)?;
+        let hands = self
+            .shown
+            .iter()
+            .map(|cards| playing_cards(cards).map(Some))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        println!("winners: {}", ids(&evaluator::winners(&board, &hands)?));
+
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    ensure!(
+        (2..=MAX_PLAYERS).contains(&cli.players),
+        "expected 2 to {} players",
+        MAX_PLAYERS
+    );
+    ensure!(
+        cli.index < cli.players,
+        "the index of the player must be smaller than the number of players"
+    );
+    let seed: [u8; 32] = hex::decode(&cli.seed)?
+        .try_into()
+        .map_err(|_| anyhow!("the seed must be 32 bytes long"))?;
+
+    let rng = &mut thread_rng();
+    let mut hand = Hand::new(rng, seed, cli.index, cli.players)?;
+    let mut relay =
+        RelayClient::connect(&cli.relay).with_context(|| format!("connecting to {}", cli.relay))?;
+
+    for (sender, step) in schedule(cli.players) {
+        if sender == cli.index {
+            relay.send(&hand.message(rng, step)?)?;
+        }
+
+        let bytes = relay.recv()?;
+        hand.receive(rng, sender, step, &bytes)
+            .with_context(|| format!("checking {:?} of player {}", step, sender))?;
+    }
+
+    hand.finish()
+}
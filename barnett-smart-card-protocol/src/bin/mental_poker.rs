@@ -0,0 +1,663 @@
+//! Command-line access to the individual protocol steps, e.g. to inspect or re-verify the messages of a
+//! disputed game without writing any code.
+//!
+//! Every protocol object is read from and written to a file. Objects are read and written in the format
+//! selected with `--format`: raw binary (`bin`), hex-encoded binary (`hex`) or a JSON envelope recording
+//! the curve and the kind of the object along with its hex encoding (`json`). Secret keys are stored in
+//! encrypted keystores, protected by the passphrase found in the `MENTAL_POKER_PASSPHRASE` environment
+//! variable.
+//!
+//! Public parameters are derived deterministically from a seed, so that the small parameters file
+//! (curve, `m`, `n` and seed) is enough for anyone to recompute them.
+
+use barnett_smart_card_protocol::discrete_log_cards::{self, keystore, Transcript};
+use barnett_smart_card_protocol::witness::Witness;
+use barnett_smart_card_protocol::{
+    CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification, PointValidation,
+    ProtocolSetup, RevealVerification, ShuffleVerification,
+};
+
+use anyhow::{anyhow, bail, Context};
+use ark_ec::ProjectiveCurve;
+use ark_ff::UniformRand;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use clap::{ArgEnum, Parser, Subcommand};
+use proof_essentials::utils::permutation::Permutation;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde_json::json;
+use std::convert::TryInto;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PASSPHRASE_VAR: &str = "MENTAL_POKER_PASSPHRASE";
+
+/// Largest deck (m * n cards) the parameters can be derived for. Setup allocates commitment parameters
+/// for `n` cards, so a parameters file must not be able to request an arbitrary amount of memory.
+const MAX_DECK_SIZE: usize = 1 << 12;
+
+type CardProtocol<'a, C> = discrete_log_cards::DLCards<'a, C>;
+
+#[derive(Parser)]
+#[clap(
+    name = "mental-poker",
+    about = "Run and verify Barnett-Smart card protocol steps on files"
+)]
+struct Cli {
+    /// Elliptic curve the protocol runs over
+    #[clap(long, arg_enum, default_value = "starknet", global = true)]
+    curve: CurveName,
+
+    /// Encoding of the files read and written by the command
+    #[clap(long, arg_enum, default_value = "bin", global = true)]
+    format: Format,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum CurveName {
+    Starknet,
+    #[clap(name = "bls12-377")]
+    Bls12_377,
+}
+
+impl CurveName {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Starknet => "starknet",
+            Self::Bls12_377 => "bls12-377",
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum Format {
+    Bin,
+    Hex,
+    Json,
+}
+
+impl Format {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Bin => "bin",
+            Self::Hex => "hex",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Derive public parameters for decks of m * n cards from a seed (random if omitted)
+    Setup {
+        #[clap(long)]
+        m: usize,
+        #[clap(long)]
+        n: usize,
+        /// 32-byte seed, hex-encoded
+        #[clap(long)]
+        seed: Option<String>,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Generate a player key pair. The secret key is written to an encrypted keystore.
+    Keygen {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        out_pk: PathBuf,
+        #[clap(long)]
+        out_sk: PathBuf,
+    },
+    /// Prove ownership of a key, bound to the keys and names of all the participants of the game
+    ProveKey {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        pk: PathBuf,
+        #[clap(long)]
+        sk: PathBuf,
+        /// Public name of the player
+        #[clap(long)]
+        info: String,
+        /// Public key of every participant, including this player
+        #[clap(long = "participant-pk", required = true)]
+        participant_pks: Vec<PathBuf>,
+        /// Name of every participant, in the same order as the keys
+        #[clap(long = "participant-info", required = true)]
+        participant_infos: Vec<String>,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Verify the key ownership proofs of all players and compute the aggregate key
+    Aggregate {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long = "pk", required = true)]
+        pks: Vec<PathBuf>,
+        #[clap(long = "proof", required = true)]
+        proofs: Vec<PathBuf>,
+        #[clap(long = "info", required = true)]
+        infos: Vec<String>,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Mask a deck of open cards under the aggregate key
+    MaskDeck {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        shared_key: PathBuf,
+        /// Open cards to mask. A deck of random cards is sampled if omitted.
+        #[clap(long)]
+        cards: Option<PathBuf>,
+        /// Where to write the sampled cards
+        #[clap(long, required_unless_present = "cards")]
+        out_cards: Option<PathBuf>,
+        #[clap(long)]
+        out: PathBuf,
+        #[clap(long)]
+        out_proofs: PathBuf,
+    },
+    /// Shuffle and remask a deck with a random permutation
+    Shuffle {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        shared_key: PathBuf,
+        #[clap(long)]
+        deck: PathBuf,
+        #[clap(long)]
+        out: PathBuf,
+        #[clap(long)]
+        out_proof: PathBuf,
+    },
+    /// Verify a shuffle argument
+    VerifyShuffle {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        shared_key: PathBuf,
+        #[clap(long)]
+        deck: PathBuf,
+        #[clap(long)]
+        shuffled: PathBuf,
+        #[clap(long)]
+        proof: PathBuf,
+    },
+    /// Compute a reveal token, with its proof, for a card of a deck
+    RevealToken {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        pk: PathBuf,
+        #[clap(long)]
+        sk: PathBuf,
+        #[clap(long)]
+        deck: PathBuf,
+        #[clap(long)]
+        index: usize,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Verify reveal tokens and open a card of a deck
+    Unmask {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        deck: PathBuf,
+        #[clap(long)]
+        index: usize,
+        #[clap(long = "token", required = true)]
+        tokens: Vec<PathBuf>,
+        /// Open cards of the game, to report the position of the revealed card
+        #[clap(long)]
+        cards: Option<PathBuf>,
+    },
+    /// Replay and verify a game transcript
+    VerifyTranscript {
+        #[clap(long)]
+        params: PathBuf,
+        #[clap(long)]
+        transcript: PathBuf,
+    },
+}
+
+/// Reads and writes protocol objects in the selected format. Files in another format are rejected.
+struct Files {
+    curve: CurveName,
+    format: Format,
+}
+
+impl Files {
+    fn write(&self, path: &Path, kind: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let contents = match self.format {
+            Format::Bin => bytes.to_vec(),
+            Format::Hex => hex::encode(bytes).into_bytes(),
+            Format::Json => serde_json::to_vec_pretty(&json!({
+                "curve": self.curve.name(),
+                "kind": kind,
+                "data": hex::encode(bytes),
+            }))?,
+        };
+        fs::write(path, contents).with_context(|| format!("writing {}", path.display()))?;
+
+        println!("Wrote {} to {}", kind, path.display());
+        Ok(())
+    }
+
+    fn read(&self, path: &Path, kind: &str) -> anyhow::Result<Vec<u8>> {
+        let contents = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let decoding = || format!("decoding {} as {}", path.display(), self.format.name());
+
+        match self.format {
+            Format::Bin => Ok(contents),
+            Format::Hex => {
+                let text = std::str::from_utf8(&contents).with_context(decoding)?;
+                hex::decode(text.trim()).with_context(decoding)
+            }
+            Format::Json => {
+                let envelope: serde_json::Value =
+                    serde_json::from_slice(&contents).with_context(decoding)?;
+                let field = |name: &str| {
+                    envelope[name]
+                        .as_str()
+                        .ok_or_else(|| anyhow!("{}: missing field `{}`", path.display(), name))
+                };
+                if field("curve")? != self.curve.name() {
+                    bail!(
+                        "{}: expected an object over {}, found {}",
+                        path.display(),
+                        self.curve.name(),
+                        field("curve")?
+                    );
+                }
+                if field("kind")? != kind {
+                    bail!(
+                        "{}: expected {}, found {}",
+                        path.display(),
+                        kind,
+                        field("kind")?
+                    );
+                }
+                hex::decode(field("data")?).with_context(decoding)
+            }
+        }
+    }
+
+    fn write_object<T: CanonicalSerialize>(
+        &self,
+        path: &Path,
+        kind: &str,
+        object: &T,
+    ) -> anyhow::Result<()> {
+        let mut bytes = Vec::new();
+        object.serialize(&mut bytes)?;
+        self.write(path, kind, &bytes)
+    }
+
+    fn read_object<T: CanonicalDeserialize>(&self, path: &Path, kind: &str) -> anyhow::Result<T> {
+        let bytes = self.read(path, kind)?;
+        T::deserialize(&bytes[..]).with_context(|| format!("decoding {}", path.display()))
+    }
+}
+
+/// Reject deck dimensions that are empty or larger than `MAX_DECK_SIZE`
+fn check_deck_dimensions(m: usize, n: usize) -> anyhow::Result<()> {
+    match m.checked_mul(n) {
+        Some(size) if size > 0 && size <= MAX_DECK_SIZE => Ok(()),
+        _ => bail!(
+            "decks of {} * {} cards are not supported (at most {} cards)",
+            m,
+            n,
+            MAX_DECK_SIZE
+        ),
+    }
+}
+
+/// The public parameters are recomputed from (m, n, seed) whenever they are needed
+fn write_params(
+    files: &Files,
+    path: &Path,
+    m: usize,
+    n: usize,
+    seed: &[u8; 32],
+) -> anyhow::Result<()> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(m as u64).to_le_bytes());
+    bytes.extend_from_slice(&(n as u64).to_le_bytes());
+    bytes.extend_from_slice(seed);
+    files.write(path, "parameters", &bytes)
+}
+
+fn read_params<C: ProjectiveCurve>(
+    files: &Files,
+    path: &Path,
+) -> anyhow::Result<discrete_log_cards::Parameters<C>> {
+    let bytes = files.read(path, "parameters")?;
+    if bytes.len() != 48 {
+        bail!("{}: malformed parameters file", path.display());
+    }
+
+    let m = u64::from_le_bytes(bytes[..8].try_into()?) as usize;
+    let n = u64::from_le_bytes(bytes[8..16].try_into()?) as usize;
+    let seed: [u8; 32] = bytes[16..].try_into()?;
+    check_deck_dimensions(m, n).with_context(|| format!("loading {}", path.display()))?;
+
+    Ok(CardProtocol::<C>::setup(
+        &mut ChaCha20Rng::from_seed(seed),
+        m,
+        n,
+    )?)
+}
+
+fn passphrase() -> anyhow::Result<String> {
+    std::env::var(PASSPHRASE_VAR)
+        .with_context(|| format!("the {} environment variable must be set", PASSPHRASE_VAR))
+}
+
+fn read_public_key<C: ProjectiveCurve>(
+    files: &Files,
+    path: &Path,
+) -> anyhow::Result<discrete_log_cards::PublicKey<C>> {
+    let bytes = files.read(path, "public key")?;
+    CardProtocol::<C>::deserialize_public_key(&bytes[..])
+        .with_context(|| format!("decoding {}", path.display()))
+}
+
+fn read_deck<C: ProjectiveCurve>(
+    files: &Files,
+    path: &Path,
+) -> anyhow::Result<Vec<discrete_log_cards::MaskedCard<C>>> {
+    let bytes = files.read(path, "deck")?;
+    CardProtocol::<C>::deserialize_deck(&bytes[..])
+        .with_context(|| format!("decoding {}", path.display()))
+}
+
+fn read_secret_key<C: ProjectiveCurve>(
+    path: &Path,
+) -> anyhow::Result<discrete_log_cards::PlayerSecretKey<C>> {
+    keystore::load_secret_key(path, passphrase()?.as_bytes())
+        .with_context(|| format!("opening keystore {}", path.display()))
+}
+
+fn run<C: ProjectiveCurve>(files: &Files, command: Command) -> anyhow::Result<()> {
+    let rng = &mut thread_rng();
+
+    match command {
+        Command::Setup { m, n, seed, out } => {
+            check_deck_dimensions(m, n)?;
+            let seed = match seed {
+                Some(seed) => hex::decode(seed)?
+                    .try_into()
+                    .map_err(|_| anyhow!("the seed must be 32 bytes long"))?,
+                None => rng.gen::<[u8; 32]>(),
+            };
+            write_params(files, &out, m, n, &seed)?;
+        }
+
+        Command::Keygen {
+            params,
+            out_pk,
+            out_sk,
+        } => {
+            let pp = read_params::<C>(files, &params)?;
+            let (pk, sk) = CardProtocol::<C>::player_keygen(rng, &pp)?;
+            keystore::save_secret_key(
+                rng,
+                &out_sk,
+                &sk,
+                passphrase()?.as_bytes(),
+                &keystore::KdfParams::default(),
+            )?;
+            println!("Wrote secret key to {}", out_sk.display());
+            files.write_object(&out_pk, "public key", &pk)?;
+        }
+
+        Command::ProveKey {
+            params,
+            pk,
+            sk,
+            info,
+            participant_pks,
+            participant_infos,
+            out,
+        } => {
+            if participant_pks.len() != participant_infos.len() {
+                bail!("every participant needs both a public key and a name");
+            }
+
+            let pp = read_params::<C>(files, &params)?;
+            let pk = read_public_key::<C>(files, &pk)?;
+            let sk = read_secret_key::<C>(&sk)?;
+
+            let participants = participant_pks
+                .iter()
+                .zip(participant_infos.iter())
+                .map(|(path, info)| {
+                    Ok((read_public_key::<C>(files, path)?, info.as_bytes().to_vec()))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let participants_digest = CardProtocol::<C>::participants_digest(
+                participants.iter().map(|(pk, info)| (pk, info)),
+            )?;
+
+            let proof = CardProtocol::<C>::prove_key_ownership_in_game(
+                rng,
+                &pp,
+                &pk,
+                &sk,
+                &info.as_bytes().to_vec(),
+                &participants_digest,
+            )?;
+            files.write_object(&out, "key ownership proof", &proof)?;
+        }
+
+        Command::Aggregate {
+            params,
+            pks,
+            proofs,
+            infos,
+            out,
+        } => {
+            if pks.len() != proofs.len() || pks.len() != infos.len() {
+                bail!("every player needs a public key, a proof and a name");
+            }
+
+            let pp = read_params::<C>(files, &params)?;
+            let players = pks
+                .iter()
+                .zip(proofs.iter())
+                .zip(infos.iter())
+                .map(|((pk, proof), info)| {
+                    Ok((
+                        read_public_key::<C>(files, pk)?,
+                        files.read_object::<discrete_log_cards::KeyOwnershipProof<C>>(
+                            proof,
+                            "key ownership proof",
+                        )?,
+                        info.as_bytes().to_vec(),
+                    ))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let shared_key = CardProtocol::<C>::compute_aggregate_key(&pp, &players)?;
+            files.write_object(&out, "public key", &shared_key)?;
+        }
+
+        Command::MaskDeck {
+            params,
+            shared_key,
+            cards,
+            out_cards,
+            out,
+            out_proofs,
+        } => {
+            let pp = read_params::<C>(files, &params)?;
+            let shared_key = read_public_key::<C>(files, &shared_key)?;
+
+            let cards = match cards {
+                Some(path) => {
+                    files.read_object::<Vec<discrete_log_cards::Card<C>>>(&path, "cards")?
+                }
+                None => (0..pp.deck_size())
+                    .map(|_| discrete_log_cards::Card::<C>::rand(rng))
+                    .collect(),
+            };
+            if let Some(path) = out_cards {
+                files.write_object(&path, "cards", &cards)?;
+            }
+
+            let (deck, proofs): (Vec<_>, Vec<_>) = cards
+                .iter()
+                .map(|card| {
+                    let alpha = Witness::new(C::ScalarField::rand(rng));
+                    CardProtocol::<C>::mask(rng, &pp, &shared_key, card, &alpha)
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .unzip();
+
+            files.write_object(&out, "deck", &deck)?;
+            files.write_object(&out_proofs, "masking proofs", &proofs)?;
+        }
+
+        Command::Shuffle {
+            params,
+            shared_key,
+            deck,
+            out,
+            out_proof,
+        } => {
+            let pp = read_params::<C>(files, &params)?;
+            let shared_key = read_public_key::<C>(files, &shared_key)?;
+            let deck = read_deck::<C>(files, &deck)?;
+
+            let permutation = Permutation::new(rng, deck.len());
+            let masking_factors: Witness<Vec<C::ScalarField>> = Witness::sample(rng, deck.len());
+            let (shuffled, proof) = CardProtocol::<C>::shuffle_and_remask(
+                rng,
+                &pp,
+                &shared_key,
+                &deck,
+                &masking_factors,
+                &permutation,
+            )?;
+
+            files.write_object(&out, "deck", &shuffled)?;
+            files.write_object(&out_proof, "shuffle proof", &proof)?;
+        }
+
+        Command::VerifyShuffle {
+            params,
+            shared_key,
+            deck,
+            shuffled,
+            proof,
+        } => {
+            let pp = read_params::<C>(files, &params)?;
+            let shared_key = read_public_key::<C>(files, &shared_key)?;
+            let deck = read_deck::<C>(files, &deck)?;
+            let shuffled = read_deck::<C>(files, &shuffled)?;
+            let proof = files
+                .read_object::<discrete_log_cards::ShuffleProof<C>>(&proof, "shuffle proof")?;
+
+            CardProtocol::<C>::verify_shuffle(&pp, &shared_key, &deck, &shuffled, &proof)?;
+            println!("Shuffle is valid");
+        }
+
+        Command::RevealToken {
+            params,
+            pk,
+            sk,
+            deck,
+            index,
+            out,
+        } => {
+            let pp = read_params::<C>(files, &params)?;
+            let pk = read_public_key::<C>(files, &pk)?;
+            let sk = read_secret_key::<C>(&sk)?;
+            let deck = read_deck::<C>(files, &deck)?;
+            let masked_card = deck
+                .get(index)
+                .ok_or_else(|| anyhow!("no card at index {} of the deck", index))?;
+
+            let (token, proof) =
+                CardProtocol::<C>::compute_reveal_token(rng, &pp, &sk, &pk, masked_card)?;
+
+            // A reveal token file holds the token, its proof and the key of its issuer
+            let mut bytes = Vec::new();
+            token.serialize(&mut bytes)?;
+            proof.serialize(&mut bytes)?;
+            pk.serialize(&mut bytes)?;
+            files.write(&out, "reveal token", &bytes)?;
+        }
+
+        Command::Unmask {
+            params,
+            deck,
+            index,
+            tokens,
+            cards,
+        } => {
+            let pp = read_params::<C>(files, &params)?;
+            let deck = read_deck::<C>(files, &deck)?;
+            let masked_card = deck
+                .get(index)
+                .ok_or_else(|| anyhow!("no card at index {} of the deck", index))?;
+
+            let tokens = tokens
+                .iter()
+                .map(|path| {
+                    let bytes = files.read(path, "reveal token")?;
+                    let mut reader = &bytes[..];
+                    let token = CardProtocol::<C>::deserialize_reveal_token(&mut reader)?;
+                    let proof = discrete_log_cards::RevealProof::<C>::deserialize(&mut reader)?;
+                    let pk = CardProtocol::<C>::deserialize_public_key(&mut reader)?;
+                    Ok((token, proof, pk))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let card = CardProtocol::<C>::unmask(&pp, tokens.iter(), masked_card)?;
+
+            let mut bytes = Vec::new();
+            card.serialize(&mut bytes)?;
+            println!("Card: {}", hex::encode(bytes));
+
+            if let Some(path) = cards {
+                let cards =
+                    files.read_object::<Vec<discrete_log_cards::Card<C>>>(&path, "cards")?;
+                match cards.iter().position(|c| *c == card) {
+                    Some(position) => println!("Card number {} of {}", position, path.display()),
+                    None => bail!("the card does not belong to {}", path.display()),
+                }
+            }
+        }
+
+        Command::VerifyTranscript { params, transcript } => {
+            let pp = read_params::<C>(files, &params)?;
+            let transcript: Transcript<C> = files.read_object(&transcript, "transcript")?;
+
+            discrete_log_cards::verify_transcript(&pp, &transcript)?;
+            println!("Transcript is valid ({} steps)", transcript.steps().len());
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let files = Files {
+        curve: cli.curve,
+        format: cli.format,
+    };
+
+    match cli.curve {
+        CurveName::Starknet => run::<starknet_curve::Projective>(&files, cli.command),
+        CurveName::Bls12_377 => run::<ark_bls12_377::G1Projective>(&files, cli.command),
+    }
+}
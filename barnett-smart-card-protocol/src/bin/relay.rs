@@ -0,0 +1,27 @@
+//! Relay for games played over TCP: a bulletin board forwarding every message posted by a player to all
+//! the players, in the same order for everybody. See the `holdem-player` binary for a player.
+
+use barnett_smart_card_protocol::net::Relay;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(
+    name = "relay",
+    about = "Forward the messages of the players of a game"
+)]
+struct Cli {
+    /// Address to listen on. Port 0 picks a free port, which is printed on startup.
+    #[clap(long, default_value = "127.0.0.1:7878")]
+    listen: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    let relay = Relay::bind(&cli.listen)?;
+    println!("Listening on {}", relay.local_addr()?);
+    relay.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Deterministic derivation of per-game secret keys from a single master seed.
+//!
+//! The secret key for a game is `PRF(seed, game_id)` reduced into the scalar field, where the PRF is
+//! Blake2b over a domain-separated, length-prefixed encoding of its inputs. As long as the seed stays
+//! secret, the keys of two different games are computationally independent: the public keys a player
+//! uses at different tables cannot be linked to each other or to the seed.
+
+use ark_ff::PrimeField;
+use ark_std::rand::Rng;
+use ark_std::Zero;
+use blake2::{Blake2b, Digest};
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
+
+const GAME_KEY_DERIVATION_SEED: &[u8] = b"Game Key Derivation";
+
+/// Length in bytes of a master seed
+pub const MASTER_SEED_LEN: usize = 32;
+
+/// Root secret from which all of a player's game keys are derived. Backing up the seed is enough to
+/// recover the keys of every game. Like `PlayerSecretKey`, the seed is wiped from memory when dropped,
+/// is redacted from `Debug` output and cannot be cloned.
+pub struct MasterSeed([u8; MASTER_SEED_LEN]);
+
+impl MasterSeed {
+    pub fn new(seed: [u8; MASTER_SEED_LEN]) -> Self {
+        Self(seed)
+    }
+
+    /// Sample a fresh seed
+    pub fn generate<R: Rng>(rng: &mut R) -> Self {
+        let mut seed = [0u8; MASTER_SEED_LEN];
+        rng.fill_bytes(&mut seed);
+
+        Self(seed)
+    }
+
+    pub fn expose_secret(&self) -> &[u8; MASTER_SEED_LEN] {
+        &self.0
+    }
+
+    /// Derive the secret scalar for the game identified by `game_id`.
+    pub fn derive_scalar<F: PrimeField>(&self, game_id: &[u8]) -> F {
+        // Retry with a counter in the (negligible) event of deriving zero
+        let mut counter = 0u64;
+        loop {
+            let mut hasher = Blake2b::new();
+            hasher.update(GAME_KEY_DERIVATION_SEED);
+            hasher.update(&(self.0.len() as u64).to_le_bytes());
+            hasher.update(&self.0);
+            hasher.update(&(game_id.len() as u64).to_le_bytes());
+            hasher.update(game_id);
+            hasher.update(&counter.to_le_bytes());
+
+            // 64 bytes of output keep the bias of the modular reduction negligible
+            let digest = Zeroizing::new(hasher.finalize().to_vec());
+            let scalar = F::from_le_bytes_mod_order(&digest);
+
+            if !scalar.is_zero() {
+                return scalar;
+            }
+            counter += 1;
+        }
+    }
+}
+
+impl Zeroize for MasterSeed {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for MasterSeed {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl fmt::Debug for MasterSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterSeed(<redacted>)")
+    }
+}
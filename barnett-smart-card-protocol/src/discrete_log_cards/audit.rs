@@ -0,0 +1,447 @@
+use crate::discrete_log_cards::{
+    Card, DLCards, MaskedCard, MaskingProof, Parameters, PlayerSecretKey, PublicKey, RevealProof,
+    RevealToken, ShuffleProof,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::{MaskingVerification, RevealVerification, ShuffleVerification};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::PrimeField;
+use ark_std::Zero;
+use proof_essentials::homomorphic_encryption::el_gamal;
+use std::collections::HashMap;
+use zeroize::Zeroizing;
+
+/// Reveal tokens published for the card at `card_index` of the final deck. Tokens may come from any
+/// subset of the players (e.g. all but the recipient of a private card).
+pub struct RevealRecord<C: ProjectiveCurve> {
+    pub card_index: usize,
+    pub tokens: Vec<(RevealToken<C>, RevealProof<C>, PublicKey<C>)>,
+}
+
+/// The public messages of a hand that are needed to audit it once the players disclose their keys.
+pub struct GameRecord<C: ProjectiveCurve> {
+    /// Public keys of the players, in turn order
+    pub players: Vec<PublicKey<C>>,
+    /// The open cards, their initial masking and the corresponding proofs
+    pub masked_deck: Vec<(Card<C>, MaskedCard<C>, MaskingProof<C>)>,
+    /// The output of every shuffle and its proof, in turn order
+    pub shuffles: Vec<(Vec<MaskedCard<C>>, ShuffleProof<C>)>,
+    pub reveals: Vec<RevealRecord<C>>,
+}
+
+/// An inconsistency found while auditing a game.
+#[derive(Debug, PartialEq)]
+pub enum AuditFinding {
+    /// The secret key disclosed by `player` does not match their public key
+    SecretKeyMismatch { player: usize },
+    /// A recorded proof does not verify. The error context locates the faulty step.
+    InvalidProof(CardProtocolError),
+    /// The initially masked `card` does not decrypt to the announced open card
+    MaskingMismatch { card: usize },
+    /// The deck was shuffled `found` times instead of once by each of the `expected` players
+    ShuffleCountMismatch { expected: usize, found: usize },
+    /// The shuffle performed by `player` changed the content of the deck: its output holds neither the
+    /// cards of the initial deck nor the cards it was given
+    ShuffleContentMismatch { player: usize },
+    /// The reveal at index `reveal` refers to a card outside of the deck
+    CardIndexOutOfRange { reveal: usize },
+    /// A token of the reveal at index `reveal` was issued for a key that is not part of the game
+    UnknownRevealer { reveal: usize, token: usize },
+    /// The token issued by `player` for the reveal at index `reveal` is not the one their key yields
+    RevealTokenMismatch { reveal: usize, player: usize },
+}
+
+/// Outcome of an audit.
+#[derive(Debug, PartialEq)]
+pub struct AuditReport<C: ProjectiveCurve> {
+    /// The open card at every position of the final deck. Empty if some player disclosed a wrong key,
+    /// in which case the deck cannot be decrypted.
+    pub final_deck: Vec<Card<C>>,
+    pub findings: Vec<AuditFinding>,
+}
+
+impl<C: ProjectiveCurve> AuditReport<C> {
+    /// Whether the game was played honestly.
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Audit a finished game after every player disclosed their secret key. Each key is checked against
+/// the public key of its owner, all proofs of the record are verified and, when all keys are correct,
+/// every masked card is decrypted to check that the masking, each shuffle and each reveal token are
+/// consistent with the cards actually dealt. Unlike the `Verifier`, the audit does not stop at the first
+/// problem but reports all of them.
+pub fn audit_game<C: ProjectiveCurve>(
+    pp: &Parameters<C>,
+    record: &GameRecord<C>,
+    disclosed_keys: &[PlayerSecretKey<C>],
+) -> Result<AuditReport<C>, CardProtocolError> {
+    if disclosed_keys.len() != record.players.len() {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: format!(
+                "{} players but {} disclosed keys",
+                record.players.len(),
+                disclosed_keys.len()
+            ),
+            context: ErrorContext::default(),
+        });
+    }
+
+    let mut findings = Vec::new();
+
+    let generator = pp.enc_parameters.generator;
+    let mut keys_match = true;
+    for (i, (pk, sk)) in record.players.iter().zip(disclosed_keys).enumerate() {
+        if generator.mul(sk.expose_secret().into_repr()).into_affine() != *pk {
+            findings.push(AuditFinding::SecretKeyMismatch { player: i });
+            keys_match = false;
+        }
+    }
+
+    let shared_key = record
+        .players
+        .iter()
+        .fold(PublicKey::<C>::zero(), |acc, pk| acc + *pk);
+
+    // Every player shuffles the deck exactly once
+    if record.shuffles.len() != record.players.len() {
+        findings.push(AuditFinding::ShuffleCountMismatch {
+            expected: record.players.len(),
+            found: record.shuffles.len(),
+        });
+    }
+
+    // Verify every proof of the record
+    for (i, (card, masked, proof)) in record.masked_deck.iter().enumerate() {
+        if let Err(e) = DLCards::<C>::verify_mask(pp, &shared_key, card, masked, proof) {
+            findings.push(AuditFinding::InvalidProof(e.with_card(i)));
+        }
+    }
+
+    let initial_deck = record
+        .masked_deck
+        .iter()
+        .map(|(_, masked, _)| *masked)
+        .collect::<Vec<_>>();
+
+    let mut current_deck = initial_deck.as_slice();
+    for (i, (shuffled, proof)) in record.shuffles.iter().enumerate() {
+        if let Err(e) = DLCards::<C>::verify_shuffle(pp, &shared_key, current_deck, shuffled, proof)
+        {
+            findings.push(AuditFinding::InvalidProof(e.with_player(i)));
+        }
+        current_deck = shuffled.as_slice();
+    }
+    let final_deck = current_deck;
+
+    for (i, reveal) in record.reveals.iter().enumerate() {
+        let masked = match final_deck.get(reveal.card_index) {
+            Some(masked) => masked,
+            None => {
+                findings.push(AuditFinding::CardIndexOutOfRange { reveal: i });
+                continue;
+            }
+        };
+
+        for (j, (token, proof, pk)) in reveal.tokens.iter().enumerate() {
+            let player = match record.players.iter().position(|p| p == pk) {
+                Some(player) => player,
+                None => {
+                    findings.push(AuditFinding::UnknownRevealer {
+                        reveal: i,
+                        token: j,
+                    });
+                    continue;
+                }
+            };
+
+            if let Err(e) = DLCards::<C>::verify_reveal(pp, pk, token, masked, proof) {
+                findings.push(AuditFinding::InvalidProof(
+                    e.with_player(player).with_card(reveal.card_index),
+                ));
+            }
+
+            if keys_match {
+                let expected = masked
+                    .0
+                    .mul(disclosed_keys[player].expose_secret().into_repr())
+                    .into_affine();
+                if token.0 != expected {
+                    findings.push(AuditFinding::RevealTokenMismatch { reveal: i, player });
+                }
+            }
+        }
+    }
+
+    // Without all the keys the cards cannot be decrypted
+    if !keys_match {
+        return Ok(AuditReport {
+            final_deck: Vec::new(),
+            findings,
+        });
+    }
+
+    let mut aggregate_sk = Zeroizing::new(C::ScalarField::zero());
+    for sk in disclosed_keys {
+        *aggregate_sk += sk.expose_secret();
+    }
+    let decrypt = |masked: &MaskedCard<C>| -> Card<C> {
+        el_gamal::Plaintext(masked.1 + -masked.0.mul(aggregate_sk.into_repr()).into_affine())
+    };
+
+    for (i, (card, masked, _)) in record.masked_deck.iter().enumerate() {
+        if decrypt(masked) != *card {
+            findings.push(AuditFinding::MaskingMismatch { card: i });
+        }
+    }
+
+    // Every shuffle must preserve the initial cards. A player who received an altered deck and shuffled
+    // it faithfully is not blamed for the cards changed before their turn.
+    let initial_content = content(initial_deck.iter().map(decrypt));
+    let mut input_content = initial_content.clone();
+    for (i, (shuffled, _)) in record.shuffles.iter().enumerate() {
+        let shuffled_content = content(shuffled.iter().map(decrypt));
+        if shuffled_content != initial_content && shuffled_content != input_content {
+            findings.push(AuditFinding::ShuffleContentMismatch { player: i });
+        }
+        input_content = shuffled_content;
+    }
+
+    Ok(AuditReport {
+        final_deck: final_deck.iter().map(decrypt).collect(),
+        findings,
+    })
+}
+
+/// Multiset of the cards in a deck
+fn content<C: ProjectiveCurve>(deck: impl Iterator<Item = Card<C>>) -> HashMap<Card<C>, usize> {
+    let mut content = HashMap::new();
+    for card in deck {
+        *content.entry(card).or_insert(0) += 1;
+    }
+
+    content
+}
+
+#[cfg(test)]
+mod test {
+    use super::content;
+    use crate::discrete_log_cards::{self, audit_game, AuditFinding, GameRecord, RevealRecord};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{CardMasking, CardReveal, DeckShuffle, KeyManagement, ProtocolSetup};
+
+    use ark_ff::UniformRand;
+    use ark_std::Zero;
+    use proof_essentials::utils::permutation::Permutation;
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+    type SecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
+
+    type Card = discrete_log_cards::Card<Curve>;
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    /// Play a hand with three players: mask a deck, let every player shuffle it and reveal the first card
+    /// to everyone. The `cheater`, if any, replaces a card of the deck before publishing their shuffle.
+    fn play_hand(
+        cheater: Option<usize>,
+    ) -> (CardParameters, GameRecord<Curve>, Vec<SecretKey>, Vec<Card>) {
+        let rng = &mut thread_rng();
+        let m = 2;
+        let n = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let (players, secret_keys): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .unzip();
+        let shared_key = players.iter().fold(PublicKey::zero(), |acc, pk| acc + *pk);
+
+        let cards = (0..m * n).map(|_| Card::rand(rng)).collect::<Vec<_>>();
+        let masked_deck = cards
+            .iter()
+            .map(|card| {
+                let alpha = Witness::new(Scalar::rand(rng));
+                let (masked, proof) =
+                    CardProtocol::mask(rng, &parameters, &shared_key, card, &alpha).unwrap();
+                (*card, masked, proof)
+            })
+            .collect::<Vec<_>>();
+
+        let mut shuffles = Vec::new();
+        let mut current_deck = masked_deck.iter().map(|x| x.1).collect::<Vec<_>>();
+        for i in 0..players.len() {
+            let permutation = Permutation::new(rng, m * n);
+            let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
+            let (mut shuffled, proof) = CardProtocol::shuffle_and_remask(
+                rng,
+                &parameters,
+                &shared_key,
+                &current_deck,
+                &masking_factors,
+                &permutation,
+            )
+            .unwrap();
+            if cheater == Some(i) {
+                shuffled[0] = shuffled[1];
+            }
+            current_deck = shuffled.clone();
+            shuffles.push((shuffled, proof));
+        }
+
+        let tokens = players
+            .iter()
+            .zip(secret_keys.iter())
+            .map(|(pk, sk)| {
+                let (token, proof) =
+                    CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &current_deck[0])
+                        .unwrap();
+                (token, proof, *pk)
+            })
+            .collect();
+
+        let record = GameRecord {
+            players,
+            masked_deck,
+            shuffles,
+            reveals: vec![RevealRecord {
+                card_index: 0,
+                tokens,
+            }],
+        };
+
+        (parameters, record, secret_keys, cards)
+    }
+
+    #[test]
+    fn honest_game() {
+        let (parameters, record, secret_keys, cards) = play_hand(None);
+
+        let report = audit_game(&parameters, &record, &secret_keys).unwrap();
+        assert!(report.is_clean());
+
+        // The final deck is a permutation of the initial cards
+        assert_eq!(
+            content(report.final_deck.into_iter()),
+            content(cards.into_iter())
+        );
+    }
+
+    #[test]
+    fn wrong_disclosed_key() {
+        let (parameters, record, mut secret_keys, _) = play_hand(None);
+
+        secret_keys[1] = SecretKey::new(Scalar::rand(&mut thread_rng()));
+
+        let report = audit_game(&parameters, &record, &secret_keys).unwrap();
+        assert_eq!(
+            report.findings,
+            vec![AuditFinding::SecretKeyMismatch { player: 1 }]
+        );
+        assert!(report.final_deck.is_empty());
+
+        assert!(audit_game(&parameters, &record, &secret_keys[1..]).is_err());
+    }
+
+    #[test]
+    fn detect_cheating() {
+        let rng = &mut thread_rng();
+
+        // Player 1 replaces a card during their shuffle, player 2 faithfully shuffles the altered deck
+        let (parameters, mut record, secret_keys, _) = play_hand(Some(1));
+
+        // Announce a different open card for the first masked card
+        record.masked_deck[0].0 = Card::rand(rng);
+
+        // Publish a bogus reveal token
+        record.reveals[0].tokens[2].0 = RevealToken::rand(rng);
+
+        let report = audit_game(&parameters, &record, &secret_keys).unwrap();
+        let findings = report.findings;
+
+        assert_eq!(findings.len(), 6);
+        assert!(matches!(
+            &findings[0],
+            AuditFinding::InvalidProof(CardProtocolError::MaskingVerificationError { context, .. })
+                if *context == ErrorContext::card(0)
+        ));
+        assert!(matches!(
+            &findings[1],
+            AuditFinding::InvalidProof(CardProtocolError::ShuffleVerificationError { context, .. })
+                if *context == ErrorContext::player(1)
+        ));
+        assert!(matches!(
+            &findings[2],
+            AuditFinding::InvalidProof(CardProtocolError::RevealVerificationError { context, .. })
+                if context.player == Some(2) && context.card == Some(0)
+        ));
+        assert_eq!(
+            findings[3..],
+            [
+                AuditFinding::RevealTokenMismatch {
+                    reveal: 0,
+                    player: 2
+                },
+                AuditFinding::MaskingMismatch { card: 0 },
+                AuditFinding::ShuffleContentMismatch { player: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_wrong_number_of_shuffles() {
+        let rng = &mut thread_rng();
+
+        // The last player skipped their shuffle
+        let (parameters, mut record, secret_keys, _) = play_hand(None);
+        record.shuffles.pop();
+
+        let report = audit_game(&parameters, &record, &secret_keys).unwrap();
+        assert_eq!(
+            report.findings[0],
+            AuditFinding::ShuffleCountMismatch {
+                expected: 3,
+                found: 2
+            }
+        );
+
+        // A valid shuffle was published after every player had shuffled
+        let (parameters, mut record, secret_keys, _) = play_hand(None);
+        let shared_key = record
+            .players
+            .iter()
+            .fold(PublicKey::zero(), |acc, pk| acc + *pk);
+        let deck = record.shuffles[2].0.clone();
+        let permutation = Permutation::new(rng, deck.len());
+        let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, deck.len());
+        let extra_shuffle = CardProtocol::shuffle_and_remask(
+            rng,
+            &parameters,
+            &shared_key,
+            &deck,
+            &masking_factors,
+            &permutation,
+        )
+        .unwrap();
+        record.shuffles.push(extra_shuffle);
+
+        let report = audit_game(&parameters, &record, &secret_keys).unwrap();
+        assert_eq!(
+            report.findings[0],
+            AuditFinding::ShuffleCountMismatch {
+                expected: 3,
+                found: 4
+            }
+        );
+    }
+}
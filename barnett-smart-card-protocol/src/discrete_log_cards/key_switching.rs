@@ -0,0 +1,735 @@
+use super::{
+    DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+    KEY_SWITCH_BATCH_SEED, KEY_SWITCH_RNG_SEED,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::{KeySwitchVerification, KeySwitching, PointValidation};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{to_bytes, PrimeField, UniformRand};
+use ark_marlin::rng::FiatShamirRng;
+use ark_serialize::CanonicalSerialize;
+use ark_std::rand::Rng;
+use ark_std::Zero;
+use blake2::Blake2s;
+use proof_essentials::homomorphic_encryption::el_gamal;
+use proof_essentials::zkp::{proofs::chaum_pedersen_dl_equality, ArgumentOfKnowledge};
+
+/// Direction of a key switch. Proofs are bound to it, so that the proof of an addition never verifies
+/// as the proof of a removal, and conversely.
+#[derive(Clone, Copy)]
+enum Switch {
+    Addition,
+    Removal,
+}
+
+impl Switch {
+    fn tag(self) -> &'static [u8] {
+        match self {
+            Self::Addition => b"Addition",
+            Self::Removal => b"Removal",
+        }
+    }
+}
+
+/// Partial decryptions `deck[i].0 * sk` of every card of `deck`, which add the key of `sk` to the
+/// aggregate key when added to the second component of the cards, and remove it when subtracted
+fn partial_decryptions<C: ProjectiveCurve>(
+    sk: &PlayerSecretKey<C>,
+    deck: &[MaskedCard<C>],
+) -> Vec<RevealToken<C>> {
+    deck.iter()
+        .map(|masked_card| {
+            el_gamal::Plaintext(
+                masked_card
+                    .0
+                    .mul(sk.expose_secret().into_repr())
+                    .into_affine(),
+            )
+        })
+        .collect()
+}
+
+/// Apply `tokens` to the second component of the cards of `deck`, adding them for an addition and
+/// subtracting them for a removal
+fn apply_tokens<C: ProjectiveCurve>(
+    deck: &[MaskedCard<C>],
+    tokens: &[RevealToken<C>],
+    switch: Switch,
+) -> Vec<MaskedCard<C>> {
+    deck.iter()
+        .zip(tokens)
+        .map(|(masked_card, token)| {
+            let second = match switch {
+                Switch::Addition => masked_card.1.into_projective() + token.0.into_projective(),
+                Switch::Removal => masked_card.1.into_projective() - token.0.into_projective(),
+            };
+            el_gamal::Ciphertext(masked_card.0, second.into_affine())
+        })
+        .collect()
+}
+
+/// Tokens `deck[i].0 * sk` turning each card of `deck` into the card of `switched_deck` at the same
+/// index when added to its second component. Switching keys must leave the first component, which
+/// carries the masking randomness, unchanged.
+fn switch_tokens<C: ProjectiveCurve>(
+    deck: &[MaskedCard<C>],
+    switched_deck: &[MaskedCard<C>],
+) -> Result<Vec<RevealToken<C>>, CardProtocolError> {
+    if deck.is_empty() {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: String::from("cannot switch the key of an empty deck"),
+            context: ErrorContext::default(),
+        });
+    }
+    if switched_deck.len() != deck.len() {
+        return Err(CardProtocolError::DeckSizeMismatch {
+            expected: deck.len(),
+            found: switched_deck.len(),
+            context: ErrorContext::default(),
+        });
+    }
+
+    deck.iter()
+        .zip(switched_deck)
+        .enumerate()
+        .map(|(i, (masked_card, switched))| {
+            DLCards::<C>::validate_masked_card(masked_card).map_err(|e| e.with_card(i))?;
+            DLCards::<C>::validate_masked_card(switched).map_err(|e| e.with_card(i))?;
+            if switched.0 != masked_card.0 {
+                return Err(CardProtocolError::ParameterMismatch {
+                    reason: String::from("key switch changed the masking of a card"),
+                    context: ErrorContext::card(i),
+                });
+            }
+
+            Ok(el_gamal::Plaintext(
+                (switched.1.into_projective() - masked_card.1.into_projective()).into_affine(),
+            ))
+        })
+        .collect()
+}
+
+/// Combine the statements `tokens[i] = deck[i].0 * sk` into a single one, with coefficients derived
+/// from all of them and from the direction of the switch, so that one Chaum-Pedersen proof covers the
+/// whole deck.
+fn batch_statement<C: ProjectiveCurve>(
+    switch: Switch,
+    pk: &PublicKey<C>,
+    deck: &[MaskedCard<C>],
+    tokens: &[RevealToken<C>],
+) -> Result<(C::Affine, C::Affine), CardProtocolError> {
+    let mut transcript = Vec::new();
+    pk.serialize(&mut transcript)?;
+    for (masked_card, token) in deck.iter().zip(tokens) {
+        masked_card.serialize(&mut transcript)?;
+        token.serialize(&mut transcript)?;
+    }
+
+    let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![
+        KEY_SWITCH_BATCH_SEED,
+        switch.tag(),
+        transcript
+    ]?);
+    let mut base = C::zero();
+    let mut token = C::zero();
+    for (masked_card, partial_token) in deck.iter().zip(tokens) {
+        let coefficient = C::ScalarField::rand(&mut fs_rng).into_repr();
+        base += masked_card.0.mul(coefficient);
+        token += partial_token.0.mul(coefficient);
+    }
+
+    Ok((base.into_affine(), token.into_affine()))
+}
+
+fn prove_switch<C: ProjectiveCurve, R: Rng>(
+    rng: &mut R,
+    switch: Switch,
+    pp: &Parameters<C>,
+    pk: &PublicKey<C>,
+    sk: &PlayerSecretKey<C>,
+    deck: &[MaskedCard<C>],
+    tokens: &[RevealToken<C>],
+) -> Result<chaum_pedersen_dl_equality::proof::Proof<C>, CardProtocolError> {
+    let (base, token) = batch_statement(switch, pk, deck, tokens)?;
+
+    // Map to Chaum-Pedersen parameters
+    let cp_parameters =
+        chaum_pedersen_dl_equality::Parameters::new(&base, &pp.enc_parameters.generator);
+
+    // Map to Chaum-Pedersen statement
+    let cp_statement = chaum_pedersen_dl_equality::Statement::new(&token, pk);
+
+    let mut fs_rng =
+        FiatShamirRng::<Blake2s>::from_seed(&to_bytes![KEY_SWITCH_RNG_SEED, switch.tag()]?);
+    let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+        rng,
+        &cp_parameters,
+        &cp_statement,
+        sk.expose_secret(),
+        &mut fs_rng,
+    )?;
+
+    Ok(proof)
+}
+
+fn verify_switch<C: ProjectiveCurve>(
+    switch: Switch,
+    pp: &Parameters<C>,
+    pk: &PublicKey<C>,
+    deck: &[MaskedCard<C>],
+    tokens: &[RevealToken<C>],
+    proof: &chaum_pedersen_dl_equality::proof::Proof<C>,
+) -> Result<(), CardProtocolError> {
+    DLCards::<C>::validate_public_key(pk)?;
+    let (base, token) = batch_statement(switch, pk, deck, tokens)?;
+
+    // Map to Chaum-Pedersen parameters
+    let cp_parameters =
+        chaum_pedersen_dl_equality::Parameters::new(&base, &pp.enc_parameters.generator);
+
+    // Map to Chaum-Pedersen statement
+    let cp_statement = chaum_pedersen_dl_equality::Statement::new(&token, pk);
+
+    let mut fs_rng =
+        FiatShamirRng::<Blake2s>::from_seed(&to_bytes![KEY_SWITCH_RNG_SEED, switch.tag()]?);
+    chaum_pedersen_dl_equality::DLEquality::verify(
+        &cp_parameters,
+        &cp_statement,
+        proof,
+        &mut fs_rng,
+    )
+    .map_err(|source| CardProtocolError::KeySwitchVerificationError {
+        source,
+        context: ErrorContext::default(),
+    })
+}
+
+impl<'a, C: ProjectiveCurve> KeySwitchVerification for DLCards<'a, C> {
+    type ZKProofKeySwitch = chaum_pedersen_dl_equality::proof::Proof<C>;
+
+    fn verify_key_addition(
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        deck: &[Self::MaskedCard],
+        switched_deck: &[Self::MaskedCard],
+        proof: &Self::ZKProofKeySwitch,
+    ) -> Result<(), CardProtocolError> {
+        let tokens = switch_tokens(deck, switched_deck)?;
+
+        verify_switch(Switch::Addition, pp, pk, deck, &tokens, proof)
+    }
+
+    fn verify_key_removal(
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        deck: &[Self::MaskedCard],
+        switched_deck: &[Self::MaskedCard],
+        proof: &Self::ZKProofKeySwitch,
+    ) -> Result<(), CardProtocolError> {
+        // The tokens removed from `deck` are the ones that would add them back to `switched_deck`
+        let tokens = switch_tokens(switched_deck, deck)?;
+
+        verify_switch(Switch::Removal, pp, pk, deck, &tokens, proof)
+    }
+}
+
+impl<'a, C: ProjectiveCurve> KeySwitching for DLCards<'a, C> {
+    fn add_key_to_deck<R: Rng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        sk: &Self::PlayerSecretKey,
+        deck: &[Self::MaskedCard],
+    ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofKeySwitch), CardProtocolError> {
+        let tokens = partial_decryptions(sk, deck);
+        let proof = prove_switch(rng, Switch::Addition, pp, pk, sk, deck, &tokens)?;
+
+        Ok((apply_tokens(deck, &tokens, Switch::Addition), proof))
+    }
+
+    fn remove_key_from_deck<R: Rng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        sk: &Self::PlayerSecretKey,
+        deck: &[Self::MaskedCard],
+    ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofKeySwitch), CardProtocolError> {
+        let tokens = partial_decryptions(sk, deck);
+        let proof = prove_switch(rng, Switch::Removal, pp, pk, sk, deck, &tokens)?;
+
+        Ok((apply_tokens(deck, &tokens, Switch::Removal), proof))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discrete_log_cards::{self, GameSession, SessionPhase};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{
+        CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification,
+        KeySwitchVerification, KeySwitching, ProtocolSetup, RevealVerification,
+    };
+
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{One, UniformRand};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+    type PlayerSecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    const M: usize = 2;
+    const N: usize = 4;
+
+    fn open(
+        parameters: &CardParameters,
+        players: &[(PublicKey, PlayerSecretKey)],
+        masked_card: &MaskedCard,
+    ) -> Card {
+        let decryption_key = players
+            .iter()
+            .map(|(pk, sk)| {
+                let (token, proof) = CardProtocol::compute_reveal_token(
+                    &mut thread_rng(),
+                    parameters,
+                    sk,
+                    pk,
+                    masked_card,
+                )
+                .unwrap();
+                (token, proof, *pk)
+            })
+            .collect::<Vec<_>>();
+
+        CardProtocol::unmask(parameters, &decryption_key, masked_card).unwrap()
+    }
+
+    /// Card `masked_card` opens to with `tokens`, whoever computed them
+    fn unmask_with(masked_card: &MaskedCard, tokens: &[RevealToken]) -> Card {
+        let card = tokens
+            .iter()
+            .fold(masked_card.1.into_projective(), |acc, token| {
+                acc - token.0.into_projective()
+            });
+
+        el_gamal::Plaintext(card.into_affine())
+    }
+
+    /// Session in which `players` registered and masked the deck, with the open cards of the deck
+    fn masked_game(
+        parameters: &CardParameters,
+        players: &[(PublicKey, PlayerSecretKey)],
+        infos: &[Vec<u8>],
+    ) -> (GameSession<Curve>, Vec<Card>) {
+        let rng = &mut thread_rng();
+        let mut serialized = Vec::new();
+        parameters.serialize(&mut serialized).unwrap();
+        let mut session = GameSession::new(CardParameters::deserialize(&serialized[..]).unwrap());
+
+        let digest =
+            CardProtocol::participants_digest(players.iter().map(|(pk, _)| pk).zip(infos)).unwrap();
+        for ((pk, sk), info) in players.iter().zip(infos) {
+            let proof =
+                CardProtocol::prove_key_ownership_in_game(rng, parameters, pk, sk, info, &digest)
+                    .unwrap();
+            session.register_player(*pk, proof, info.clone()).unwrap();
+        }
+        let shared_key = *session.aggregate_keys().unwrap();
+
+        let cards: Vec<Card> = sample_vector(rng, M * N);
+        let initial_deck = cards
+            .iter()
+            .map(|card| {
+                let (masked, proof) = CardProtocol::mask(
+                    rng,
+                    parameters,
+                    &shared_key,
+                    card,
+                    &Witness::new(Scalar::one()),
+                )
+                .unwrap();
+                (*card, masked, proof)
+            })
+            .collect::<Vec<_>>();
+        session.submit_initial_deck(&initial_deck).unwrap();
+
+        (session, cards)
+    }
+
+    fn shuffle(parameters: &CardParameters, session: &mut GameSession<Curve>, player: usize) {
+        let rng = &mut thread_rng();
+        let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, M * N);
+        let (deck, proof) = CardProtocol::shuffle_and_remask(
+            rng,
+            parameters,
+            session.shared_key().unwrap(),
+            session.deck(),
+            &masking_factors,
+            &Permutation::new(rng, M * N),
+        )
+        .unwrap();
+        session.submit_shuffle(player, deck, &proof).unwrap();
+    }
+
+    /// Session in which `players` registered and shuffled the deck, with the open cards of the deck
+    fn shuffled_game(
+        parameters: &CardParameters,
+        players: &[(PublicKey, PlayerSecretKey)],
+        infos: &[Vec<u8>],
+    ) -> (GameSession<Curve>, Vec<Card>) {
+        let (mut session, cards) = masked_game(parameters, players, infos);
+        for player in 0..players.len() {
+            shuffle(parameters, &mut session, player);
+        }
+
+        (session, cards)
+    }
+
+    #[test]
+    fn key_addition_keeps_cards() {
+        let rng = &mut thread_rng();
+        let parameters = CardProtocol::setup(rng, M, N).unwrap();
+        let mut players = (0..2)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .collect::<Vec<_>>();
+        let shared_key = players[0].0 + players[1].0;
+
+        let cards: Vec<Card> = sample_vector(rng, M * N);
+        let deck = cards
+            .iter()
+            .map(|card| {
+                let alpha = Witness::new(Scalar::rand(rng));
+                CardProtocol::mask(rng, &parameters, &shared_key, card, &alpha)
+                    .unwrap()
+                    .0
+            })
+            .collect::<Vec<_>>();
+
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let (switched_deck, proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, &pk, &sk, &deck).unwrap();
+        assert_eq!(
+            CardProtocol::verify_key_addition(&parameters, &pk, &deck, &switched_deck, &proof),
+            Ok(())
+        );
+
+        // The cards need the reveal token of the new player, and only open to the same cards with it
+        let card = open(&parameters, &players, &switched_deck[0]);
+        assert_ne!(card, cards[0]);
+        players.push((pk, sk));
+        for (switched, card) in switched_deck.iter().zip(&cards) {
+            assert_eq!(open(&parameters, &players, switched), *card);
+        }
+
+        // Undoing the addition takes the same partial decryptions, but the proof of the addition does
+        // not verify as a removal
+        assert!(matches!(
+            CardProtocol::verify_key_removal(&parameters, &pk, &switched_deck, &deck, &proof),
+            Err(CardProtocolError::KeySwitchVerificationError { .. })
+        ));
+
+        // Cards may neither be swapped nor switched with another key
+        let mut swapped_deck = switched_deck.clone();
+        swapped_deck.swap(0, 1);
+        assert_eq!(
+            CardProtocol::verify_key_addition(&parameters, &pk, &deck, &swapped_deck, &proof),
+            Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("key switch changed the masking of a card"),
+                context: ErrorContext::card(0),
+            })
+        );
+
+        let (other_pk, other_sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let (other_deck, other_proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, &other_pk, &other_sk, &deck).unwrap();
+        assert!(matches!(
+            CardProtocol::verify_key_addition(&parameters, &pk, &deck, &other_deck, &other_proof),
+            Err(CardProtocolError::KeySwitchVerificationError { .. })
+        ));
+    }
+
+    #[test]
+    fn admit_player_while_shuffling() {
+        let rng = &mut thread_rng();
+        let parameters = CardProtocol::setup(rng, M, N).unwrap();
+        let players = (0..4)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .collect::<Vec<_>>();
+        let infos = (0..4).map(|i| vec![i as u8]).collect::<Vec<_>>();
+
+        // Players 0 and 1 start the game, player 0 shuffles
+        let (mut session, cards) = masked_game(&parameters, &players[..2], &infos[..2]);
+        shuffle(&parameters, &mut session, 0);
+
+        // Player 2 joins with a proof bound to the new set of players, and switches the deck
+        let digest =
+            CardProtocol::participants_digest(players[..3].iter().map(|(pk, _)| pk).zip(&infos))
+                .unwrap();
+        let (pk, sk) = &players[2];
+        let prove_ownership = || {
+            CardProtocol::prove_key_ownership_in_game(
+                &mut thread_rng(),
+                &parameters,
+                pk,
+                sk,
+                &infos[2],
+                &digest,
+            )
+            .unwrap()
+        };
+        let deck = session.deck().to_vec();
+        let (switched_deck, switch_proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, pk, sk, &deck).unwrap();
+
+        let (_, wrong_proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, &players[0].0, &players[0].1, &deck)
+                .unwrap();
+        assert!(session
+            .admit_player(
+                *pk,
+                prove_ownership(),
+                infos[2].clone(),
+                switched_deck.clone(),
+                &wrong_proof
+            )
+            .is_err());
+        assert_eq!(session.num_players(), 2);
+
+        assert_eq!(
+            session.admit_player(
+                *pk,
+                prove_ownership(),
+                infos[2].clone(),
+                switched_deck.clone(),
+                &switch_proof
+            ),
+            Ok(2)
+        );
+        assert_eq!(
+            session.shared_key(),
+            Some(&(players[0].0 + players[1].0 + players[2].0))
+        );
+        assert_eq!(session.phase(), SessionPhase::Shuffling { round: 1 });
+
+        // The switch publishes the partial decryptions of the deck by player 2: with the reveal tokens
+        // of the others, they open the cards of the switched deck
+        let leaked = deck
+            .iter()
+            .zip(&switched_deck)
+            .map(|(masked_card, switched)| {
+                el_gamal::Plaintext(
+                    (switched.1.into_projective() - masked_card.1.into_projective()).into_affine(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut tokens = players[..2]
+            .iter()
+            .map(|(pk, sk)| {
+                CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &switched_deck[0])
+                    .unwrap()
+                    .0
+            })
+            .collect::<Vec<_>>();
+        tokens.push(leaked[0]);
+        assert!(cards.contains(&unmask_with(&switched_deck[0], &tokens)));
+
+        // The remaining shuffles remask the deck before any card is dealt, player 2 shuffling last
+        shuffle(&parameters, &mut session, 1);
+        shuffle(&parameters, &mut session, 2);
+        assert_eq!(session.phase(), SessionPhase::Dealing);
+
+        // Player 2 is dealt a card and the others publish their reveal tokens for it
+        session.deal(0, 2).unwrap();
+        for (player, (pk, sk)) in players[..2].iter().enumerate() {
+            let (token, proof) =
+                CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &session.deck()[0])
+                    .unwrap();
+            session.submit_reveal(0, player, token, proof).unwrap();
+        }
+
+        // An outsider holding every public token and the leaked partial decryptions cannot open it
+        let masked_card = session.deck()[0];
+        let public_tokens = session
+            .reveal_tokens(0)
+            .iter()
+            .map(|(token, _, _)| *token)
+            .collect::<Vec<_>>();
+        for leaked_token in &leaked {
+            let mut tokens = public_tokens.clone();
+            tokens.push(*leaked_token);
+            assert!(!cards.contains(&unmask_with(&masked_card, &tokens)));
+        }
+
+        // Player 2 opens it with their own token
+        let (pk, sk) = &players[2];
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &masked_card).unwrap();
+        let mut decryption_key = session.reveal_tokens(0);
+        decryption_key.push((token, proof, *pk));
+        let hole_card = CardProtocol::unmask(&parameters, &decryption_key, &masked_card).unwrap();
+        assert!(cards.contains(&hole_card));
+
+        // Nobody joins once the deck is final
+        let digest =
+            CardProtocol::participants_digest(players.iter().map(|(pk, _)| pk).zip(&infos))
+                .unwrap();
+        let (pk, sk) = &players[3];
+        let proof =
+            CardProtocol::prove_key_ownership_in_game(rng, &parameters, pk, sk, &infos[3], &digest)
+                .unwrap();
+        let (switched_deck, switch_proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, pk, sk, session.deck()).unwrap();
+        assert_eq!(
+            session.admit_player(*pk, proof, infos[3].clone(), switched_deck, &switch_proof),
+            Err(CardProtocolError::WrongProtocolPhase {
+                expected: String::from("shuffling"),
+                found: String::from("dealing"),
+                context: ErrorContext::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn key_removal_keeps_cards() {
+        let rng = &mut thread_rng();
+        let parameters = CardProtocol::setup(rng, M, N).unwrap();
+        let mut players = (0..3)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .collect::<Vec<_>>();
+        let shared_key = players[0].0 + players[1].0 + players[2].0;
+
+        let cards: Vec<Card> = sample_vector(rng, M * N);
+        let deck = cards
+            .iter()
+            .map(|card| {
+                let alpha = Witness::new(Scalar::rand(rng));
+                CardProtocol::mask(rng, &parameters, &shared_key, card, &alpha)
+                    .unwrap()
+                    .0
+            })
+            .collect::<Vec<_>>();
+
+        let (pk, sk) = players.pop().unwrap();
+        let (switched_deck, proof) =
+            CardProtocol::remove_key_from_deck(rng, &parameters, &pk, &sk, &deck).unwrap();
+        assert_eq!(
+            CardProtocol::verify_key_removal(&parameters, &pk, &deck, &switched_deck, &proof),
+            Ok(())
+        );
+
+        // The remaining players open the same cards on their own
+        for (switched, card) in switched_deck.iter().zip(&cards) {
+            assert_eq!(open(&parameters, &players, switched), *card);
+        }
+
+        // A removal is not an addition, and only the owner of a key can remove it
+        assert!(matches!(
+            CardProtocol::verify_key_addition(&parameters, &pk, &deck, &switched_deck, &proof),
+            Err(CardProtocolError::KeySwitchVerificationError { .. })
+        ));
+        let (other_pk, other_sk) = &players[0];
+        let (other_deck, other_proof) =
+            CardProtocol::remove_key_from_deck(rng, &parameters, other_pk, other_sk, &deck)
+                .unwrap();
+        assert!(matches!(
+            CardProtocol::verify_key_removal(&parameters, &pk, &deck, &other_deck, &other_proof),
+            Err(CardProtocolError::KeySwitchVerificationError { .. })
+        ));
+    }
+
+    #[test]
+    fn remaining_players_continue_after_departure() {
+        let rng = &mut thread_rng();
+        let parameters = CardProtocol::setup(rng, M, N).unwrap();
+        let mut players = (0..3)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .collect::<Vec<_>>();
+        let infos = (0..3).map(|i| vec![i as u8]).collect::<Vec<_>>();
+        let (mut session, cards) = shuffled_game(&parameters, &players, &infos);
+
+        // Card 0 goes to player 1, who leaves, and card 1 to player 2
+        session.deal(0, 1).unwrap();
+        session.deal(1, 2).unwrap();
+        let (pk, sk) = &players[0];
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &session.deck()[1])
+                .unwrap();
+        session.submit_reveal(1, 0, token, proof).unwrap();
+
+        let (pk, sk) = &players[1];
+        let (switched_deck, switch_proof) =
+            CardProtocol::remove_key_from_deck(rng, &parameters, pk, sk, session.deck()).unwrap();
+        assert!(matches!(
+            session.remove_player(2, switched_deck.clone(), &switch_proof),
+            Err(CardProtocolError::KeySwitchVerificationError {
+                context: ErrorContext {
+                    player: Some(2),
+                    card: None,
+                },
+                ..
+            })
+        ));
+        session
+            .remove_player(1, switched_deck, &switch_proof)
+            .unwrap();
+        players.remove(1);
+
+        assert_eq!(session.num_players(), 2);
+        assert_eq!(session.shared_key(), Some(&(players[0].0 + players[1].0)));
+        assert_eq!(session.owner(1), Some(1));
+        assert_eq!(session.phase(), SessionPhase::Dealing);
+
+        // Player 2, now 1, opens their card with the token player 0 issued before the departure
+        let (pk, sk) = &players[1];
+        let masked_card = session.deck()[1];
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &masked_card).unwrap();
+        let mut decryption_key = session.reveal_tokens(1);
+        decryption_key.push((token, proof, *pk));
+        let hole_card = CardProtocol::unmask(&parameters, &decryption_key, &masked_card).unwrap();
+        assert!(cards.contains(&hole_card));
+
+        // The remaining players open the next cards, but never the one of the player who left
+        session.start_play().unwrap();
+        for card_index in [0, 2] {
+            for (player, (pk, sk)) in players.iter().enumerate() {
+                let (token, proof) = CardProtocol::compute_reveal_token(
+                    rng,
+                    &parameters,
+                    sk,
+                    pk,
+                    &session.deck()[card_index],
+                )
+                .unwrap();
+                session
+                    .submit_reveal(card_index, player, token, proof)
+                    .unwrap();
+            }
+        }
+        assert_eq!(
+            session.open_card(0).err(),
+            Some(CardProtocolError::ParameterMismatch {
+                reason: String::from("card of a player who left"),
+                context: ErrorContext::card(0),
+            })
+        );
+        let card = session.open_card(2).unwrap();
+        assert!(cards.contains(&card));
+        assert_ne!(card, hole_card);
+    }
+}
@@ -0,0 +1,335 @@
+//! Passphrase-protected storage for a player's secret key.
+//!
+//! A keystore is laid out as a fixed-size header followed by the encrypted key:
+//!
+//! | field      | size (bytes) |
+//! |------------|--------------|
+//! | magic      | 4            |
+//! | version    | 1            |
+//! | m_cost     | 4 (LE)       |
+//! | t_cost     | 4 (LE)       |
+//! | p_cost     | 4 (LE)       |
+//! | salt       | 16           |
+//! | nonce      | 12           |
+//! | ciphertext | rest         |
+//!
+//! The encryption key is derived from the passphrase with Argon2id using the salt and costs stored in
+//! the header. The serialized secret key is encrypted with ChaCha20-Poly1305 and the whole header is
+//! authenticated as associated data, so that any modification of the file is detected on load. As the
+//! costs are read before the header can be authenticated, costs above `MAX_KDF_PARAMS` are rejected
+//! without running the key derivation. Keystore files are only readable by their owner.
+
+use super::PlayerSecretKey;
+use crate::error::CardProtocolError;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use ark_ec::ProjectiveCurve;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::Rng;
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::convert::TryInto;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 4] = b"BSKS";
+const VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+const HEADER_LEN: usize = 4 + 1 + 3 * 4 + SALT_LEN + NONCE_LEN;
+
+/// Cost parameters of the Argon2id key derivation. They are stored in the keystore header so that
+/// a keystore can always be opened with the parameters it was created with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory size in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+/// Highest costs accepted when creating or opening a keystore: 1 GiB of memory, 16 iterations and 16
+/// lanes.
+pub const MAX_KDF_PARAMS: KdfParams = KdfParams {
+    m_cost: 1024 * 1024,
+    t_cost: 16,
+    p_cost: 16,
+};
+
+impl KdfParams {
+    fn derive_key(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; KEY_LEN]>, CardProtocolError> {
+        if self.m_cost > MAX_KDF_PARAMS.m_cost
+            || self.t_cost > MAX_KDF_PARAMS.t_cost
+            || self.p_cost > MAX_KDF_PARAMS.p_cost
+        {
+            return Err(CardProtocolError::KeystoreError(format!(
+                "KDF parameters {:?} exceed the maximum of {:?}",
+                self, MAX_KDF_PARAMS
+            )));
+        }
+
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|e| CardProtocolError::KeystoreError(e.to_string()))?;
+
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase, salt, key.as_mut())
+            .map_err(|e| CardProtocolError::KeystoreError(e.to_string()))?;
+
+        Ok(key)
+    }
+}
+
+/// Encrypt `sk` under a key derived from `passphrase`.
+pub fn encrypt_secret_key<C: ProjectiveCurve, R: Rng>(
+    rng: &mut R,
+    sk: &PlayerSecretKey<C>,
+    passphrase: &[u8],
+    kdf_params: &KdfParams,
+) -> Result<Vec<u8>, CardProtocolError> {
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    header.extend_from_slice(&kdf_params.m_cost.to_le_bytes());
+    header.extend_from_slice(&kdf_params.t_cost.to_le_bytes());
+    header.extend_from_slice(&kdf_params.p_cost.to_le_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    sk.expose_secret().serialize(&mut *plaintext)?;
+
+    let key = kdf_params.derive_key(passphrase, &salt)?;
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &header,
+            },
+        )
+        .map_err(|e| CardProtocolError::KeystoreError(e.to_string()))?;
+
+    let mut keystore = header;
+    keystore.extend_from_slice(&ciphertext);
+
+    Ok(keystore)
+}
+
+/// Decrypt a keystore produced by `encrypt_secret_key`. A wrong passphrase and a modified keystore
+/// cannot be told apart and both result in a `KeystoreDecryptionError`.
+pub fn decrypt_secret_key<C: ProjectiveCurve>(
+    keystore: &[u8],
+    passphrase: &[u8],
+) -> Result<PlayerSecretKey<C>, CardProtocolError> {
+    if keystore.len() < HEADER_LEN || keystore[..4] != MAGIC[..] {
+        return Err(CardProtocolError::SerializationError(String::from(
+            "Not a player keystore",
+        )));
+    }
+
+    let (header, ciphertext) = keystore.split_at(HEADER_LEN);
+
+    let version = header[4];
+    if version != VERSION {
+        return Err(CardProtocolError::UnsupportedKeystoreVersion(version));
+    }
+
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let kdf_params = KdfParams {
+        m_cost: read_u32(5),
+        t_cost: read_u32(9),
+        p_cost: read_u32(13),
+    };
+    let salt = &header[17..17 + SALT_LEN];
+    let nonce = &header[17 + SALT_LEN..];
+
+    let key = kdf_params.derive_key(passphrase, salt)?;
+    let plaintext = Zeroizing::new(
+        ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| CardProtocolError::KeystoreDecryptionError)?,
+    );
+
+    let sk = C::ScalarField::deserialize(&plaintext[..])?;
+
+    Ok(PlayerSecretKey::new(sk))
+}
+
+/// Encrypt `sk` and write the keystore to the file at `path`, readable and writable by its owner only.
+pub fn save_secret_key<C: ProjectiveCurve, R: Rng, P: AsRef<Path>>(
+    rng: &mut R,
+    path: P,
+    sk: &PlayerSecretKey<C>,
+    passphrase: &[u8],
+    kdf_params: &KdfParams,
+) -> Result<(), CardProtocolError> {
+    let keystore = encrypt_secret_key(rng, sk, passphrase, kdf_params)?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+
+        // The mode only applies to new files: also restrict a keystore being overwritten
+        if path.as_ref().exists() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(&keystore)?;
+
+    Ok(())
+}
+
+/// Read the keystore at `path` and decrypt the secret key it contains.
+pub fn load_secret_key<C: ProjectiveCurve, P: AsRef<Path>>(
+    path: P,
+    passphrase: &[u8],
+) -> Result<PlayerSecretKey<C>, CardProtocolError> {
+    let keystore = fs::read(path)?;
+    decrypt_secret_key(&keystore, passphrase)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::discrete_log_cards;
+    use crate::{KeyManagement, ProtocolSetup};
+
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    // Keep the tests fast
+    const TEST_KDF_PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn keystore() -> (PlayerSecretKey<Curve>, Vec<u8>) {
+        let rng = &mut thread_rng();
+        let parameters = CardProtocol::setup(rng, 2, 2).unwrap();
+        let (_, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let keystore = encrypt_secret_key(rng, &sk, b"passphrase", &TEST_KDF_PARAMS).unwrap();
+
+        (sk, keystore)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (sk, keystore) = keystore();
+
+        let loaded = decrypt_secret_key::<Curve>(&keystore, b"passphrase").unwrap();
+        assert_eq!(loaded.expose_secret(), sk.expose_secret());
+
+        let rng = &mut thread_rng();
+        let path = std::env::temp_dir().join(format!("player-{}.keystore", rng.gen::<u64>()));
+        save_secret_key(rng, &path, &sk, b"passphrase", &TEST_KDF_PARAMS).unwrap();
+        let loaded = load_secret_key::<Curve, _>(&path, b"passphrase").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.expose_secret(), sk.expose_secret());
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let (_, keystore) = keystore();
+
+        assert_eq!(
+            decrypt_secret_key::<Curve>(&keystore, b"not the passphrase").unwrap_err(),
+            CardProtocolError::KeystoreDecryptionError
+        );
+    }
+
+    #[test]
+    fn tamper_detection() {
+        let (_, keystore) = keystore();
+
+        // Flipping a bit anywhere in the salt, nonce or ciphertext must be detected
+        for i in 17..keystore.len() {
+            let mut tampered = keystore.clone();
+            tampered[i] ^= 1;
+            assert_eq!(
+                decrypt_secret_key::<Curve>(&tampered, b"passphrase").unwrap_err(),
+                CardProtocolError::KeystoreDecryptionError
+            );
+        }
+
+        // Weakening the stored KDF costs is detected as well
+        let mut tampered = keystore.clone();
+        tampered[9] ^= 1;
+        assert!(decrypt_secret_key::<Curve>(&tampered, b"passphrase").is_err());
+
+        // Raising them beyond the maximum is rejected before deriving any key
+        let mut tampered = keystore.clone();
+        tampered[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            decrypt_secret_key::<Curve>(&tampered, b"passphrase"),
+            Err(CardProtocolError::KeystoreError(_))
+        ));
+
+        let mut tampered = keystore.clone();
+        tampered[9..13].copy_from_slice(&(MAX_KDF_PARAMS.t_cost + 1).to_le_bytes());
+        assert!(matches!(
+            decrypt_secret_key::<Curve>(&tampered, b"passphrase"),
+            Err(CardProtocolError::KeystoreError(_))
+        ));
+
+        let mut tampered = keystore.clone();
+        tampered[4] = VERSION + 1;
+        assert_eq!(
+            decrypt_secret_key::<Curve>(&tampered, b"passphrase").unwrap_err(),
+            CardProtocolError::UnsupportedKeystoreVersion(VERSION + 1)
+        );
+
+        assert!(matches!(
+            decrypt_secret_key::<Curve>(&keystore[..HEADER_LEN - 1], b"passphrase"),
+            Err(CardProtocolError::SerializationError(_))
+        ));
+    }
+}
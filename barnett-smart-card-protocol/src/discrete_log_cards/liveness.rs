@@ -0,0 +1,766 @@
+use crate::discrete_log_cards::message::{Message, MessageContext, SessionId};
+use crate::discrete_log_cards::{
+    DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealProof, RevealToken,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::RevealVerification;
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{One, PrimeField, UniformRand};
+use ark_std::rand::Rng;
+use ark_std::Zero;
+use std::collections::HashSet;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Time given to a player to complete a protocol step, counted from the moment the step becomes theirs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadlines {
+    /// Time given to the player expected to shuffle, from the end of the previous round
+    pub shuffle: Duration,
+    /// Time given to every player to publish their reveal token for a card, from the moment the card
+    /// is requested
+    pub reveal: Duration,
+}
+
+impl Default for Deadlines {
+    fn default() -> Self {
+        Self {
+            shuffle: Duration::from_secs(60),
+            reveal: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Protocol step a player can fail to complete in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Step {
+    /// Shuffling the deck, in the given round
+    Shuffle { round: usize },
+    /// Publishing a reveal token for the card at the given index of the final deck
+    Reveal { card_index: usize },
+}
+
+impl Step {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Shuffle { .. } => "shuffle",
+            Self::Reveal { .. } => "reveal",
+        }
+    }
+}
+
+/// A player who missed the deadline of a step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Stall {
+    pub player: usize,
+    pub step: Step,
+}
+
+/// Feldman commitments to the polynomial a player used to split their secret key among the other
+/// players. The first commitment is the public key of the player, and the number of commitments is the
+/// number of shares needed to rebuild a reveal token of the player without them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyEscrow<C: ProjectiveCurve> {
+    pub commitments: Vec<PublicKey<C>>,
+}
+
+impl<C: ProjectiveCurve> KeyEscrow<C> {
+    /// Number of shares needed to rebuild a reveal token
+    pub fn threshold(&self) -> usize {
+        self.commitments.len()
+    }
+
+    /// Public key matching the share held by the player at index `holder`
+    pub fn share_key(&self, holder: usize) -> PublicKey<C> {
+        let x = share_point::<C>(holder);
+        let mut power = C::ScalarField::one();
+        let mut share_key = C::zero();
+        for commitment in &self.commitments {
+            share_key += commitment.mul(power.into_repr());
+            power *= x;
+        }
+
+        share_key.into_affine()
+    }
+
+    /// Check the share received by the player at index `holder` against the commitments
+    pub fn verify_share(
+        &self,
+        pp: &Parameters<C>,
+        holder: usize,
+        share: &PlayerSecretKey<C>,
+    ) -> Result<(), CardProtocolError> {
+        let share_key = pp
+            .enc_parameters
+            .generator
+            .mul(share.expose_secret().into_repr())
+            .into_affine();
+        if share_key != self.share_key(holder) {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("escrowed share does not match the commitments"),
+                context: ErrorContext::player(holder),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Point at which the polynomial of an escrow is evaluated to get the share of the player at `holder`
+fn share_point<C: ProjectiveCurve>(holder: usize) -> C::ScalarField {
+    C::ScalarField::from((holder + 1) as u64)
+}
+
+/// Split the secret key of the player at index `owner` among the other players of the game, so that
+/// any `threshold` of them can compute the reveal tokens of the owner if the owner stalls. Returns the
+/// commitments to publish, and the share to send privately to each other player, with their index.
+pub fn escrow_key<C: ProjectiveCurve, R: Rng>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    sk: &PlayerSecretKey<C>,
+    owner: usize,
+    num_players: usize,
+    threshold: usize,
+) -> Result<(KeyEscrow<C>, Vec<(usize, PlayerSecretKey<C>)>), CardProtocolError> {
+    if owner >= num_players || threshold == 0 || threshold >= num_players {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: format!(
+                "cannot escrow the key of player {} with a threshold of {} among {} players",
+                owner, threshold, num_players
+            ),
+            context: ErrorContext::player(owner),
+        });
+    }
+
+    let mut coefficients = Zeroizing::new(vec![*sk.expose_secret()]);
+    coefficients.extend((1..threshold).map(|_| C::ScalarField::rand(rng)));
+
+    let commitments = coefficients
+        .iter()
+        .map(|a| pp.enc_parameters.generator.mul(a.into_repr()).into_affine())
+        .collect();
+
+    let shares = (0..num_players)
+        .filter(|&holder| holder != owner)
+        .map(|holder| {
+            let x = share_point::<C>(holder);
+            let share = coefficients
+                .iter()
+                .rev()
+                .fold(C::ScalarField::zero(), |acc, a| acc * x + a);
+            (holder, PlayerSecretKey::new(share))
+        })
+        .collect();
+
+    Ok((KeyEscrow { commitments }, shares))
+}
+
+/// Reveal token of a stalled player, interpolated from the tokens computed by `threshold` holders of
+/// shares of their key
+pub(crate) fn combine_partial_tokens<C: ProjectiveCurve>(
+    partial_tokens: &[(usize, RevealToken<C>, RevealProof<C>)],
+) -> RevealToken<C> {
+    let points = partial_tokens
+        .iter()
+        .map(|(holder, _, _)| share_point::<C>(*holder))
+        .collect::<Vec<_>>();
+
+    partial_tokens
+        .iter()
+        .enumerate()
+        .fold(RevealToken::<C>::zero(), |acc, (i, (_, token, _))| {
+            let lagrange_coefficient = points
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold(C::ScalarField::one(), |acc, (_, x)| {
+                    acc * x / (*x - points[i])
+                });
+            acc + *token * lagrange_coefficient
+        })
+}
+
+/// Open a signed `StallReport` message of the session of `context`, `players` being the keys of the
+/// players in turn order. Returns the sender and the stall they report.
+pub(crate) fn open_stall_report<C: ProjectiveCurve>(
+    context: &MessageContext,
+    pp: &Parameters<C>,
+    report: &[u8],
+    players: &[PublicKey<C>],
+) -> Result<(usize, Stall), CardProtocolError> {
+    let (header, message) = context.open(pp, report, players)?;
+    let sender = header.sender as usize;
+    let stall = match message {
+        Message::StallReport { stalled, step } => Stall {
+            player: stalled as usize,
+            step,
+        },
+        message => {
+            return Err(CardProtocolError::InvalidMessage(format!(
+                "expected a stall report, found a {} message",
+                message.kind()
+            )))
+        }
+    };
+    if stall.player >= players.len() || stall.player == sender {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: String::from("invalid stalled player"),
+            context: ErrorContext::player(sender),
+        });
+    }
+
+    Ok((sender, stall))
+}
+
+/// How a game recovered from a stalled player.
+#[derive(Clone)]
+pub enum Recovery<C: ProjectiveCurve> {
+    /// The stalled player was excluded before any card was dealt. The deck is rebuilt under
+    /// `shared_key`, the aggregate key of the remaining players.
+    Exclusion { shared_key: PublicKey<C> },
+    /// The reveal token of the stalled player for the card at `card_index` was rebuilt from the partial
+    /// tokens of the holders of shares of their key, given with the index of each holder
+    ThresholdReveal {
+        card_index: usize,
+        masked_card: MaskedCard<C>,
+        escrow: KeyEscrow<C>,
+        partial_tokens: Vec<(usize, RevealToken<C>, RevealProof<C>)>,
+        token: RevealToken<C>,
+    },
+}
+
+/// Evidence that a game went on without a stalled player. The missing message itself cannot be proven
+/// absent; the certificate carries the signed `StallReport` messages by which every other player of the
+/// session reported the stall, and lets anybody check that the recovery only removed the contribution
+/// of that player.
+#[derive(Clone)]
+pub struct AbortCertificate<C: ProjectiveCurve> {
+    /// Session the game was played in
+    pub session_id: SessionId,
+    pub stall: Stall,
+    /// Keys of all the players at the time of the stall, in turn order
+    pub players: Vec<PublicKey<C>>,
+    /// Signed reports of the stall, one by each other player
+    pub reports: Vec<Vec<u8>>,
+    pub recovery: Recovery<C>,
+}
+
+impl<C: ProjectiveCurve> AbortCertificate<C> {
+    pub fn verify(&self, pp: &Parameters<C>) -> Result<(), CardProtocolError> {
+        let stalled = self.stall.player;
+        let mismatch = |reason: &str, context| {
+            Err(CardProtocolError::ParameterMismatch {
+                reason: String::from(reason),
+                context,
+            })
+        };
+        if stalled >= self.players.len() {
+            return mismatch(
+                "stalled player is not part of the game",
+                ErrorContext::default(),
+            );
+        }
+
+        let context = MessageContext::new(pp, self.session_id, self.players.len() as u32)?;
+        let mut reporters = HashSet::new();
+        for report in &self.reports {
+            let (sender, stall) = open_stall_report(&context, pp, report, &self.players)?;
+            if stall != self.stall || !reporters.insert(sender) {
+                return mismatch("invalid stall report", ErrorContext::player(sender));
+            }
+        }
+        if reporters.len() + 1 != self.players.len() {
+            return mismatch(
+                "stall not reported by every other player",
+                ErrorContext::player(stalled),
+            );
+        }
+
+        match &self.recovery {
+            Recovery::Exclusion { shared_key } => {
+                if !matches!(self.stall.step, Step::Shuffle { .. }) {
+                    return mismatch(
+                        "players can only be excluded for missing a shuffle",
+                        ErrorContext::player(stalled),
+                    );
+                }
+
+                let remaining = self
+                    .players
+                    .iter()
+                    .enumerate()
+                    .filter(|(player, _)| *player != stalled)
+                    .fold(C::zero(), |acc, (_, pk)| acc + pk.into_projective());
+                if remaining.into_affine() != *shared_key {
+                    return mismatch(
+                        "aggregate key is not the one of the remaining players",
+                        ErrorContext::player(stalled),
+                    );
+                }
+            }
+            Recovery::ThresholdReveal {
+                card_index,
+                masked_card,
+                escrow,
+                partial_tokens,
+                token,
+            } => {
+                let context = ErrorContext {
+                    player: Some(stalled),
+                    card: Some(*card_index),
+                };
+                if self.stall.step
+                    != (Step::Reveal {
+                        card_index: *card_index,
+                    })
+                {
+                    return mismatch("stall is not about the revealed card", context);
+                }
+                if escrow.commitments.first() != Some(&self.players[stalled]) {
+                    return mismatch(
+                        "escrow is not bound to the key of the stalled player",
+                        context,
+                    );
+                }
+                if partial_tokens.len() != escrow.threshold() {
+                    return mismatch("wrong number of partial tokens", context);
+                }
+
+                let mut holders = HashSet::new();
+                for (holder, partial_token, proof) in partial_tokens {
+                    if *holder >= self.players.len()
+                        || *holder == stalled
+                        || !holders.insert(holder)
+                    {
+                        return mismatch("invalid share holder", context);
+                    }
+                    DLCards::<C>::verify_reveal(
+                        pp,
+                        &escrow.share_key(*holder),
+                        partial_token,
+                        masked_card,
+                        proof,
+                    )
+                    .map_err(|e| e.with_player(*holder).with_card(*card_index))?;
+                }
+
+                if combine_partial_tokens(partial_tokens) != *token {
+                    return mismatch("reveal token does not match the partial tokens", context);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escrow_key, Deadlines, Recovery, Stall, Step};
+    use crate::discrete_log_cards::message::{Message, MessageContext};
+    use crate::discrete_log_cards::{self, GameSession, SessionPhase};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{
+        CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification,
+        ProtocolSetup,
+    };
+
+    use ark_ff::One;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+    use rand::thread_rng;
+    use std::time::{Duration, Instant};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+    type PlayerSecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
+
+    const M: usize = 2;
+    const N: usize = 4;
+    const SESSION: [u8; 32] = [7; 32];
+
+    struct Game {
+        parameters: CardParameters,
+        session: GameSession<Curve>,
+        players: Vec<(PublicKey, PlayerSecretKey)>,
+        cards: Vec<Card>,
+    }
+
+    impl Game {
+        /// Register `num_players` players and mask the initial deck
+        fn start(num_players: usize) -> Self {
+            let rng = &mut thread_rng();
+            let parameters = CardProtocol::setup(rng, M, N).unwrap();
+            let mut serialized = Vec::new();
+            parameters.serialize(&mut serialized).unwrap();
+            let mut session =
+                GameSession::new(CardParameters::deserialize(&serialized[..]).unwrap());
+            session.set_session_id(SESSION);
+
+            let players = (0..num_players)
+                .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+                .collect::<Vec<_>>();
+            let infos = (0..num_players).map(|i| vec![i as u8]).collect::<Vec<_>>();
+            let digest =
+                CardProtocol::participants_digest(players.iter().map(|(pk, _)| pk).zip(&infos))
+                    .unwrap();
+            for ((pk, sk), info) in players.iter().zip(&infos) {
+                let proof = CardProtocol::prove_key_ownership_in_game(
+                    rng,
+                    &parameters,
+                    pk,
+                    sk,
+                    info,
+                    &digest,
+                )
+                .unwrap();
+                session.register_player(*pk, proof, info.clone()).unwrap();
+            }
+            session.aggregate_keys().unwrap();
+
+            let mut game = Self {
+                parameters,
+                session,
+                players,
+                cards: sample_vector(rng, M * N),
+            };
+            game.mask_deck();
+            game
+        }
+
+        fn mask_deck(&mut self) {
+            let rng = &mut thread_rng();
+            let shared_key = *self.session.shared_key().unwrap();
+            let initial_deck = self
+                .cards
+                .iter()
+                .map(|card| {
+                    let (masked, proof) = CardProtocol::mask(
+                        rng,
+                        &self.parameters,
+                        &shared_key,
+                        card,
+                        &Witness::new(Scalar::one()),
+                    )
+                    .unwrap();
+                    (*card, masked, proof)
+                })
+                .collect::<Vec<_>>();
+            self.session.submit_initial_deck(&initial_deck).unwrap();
+        }
+
+        fn shuffle(&mut self, player: usize) {
+            let rng = &mut thread_rng();
+            let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, M * N);
+            let (deck, proof) = CardProtocol::shuffle_and_remask(
+                rng,
+                &self.parameters,
+                self.session.shared_key().unwrap(),
+                self.session.deck(),
+                &masking_factors,
+                &Permutation::new(rng, M * N),
+            )
+            .unwrap();
+            self.session.submit_shuffle(player, deck, &proof).unwrap();
+        }
+
+        fn reveal(&mut self, card_index: usize, player: usize) {
+            let (pk, sk) = &self.players[player];
+            let (token, proof) = CardProtocol::compute_reveal_token(
+                &mut thread_rng(),
+                &self.parameters,
+                sk,
+                pk,
+                &self.session.deck()[card_index],
+            )
+            .unwrap();
+            self.session
+                .submit_reveal(card_index, player, token, proof)
+                .unwrap();
+        }
+
+        /// Signed report of `stall` by the player at index `player`
+        fn sign_report(&self, player: usize, stall: Stall) -> Vec<u8> {
+            let (pk, sk) = &self.players[player];
+            let context =
+                MessageContext::new(&self.parameters, SESSION, self.players.len() as u32).unwrap();
+            let message = Message::StallReport {
+                stalled: stall.player as u64,
+                step: stall.step,
+            };
+            context
+                .sign(
+                    &mut thread_rng(),
+                    &self.parameters,
+                    player as u32,
+                    &message,
+                    pk,
+                    sk,
+                )
+                .unwrap()
+        }
+
+        /// Every player but the stalled one reports `stall`
+        fn report(&mut self, stall: Stall) {
+            for player in (0..self.players.len()).filter(|&player| player != stall.player) {
+                let report = self.sign_report(player, stall);
+                assert_eq!(self.session.submit_stall_report(report).unwrap(), stall);
+            }
+        }
+    }
+
+    fn past(deadline: Duration) -> Instant {
+        Instant::now() + deadline + Duration::from_secs(1)
+    }
+
+    #[test]
+    fn exclude_stalled_shuffler() {
+        let mut game = Game::start(3);
+        game.shuffle(0);
+
+        // Player 1 does not shuffle in time
+        assert_eq!(game.session.expire(Instant::now()), vec![]);
+        let stall = Stall {
+            player: 1,
+            step: Step::Shuffle { round: 1 },
+        };
+        assert_eq!(
+            game.session.expire(past(Deadlines::default().shuffle)),
+            vec![stall]
+        );
+        assert_eq!(
+            game.session.expire(past(Deadlines::default().shuffle)),
+            vec![]
+        );
+        assert_eq!(game.session.stalls(), &[stall]);
+
+        // Players only report recorded stalls, and only those of other players
+        let report = game.sign_report(
+            0,
+            Stall {
+                player: 2,
+                step: Step::Shuffle { round: 1 },
+            },
+        );
+        assert!(game.session.submit_stall_report(report).is_err());
+        let report = game.sign_report(1, stall);
+        assert!(game.session.submit_stall_report(report).is_err());
+
+        // Reports are bound to the session
+        let (pk, sk) = &game.players[0];
+        let report = MessageContext::new(&game.parameters, [8; 32], 3)
+            .unwrap()
+            .sign(
+                &mut thread_rng(),
+                &game.parameters,
+                0,
+                &Message::StallReport {
+                    stalled: 1,
+                    step: stall.step,
+                },
+                pk,
+                sk,
+            )
+            .unwrap();
+        assert!(matches!(
+            game.session.submit_stall_report(report),
+            Err(CardProtocolError::MessageHeaderMismatch { .. })
+        ));
+
+        // Only a stalled player can be excluded, once every other player reported the stall
+        assert!(game.session.exclude_player(1).is_err());
+        let report = game.sign_report(0, stall);
+        game.session.submit_stall_report(report).unwrap();
+        assert!(game.session.exclude_player(1).is_err());
+        let report = game.sign_report(2, stall);
+        game.session.submit_stall_report(report).unwrap();
+        assert!(game.session.exclude_player(2).is_err());
+        let certificate = game.session.exclude_player(1).unwrap();
+        certificate.verify(&game.parameters).unwrap();
+        assert_eq!(certificate.session_id, SESSION);
+        assert_eq!(certificate.reports.len(), 2);
+
+        let shared_key = game.players[0].0 + game.players[2].0;
+        assert!(matches!(
+            certificate.recovery,
+            Recovery::Exclusion { shared_key: key } if key == shared_key
+        ));
+        assert_eq!(game.session.shared_key(), Some(&shared_key));
+        assert_eq!(game.session.phase(), SessionPhase::KeyAggregation);
+        assert_eq!(game.session.num_players(), 2);
+        assert!(game.session.stalls().is_empty());
+
+        // A certificate keeping the key of the stalled player does not verify
+        let mut forged = certificate.clone();
+        forged.recovery = Recovery::Exclusion {
+            shared_key: shared_key + game.players[1].0,
+        };
+        assert!(forged.verify(&game.parameters).is_err());
+
+        // Nor does a certificate missing a report, repeating one, or claiming another session
+        let mut forged = certificate.clone();
+        forged.reports.pop();
+        assert!(forged.verify(&game.parameters).is_err());
+        forged.reports.push(certificate.reports[0].clone());
+        assert!(forged.verify(&game.parameters).is_err());
+        let mut forged = certificate.clone();
+        forged.session_id = [8; 32];
+        assert!(forged.verify(&game.parameters).is_err());
+
+        // The remaining players play on with a new deck
+        game.players.remove(1);
+        game.mask_deck();
+        game.shuffle(0);
+        game.shuffle(1);
+        game.session.start_play().unwrap();
+        game.reveal(0, 0);
+        game.reveal(0, 1);
+        assert!(game.cards.contains(&game.session.open_card(0).unwrap()));
+    }
+
+    #[test]
+    fn threshold_reveal_for_stalled_player() {
+        let rng = &mut thread_rng();
+        let mut game = Game::start(3);
+
+        // Player 2 splits their key between players 0 and 1, who both need to help
+        let (escrow, shares) =
+            escrow_key(rng, &game.parameters, &game.players[2].1, 2, 3, 2).unwrap();
+        for (holder, share) in &shares {
+            escrow
+                .verify_share(&game.parameters, *holder, share)
+                .unwrap();
+        }
+        assert!(escrow
+            .verify_share(&game.parameters, shares[1].0, &shares[0].1)
+            .is_err());
+
+        let (wrong_escrow, _) =
+            escrow_key(rng, &game.parameters, &game.players[1].1, 2, 3, 2).unwrap();
+        assert!(game.session.submit_key_escrow(2, wrong_escrow).is_err());
+        game.session.submit_key_escrow(2, escrow.clone()).unwrap();
+
+        for player in 0..3 {
+            game.shuffle(player);
+        }
+        game.session.start_play().unwrap();
+
+        // Player 2 never reveals the card
+        game.session.request_reveal(0).unwrap();
+        game.reveal(0, 0);
+        game.reveal(0, 1);
+        let stall = Stall {
+            player: 2,
+            step: Step::Reveal { card_index: 0 },
+        };
+        assert_eq!(
+            game.session.expire(past(Deadlines::default().reveal)),
+            vec![stall]
+        );
+        assert_eq!(
+            game.session.open_card(0).err(),
+            Some(CardProtocolError::ParameterMismatch {
+                reason: String::from("missing reveal token"),
+                context: ErrorContext {
+                    player: Some(2),
+                    card: Some(0),
+                },
+            })
+        );
+
+        let masked_card = game.session.deck()[0];
+        let partial_tokens = shares
+            .iter()
+            .map(|(holder, share)| {
+                let (token, proof) = CardProtocol::compute_reveal_token(
+                    rng,
+                    &game.parameters,
+                    share,
+                    &escrow.share_key(*holder),
+                    &masked_card,
+                )
+                .unwrap();
+                (*holder, token, proof)
+            })
+            .collect::<Vec<_>>();
+
+        // Partial tokens are only accepted once the other players reported the stall
+        let (_, token, proof) = partial_tokens[0];
+        assert!(game
+            .session
+            .submit_escrowed_reveal(0, 2, 0, token, proof)
+            .is_err());
+        game.report(stall);
+
+        // The stall only unlocks the recovery of the card player 2 did not reveal
+        assert_eq!(
+            game.session
+                .submit_escrowed_reveal(1, 2, 0, token, proof)
+                .err(),
+            Some(CardProtocolError::ParameterMismatch {
+                reason: String::from("player did not stall"),
+                context: ErrorContext {
+                    player: Some(2),
+                    card: Some(1),
+                },
+            })
+        );
+
+        // A partial token checks against the share of its holder
+        assert!(game
+            .session
+            .submit_escrowed_reveal(0, 2, 1, token, proof)
+            .is_err());
+        assert!(game
+            .session
+            .submit_escrowed_reveal(0, 2, 0, token, proof)
+            .unwrap()
+            .is_none());
+        let (_, token, proof) = partial_tokens[1];
+        let certificate = game
+            .session
+            .submit_escrowed_reveal(0, 2, 1, token, proof)
+            .unwrap()
+            .unwrap();
+        certificate.verify(&game.parameters).unwrap();
+
+        let card = game.session.open_card(0).unwrap();
+        assert!(game.cards.contains(&card));
+
+        // The rebuilt token is the one player 2 would have published
+        let (expected, _) = CardProtocol::compute_reveal_token(
+            rng,
+            &game.parameters,
+            &game.players[2].1,
+            &game.players[2].0,
+            &masked_card,
+        )
+        .unwrap();
+        let mut forged = certificate.clone();
+        match &mut forged.recovery {
+            Recovery::ThresholdReveal { token, .. } => {
+                assert_eq!(*token, expected);
+                *token = partial_tokens[0].1;
+            }
+            Recovery::Exclusion { .. } => panic!("expected a threshold reveal"),
+        }
+        assert!(forged.verify(&game.parameters).is_err());
+
+        // The certificate only covers the card the stall was reported for
+        let mut forged = certificate.clone();
+        if let Recovery::ThresholdReveal { card_index, .. } = &mut forged.recovery {
+            *card_index = 1;
+        }
+        assert!(forged.verify(&game.parameters).is_err());
+    }
+}
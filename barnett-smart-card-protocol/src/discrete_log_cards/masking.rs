@@ -22,7 +22,9 @@ impl<C: ProjectiveCurve> Mask<C::ScalarField, ElGamal<C>> for Card<C> {
 #[cfg(test)]
 mod test {
     use crate::discrete_log_cards;
-    use crate::BarnettSmartProtocol;
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{CardMasking, KeyManagement, MaskingVerification, ProtocolSetup};
 
     use ark_ff::UniformRand;
     use ark_std::{rand::Rng, Zero};
@@ -74,7 +76,7 @@ mod test {
         let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players);
 
         let some_card = Card::rand(rng);
-        let some_random = Scalar::rand(rng);
+        let some_random = Witness::new(Scalar::rand(rng));
 
         let (masked, masking_proof): (MaskedCard, MaskingProof) =
             CardProtocol::mask(rng, &parameters, &aggregate_key, &some_card, &some_random).unwrap();
@@ -100,9 +102,10 @@ mod test {
                 &wrong_masked,
                 &masking_proof
             ),
-            Err(CryptoError::ProofVerificationError(String::from(
-                "Chaum-Pedersen"
-            )))
+            Err(CardProtocolError::MaskingVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Chaum-Pedersen")),
+                context: ErrorContext::default(),
+            })
         )
     }
 }
@@ -0,0 +1,1021 @@
+//! Self-describing wire format for the protocol messages exchanged by the players.
+//!
+//! Every message is framed by a header identifying the format version, the curve, the public parameters
+//! and the game session it belongs to, as well as the index of its sender:
+//!
+//! | field                  | size (bytes) |
+//! |------------------------|--------------|
+//! | version                | 1            |
+//! | curve id               | 8            |
+//! | parameters fingerprint | 32           |
+//! | session id             | 32           |
+//! | sender                 | 4 (LE)       |
+//! | message tag            | 1            |
+//! | body                   | rest         |
+//!
+//! The body holds the canonical serialization of the fields of the message. Decoding is strict: any
+//! header field that does not match the receiver's session, an unknown tag, an invalid point or trailing
+//! bytes cause the whole message to be rejected.
+//!
+//! Signed messages carry, after the length-prefixed framed message, a Schnorr signature of the framed
+//! bytes under the sender's game key. Receivers check it against the key the sender registered, so that
+//! nobody can post a shuffle or a reveal token in another player's name.
+
+use crate::discrete_log_cards::{
+    Card, DLCards, KeyOwnershipProof, KeySwitchProof, MaskedCard, MaskingProof, Parameters,
+    PlayerSecretKey, PublicKey, RemaskingProof, RevealProof, RevealToken, ShuffleProof, Step,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::PointValidation;
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{to_bytes, FpParameters, PrimeField, ToBytes};
+use ark_marlin::rng::FiatShamirRng;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::io::{Read, Write};
+use ark_std::rand::Rng;
+use blake2::{Blake2s, Digest};
+use proof_essentials::zkp::{proofs::schnorr_identification, ArgumentOfKnowledge};
+use std::convert::TryInto;
+
+/// Version of the wire format
+pub const MESSAGE_VERSION: u8 = 1;
+
+/// Index of the player masking the initial deck, the only sender of `Mask` messages
+pub const DEALER: usize = 0;
+
+const CURVE_ID_SEED: &[u8] = b"Curve Identifier";
+const SIGNATURE_RNG_SEED: &[u8] = b"Message Signature";
+
+/// Schnorr signature of a framed message, i.e. a proof of knowledge of the sender's secret key bound to
+/// the message bytes by the Fiat-Shamir transform.
+pub type MessageSignature<C> = KeyOwnershipProof<C>;
+
+/// Identifier of a game session, chosen by the players when setting up the game
+pub type SessionId = [u8; 32];
+
+/// Identifier of the curve `C`, computed from its scalar field modulus and the generator of its
+/// prime-order subgroup.
+pub fn curve_id<C: ProjectiveCurve>() -> Result<[u8; 8], CardProtocolError> {
+    let mut bytes = Vec::new();
+    <<C::ScalarField as PrimeField>::Params as FpParameters>::MODULUS.write(&mut bytes)?;
+    C::Affine::prime_subgroup_generator().serialize(&mut bytes)?;
+
+    let mut hasher = Blake2s::new();
+    hasher.update(CURVE_ID_SEED);
+    hasher.update(&bytes);
+
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&hasher.finalize()[..8]);
+    Ok(id)
+}
+
+/// A message sent by a player at some step of the protocol.
+pub enum Message<C: ProjectiveCurve> {
+    /// Announce a key with a proof of ownership
+    KeyOwnership {
+        pk: PublicKey<C>,
+        proof: KeyOwnershipProof<C>,
+        player_public_info: Vec<u8>,
+    },
+    /// Mask an open card
+    Mask {
+        card: Card<C>,
+        masked_card: MaskedCard<C>,
+        proof: MaskingProof<C>,
+    },
+    /// Remask a masked card
+    Remask {
+        original: MaskedCard<C>,
+        remasked: MaskedCard<C>,
+        proof: RemaskingProof<C>,
+    },
+    /// Shuffle the current deck
+    Shuffle {
+        deck: Vec<MaskedCard<C>>,
+        proof: ShuffleProof<C>,
+    },
+    /// Provide a reveal token for the card at `card_index` of the current deck
+    Reveal {
+        card_index: u64,
+        token: RevealToken<C>,
+        proof: RevealProof<C>,
+    },
+    /// Publish the commitments of the escrow of the sender's key, as computed by `escrow_key`
+    KeyEscrow { commitments: Vec<PublicKey<C>> },
+    /// Provide a partial token for the card at `card_index` of the current deck, computed with the
+    /// sender's share of the key of the stalled player at index `stalled`
+    EscrowedReveal {
+        card_index: u64,
+        stalled: u64,
+        token: RevealToken<C>,
+        proof: RevealProof<C>,
+    },
+    /// Report that the player at index `stalled` missed the deadline of `step`
+    StallReport { stalled: u64, step: Step },
+    /// Join the game while the deck is being shuffled: announce a key with a proof of ownership for the
+    /// players including the sender, and move the current deck to the aggregate key extended with it
+    KeyAddition {
+        pk: PublicKey<C>,
+        proof: KeyOwnershipProof<C>,
+        player_public_info: Vec<u8>,
+        deck: Vec<MaskedCard<C>>,
+        switch_proof: KeySwitchProof<C>,
+    },
+    /// Leave the game once the deck is final, moving it to the aggregate key without the sender's key
+    KeyRemoval {
+        deck: Vec<MaskedCard<C>>,
+        switch_proof: KeySwitchProof<C>,
+    },
+}
+
+impl<C: ProjectiveCurve> Message<C> {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::KeyOwnership { .. } => 0,
+            Self::Mask { .. } => 1,
+            Self::Remask { .. } => 2,
+            Self::Shuffle { .. } => 3,
+            Self::Reveal { .. } => 4,
+            Self::KeyEscrow { .. } => 5,
+            Self::EscrowedReveal { .. } => 6,
+            Self::StallReport { .. } => 7,
+            Self::KeyAddition { .. } => 8,
+            Self::KeyRemoval { .. } => 9,
+        }
+    }
+
+    /// Name of the protocol step this message belongs to
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::KeyOwnership { .. } => "key ownership",
+            Self::Mask { .. } => "mask",
+            Self::Remask { .. } => "remask",
+            Self::Shuffle { .. } => "shuffle",
+            Self::Reveal { .. } => "reveal",
+            Self::KeyEscrow { .. } => "key escrow",
+            Self::EscrowedReveal { .. } => "escrowed reveal",
+            Self::StallReport { .. } => "stall report",
+            Self::KeyAddition { .. } => "key addition",
+            Self::KeyRemoval { .. } => "key removal",
+        }
+    }
+
+    fn write_body<W: Write>(&self, mut writer: W) -> Result<(), CardProtocolError> {
+        match self {
+            Self::KeyOwnership {
+                pk,
+                proof,
+                player_public_info,
+            } => {
+                pk.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+                player_public_info.serialize(&mut writer)?;
+            }
+            Self::Mask {
+                card,
+                masked_card,
+                proof,
+            } => {
+                card.serialize(&mut writer)?;
+                masked_card.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+            }
+            Self::Remask {
+                original,
+                remasked,
+                proof,
+            } => {
+                original.serialize(&mut writer)?;
+                remasked.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+            }
+            Self::Shuffle { deck, proof } => {
+                deck.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+            }
+            Self::Reveal {
+                card_index,
+                token,
+                proof,
+            } => {
+                card_index.serialize(&mut writer)?;
+                token.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+            }
+            Self::KeyEscrow { commitments } => {
+                commitments.serialize(&mut writer)?;
+            }
+            Self::EscrowedReveal {
+                card_index,
+                stalled,
+                token,
+                proof,
+            } => {
+                card_index.serialize(&mut writer)?;
+                stalled.serialize(&mut writer)?;
+                token.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+            }
+            Self::StallReport { stalled, step } => {
+                stalled.serialize(&mut writer)?;
+                let (kind, index) = match step {
+                    Step::Shuffle { round } => (0u8, *round),
+                    Step::Reveal { card_index } => (1u8, *card_index),
+                };
+                kind.serialize(&mut writer)?;
+                (index as u64).serialize(&mut writer)?;
+            }
+            Self::KeyAddition {
+                pk,
+                proof,
+                player_public_info,
+                deck,
+                switch_proof,
+            } => {
+                pk.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+                player_public_info.serialize(&mut writer)?;
+                deck.serialize(&mut writer)?;
+                switch_proof.serialize(&mut writer)?;
+            }
+            Self::KeyRemoval { deck, switch_proof } => {
+                deck.serialize(&mut writer)?;
+                switch_proof.serialize(&mut writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the body of a message, validating every point it contains
+    fn read_body<R: Read>(tag: u8, mut reader: R) -> Result<Self, CardProtocolError> {
+        let message = match tag {
+            0 => Self::KeyOwnership {
+                pk: DLCards::<C>::deserialize_public_key(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+                player_public_info: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            1 => Self::Mask {
+                card: DLCards::<C>::deserialize_card(&mut reader)?,
+                masked_card: DLCards::<C>::deserialize_masked_card(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            2 => Self::Remask {
+                original: DLCards::<C>::deserialize_masked_card(&mut reader)?,
+                remasked: DLCards::<C>::deserialize_masked_card(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            3 => Self::Shuffle {
+                deck: DLCards::<C>::deserialize_deck(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            4 => Self::Reveal {
+                card_index: CanonicalDeserialize::deserialize(&mut reader)?,
+                token: DLCards::<C>::deserialize_reveal_token(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            5 => {
+                let len = u64::deserialize(&mut reader)?;
+                Self::KeyEscrow {
+                    commitments: (0..len)
+                        .map(|_| DLCards::<C>::deserialize_public_key(&mut reader))
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            6 => Self::EscrowedReveal {
+                card_index: CanonicalDeserialize::deserialize(&mut reader)?,
+                stalled: CanonicalDeserialize::deserialize(&mut reader)?,
+                token: DLCards::<C>::deserialize_reveal_token(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            7 => {
+                let stalled = CanonicalDeserialize::deserialize(&mut reader)?;
+                let kind = u8::deserialize(&mut reader)?;
+                let index = u64::deserialize(&mut reader)?.try_into().map_err(|_| {
+                    CardProtocolError::InvalidMessage(String::from("step index out of range"))
+                })?;
+                let step = match kind {
+                    0 => Step::Shuffle { round: index },
+                    1 => Step::Reveal { card_index: index },
+                    kind => {
+                        return Err(CardProtocolError::InvalidMessage(format!(
+                            "unknown step kind {}",
+                            kind
+                        )))
+                    }
+                };
+                Self::StallReport { stalled, step }
+            }
+            8 => Self::KeyAddition {
+                pk: DLCards::<C>::deserialize_public_key(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+                player_public_info: CanonicalDeserialize::deserialize(&mut reader)?,
+                deck: DLCards::<C>::deserialize_deck(&mut reader)?,
+                switch_proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            9 => Self::KeyRemoval {
+                deck: DLCards::<C>::deserialize_deck(&mut reader)?,
+                switch_proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            tag => {
+                return Err(CardProtocolError::InvalidMessage(format!(
+                    "unknown message tag {}",
+                    tag
+                )))
+            }
+        };
+
+        Ok(message)
+    }
+}
+
+/// Framing header of a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageHeader {
+    pub version: u8,
+    pub curve_id: [u8; 8],
+    pub parameters_fingerprint: [u8; 32],
+    pub session_id: SessionId,
+    /// Index of the sender in the turn order
+    pub sender: u32,
+}
+
+const HEADER_LEN: usize = 1 + 8 + 32 + 32 + 4;
+
+impl MessageHeader {
+    fn write<W: Write>(&self, mut writer: W) -> Result<(), CardProtocolError> {
+        writer.write_all(&[self.version])?;
+        writer.write_all(&self.curve_id)?;
+        writer.write_all(&self.parameters_fingerprint)?;
+        writer.write_all(&self.session_id)?;
+        writer.write_all(&self.sender.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    fn read(bytes: &[u8; HEADER_LEN]) -> Self {
+        Self {
+            version: bytes[0],
+            curve_id: bytes[1..9].try_into().unwrap(),
+            parameters_fingerprint: bytes[9..41].try_into().unwrap(),
+            session_id: bytes[41..73].try_into().unwrap(),
+            sender: u32::from_le_bytes(bytes[73..77].try_into().unwrap()),
+        }
+    }
+}
+
+/// The session a player takes part in, used to frame outgoing messages and to check incoming ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageContext {
+    curve_id: [u8; 8],
+    parameters_fingerprint: [u8; 32],
+    session_id: SessionId,
+    num_players: u32,
+}
+
+impl MessageContext {
+    pub fn new<C: ProjectiveCurve>(
+        pp: &Parameters<C>,
+        session_id: SessionId,
+        num_players: u32,
+    ) -> Result<Self, CardProtocolError> {
+        Ok(Self {
+            curve_id: curve_id::<C>()?,
+            parameters_fingerprint: pp.fingerprint()?,
+            session_id,
+            num_players,
+        })
+    }
+
+    /// Header of a message sent by the player at index `sender`
+    pub fn header(&self, sender: u32) -> MessageHeader {
+        MessageHeader {
+            version: MESSAGE_VERSION,
+            curve_id: self.curve_id,
+            parameters_fingerprint: self.parameters_fingerprint,
+            session_id: self.session_id,
+            sender,
+        }
+    }
+
+    /// Check that a received header belongs to this session
+    pub fn check_header(&self, header: &MessageHeader) -> Result<(), CardProtocolError> {
+        let mismatch = |field: &str| {
+            Err(CardProtocolError::MessageHeaderMismatch {
+                field: String::from(field),
+            })
+        };
+
+        if header.version != MESSAGE_VERSION {
+            return Err(CardProtocolError::UnsupportedMessageVersion(header.version));
+        }
+        if header.curve_id != self.curve_id {
+            return mismatch("curve id");
+        }
+        if header.parameters_fingerprint != self.parameters_fingerprint {
+            return mismatch("parameters fingerprint");
+        }
+        if header.session_id != self.session_id {
+            return mismatch("session id");
+        }
+        if header.sender >= self.num_players {
+            return mismatch("sender");
+        }
+
+        Ok(())
+    }
+
+    /// Frame `message` as sent by the player at index `sender`
+    pub fn encode<C: ProjectiveCurve>(
+        &self,
+        sender: u32,
+        message: &Message<C>,
+    ) -> Result<Vec<u8>, CardProtocolError> {
+        if sender >= self.num_players {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!("sender {} is not a player of the session", sender),
+                context: ErrorContext::player(sender as usize),
+            });
+        }
+
+        let mut bytes = Vec::new();
+        self.header(sender).write(&mut bytes)?;
+        bytes.push(message.tag());
+        message.write_body(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    /// Read the header of a framed message and check that it belongs to this session
+    fn read_header(&self, bytes: &[u8]) -> Result<MessageHeader, CardProtocolError> {
+        if bytes.len() < HEADER_LEN + 1 {
+            return Err(CardProtocolError::InvalidMessage(String::from(
+                "message too short",
+            )));
+        }
+
+        let header = MessageHeader::read(bytes[..HEADER_LEN].try_into().unwrap());
+        self.check_header(&header)?;
+
+        Ok(header)
+    }
+
+    /// Decode a message received in this session
+    pub fn decode<C: ProjectiveCurve>(
+        &self,
+        bytes: &[u8],
+    ) -> Result<(MessageHeader, Message<C>), CardProtocolError> {
+        let header = self.read_header(bytes)?;
+
+        let mut reader = &bytes[HEADER_LEN + 1..];
+        let message = Message::read_body(bytes[HEADER_LEN], &mut reader)
+            .map_err(|e| e.with_player(header.sender as usize))?;
+        if !reader.is_empty() {
+            return Err(CardProtocolError::InvalidMessage(format!(
+                "trailing bytes after {} message",
+                message.kind()
+            )));
+        }
+
+        Ok((header, message))
+    }
+
+    /// Frame `message` as sent by the player at index `sender` and sign it with the sender's key
+    pub fn sign<C: ProjectiveCurve, R: Rng>(
+        &self,
+        rng: &mut R,
+        pp: &Parameters<C>,
+        sender: u32,
+        message: &Message<C>,
+        pk: &PublicKey<C>,
+        sk: &PlayerSecretKey<C>,
+    ) -> Result<Vec<u8>, CardProtocolError> {
+        let framed = self.encode(sender, message)?;
+
+        let mut fs_rng =
+            FiatShamirRng::<Blake2s>::from_seed(&to_bytes![SIGNATURE_RNG_SEED, framed]?);
+        let signature = schnorr_identification::SchnorrIdentification::prove(
+            rng,
+            &pp.enc_parameters.generator,
+            pk,
+            sk.expose_secret(),
+            &mut fs_rng,
+        )?;
+
+        let mut bytes = Vec::new();
+        framed.serialize(&mut bytes)?;
+        signature.serialize(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    /// Check the signature of a message received in this session against the public key registered
+    /// by its sender, `players` being the keys of the players in turn order, then decode it.
+    pub fn open<C: ProjectiveCurve>(
+        &self,
+        pp: &Parameters<C>,
+        bytes: &[u8],
+        players: &[PublicKey<C>],
+    ) -> Result<(MessageHeader, Message<C>), CardProtocolError> {
+        let mut reader = bytes;
+        let framed = Vec::<u8>::deserialize(&mut reader)?;
+        let signature = MessageSignature::<C>::deserialize(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(CardProtocolError::InvalidMessage(String::from(
+                "trailing bytes after signature",
+            )));
+        }
+
+        // Authenticate the sender before looking at the body
+        let header = self.read_header(&framed)?;
+        let sender = header.sender as usize;
+        let pk = players
+            .get(sender)
+            .ok_or_else(|| CardProtocolError::MessageHeaderMismatch {
+                field: String::from("sender"),
+            })?;
+
+        let mut fs_rng =
+            FiatShamirRng::<Blake2s>::from_seed(&to_bytes![SIGNATURE_RNG_SEED, framed]?);
+        schnorr_identification::SchnorrIdentification::verify(
+            &pp.enc_parameters.generator,
+            pk,
+            &signature,
+            &mut fs_rng,
+        )
+        .map_err(|source| CardProtocolError::MessageSignatureError {
+            source,
+            context: ErrorContext::player(sender),
+        })?;
+
+        self.decode(&framed)
+    }
+
+    /// Same as [`open`](Self::open), additionally rejecting the message unless it comes from the player
+    /// whose turn it is, e.g. the player expected to shuffle the deck or to provide a reveal token.
+    pub fn open_in_turn<C: ProjectiveCurve>(
+        &self,
+        pp: &Parameters<C>,
+        bytes: &[u8],
+        players: &[PublicKey<C>],
+        turn: u32,
+    ) -> Result<(MessageHeader, Message<C>), CardProtocolError> {
+        let (header, message) = self.open(pp, bytes, players)?;
+        if header.sender != turn {
+            return Err(CardProtocolError::OutOfTurn {
+                expected: turn as usize,
+                context: ErrorContext::player(header.sender as usize),
+            });
+        }
+
+        Ok((header, message))
+    }
+
+    /// Open the key ownership or key addition message by which the next player announces their key,
+    /// `registered` being the keys announced so far in turn order. Such a message is signed with the key
+    /// it announces, since the sender has no other key known to the others yet.
+    pub fn open_key_announcement<C: ProjectiveCurve>(
+        &self,
+        pp: &Parameters<C>,
+        bytes: &[u8],
+        registered: &[PublicKey<C>],
+    ) -> Result<(MessageHeader, Message<C>), CardProtocolError> {
+        let framed = Vec::<u8>::deserialize(bytes)?;
+        let (header, message) = self.decode::<C>(&framed)?;
+        if header.sender as usize != registered.len() {
+            return Err(CardProtocolError::OutOfTurn {
+                expected: registered.len(),
+                context: ErrorContext::player(header.sender as usize),
+            });
+        }
+        let pk = match message {
+            Message::KeyOwnership { pk, .. } | Message::KeyAddition { pk, .. } => pk,
+            message => {
+                return Err(CardProtocolError::InvalidMessage(format!(
+                    "expected a key announcement, found a {} message",
+                    message.kind()
+                )))
+            }
+        };
+
+        let mut players = registered.to_vec();
+        players.push(pk);
+        self.open(pp, bytes, &players)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Message, MessageContext, MESSAGE_VERSION};
+    use crate::discrete_log_cards::{self, escrow_key, Step};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{CardMasking, CardReveal, DeckShuffle, KeyManagement, KeySwitching, ProtocolSetup};
+
+    use ark_ff::UniformRand;
+    use ark_std::Zero;
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+
+    const SESSION: [u8; 32] = [7; 32];
+
+    #[test]
+    fn roundtrip() {
+        let rng = &mut thread_rng();
+        let m = 2;
+        let n = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let context = MessageContext::new(&parameters, SESSION, 3).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let card = Card::rand(rng);
+        let alpha = Witness::new(Scalar::rand(rng));
+        let (masked_card, proof) =
+            CardProtocol::mask(rng, &parameters, &pk, &card, &alpha).unwrap();
+        let (token, reveal_proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, &sk, &pk, &masked_card).unwrap();
+        let (escrow, _) = escrow_key(rng, &parameters, &sk, 0, 3, 2).unwrap();
+        let (partial_token, partial_proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, &sk, &pk, &masked_card).unwrap();
+        let ownership_proof =
+            CardProtocol::prove_key_ownership(rng, &parameters, &pk, &sk, &b"player".to_vec())
+                .unwrap();
+
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
+        let (shuffled, shuffle_proof) = CardProtocol::shuffle_and_remask(
+            rng,
+            &parameters,
+            &pk,
+            &deck,
+            &masking_factors,
+            &Permutation::new(rng, m * n),
+        )
+        .unwrap();
+        let (switched_deck, switch_proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, &pk, &sk, &deck).unwrap();
+        let (remaining_deck, removal_proof) =
+            CardProtocol::remove_key_from_deck(rng, &parameters, &pk, &sk, &deck).unwrap();
+
+        let messages = vec![
+            Message::Mask {
+                card,
+                masked_card,
+                proof,
+            },
+            Message::Shuffle {
+                deck: shuffled,
+                proof: shuffle_proof,
+            },
+            Message::Reveal {
+                card_index: 3,
+                token,
+                proof: reveal_proof,
+            },
+            Message::KeyEscrow {
+                commitments: escrow.commitments,
+            },
+            Message::EscrowedReveal {
+                card_index: 3,
+                stalled: 0,
+                token: partial_token,
+                proof: partial_proof,
+            },
+            Message::StallReport {
+                stalled: 1,
+                step: Step::Shuffle { round: 1 },
+            },
+            Message::StallReport {
+                stalled: 0,
+                step: Step::Reveal { card_index: 3 },
+            },
+            Message::KeyAddition {
+                pk,
+                proof: ownership_proof,
+                player_public_info: b"player".to_vec(),
+                deck: switched_deck,
+                switch_proof,
+            },
+            Message::KeyRemoval {
+                deck: remaining_deck,
+                switch_proof: removal_proof,
+            },
+        ];
+
+        for message in messages {
+            let bytes = context.encode(2, &message).unwrap();
+            let (header, decoded) = context.decode::<Curve>(&bytes).unwrap();
+            assert_eq!(header, context.header(2));
+            assert_eq!(decoded.kind(), message.kind());
+            assert_eq!(context.encode(2, &decoded).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn strict_decoding() {
+        let rng = &mut thread_rng();
+
+        let parameters = CardProtocol::setup(rng, 2, 4).unwrap();
+        let context = MessageContext::new(&parameters, SESSION, 3).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let masked_card = MaskedCard::rand(rng);
+        let reveal = || {
+            CardProtocol::compute_reveal_token(
+                &mut thread_rng(),
+                &parameters,
+                &sk,
+                &pk,
+                &masked_card,
+            )
+            .unwrap()
+        };
+        let (token, proof) = reveal();
+        let message = Message::<Curve>::Reveal {
+            card_index: 0,
+            token,
+            proof,
+        };
+        let bytes = context.encode(1, &message).unwrap();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = MESSAGE_VERSION + 1;
+        assert_eq!(
+            context.decode::<Curve>(&wrong_version).err(),
+            Some(CardProtocolError::UnsupportedMessageVersion(
+                MESSAGE_VERSION + 1
+            ))
+        );
+
+        let header_mismatch = |field: &str| {
+            Some(CardProtocolError::MessageHeaderMismatch {
+                field: String::from(field),
+            })
+        };
+
+        let other_curve_context = MessageContext::new(
+            &discrete_log_cards::DLCards::<ark_bls12_377::G1Projective>::setup(rng, 2, 4).unwrap(),
+            SESSION,
+            3,
+        )
+        .unwrap();
+        assert_eq!(
+            other_curve_context
+                .decode::<ark_bls12_377::G1Projective>(&bytes)
+                .err(),
+            header_mismatch("curve id")
+        );
+
+        // Other parameters over the same curve
+        let other_parameters = CardProtocol::setup(rng, 2, 4).unwrap();
+        let other_parameters_context = MessageContext::new(&other_parameters, SESSION, 3).unwrap();
+        assert_eq!(
+            other_parameters_context.decode::<Curve>(&bytes).err(),
+            header_mismatch("parameters fingerprint")
+        );
+
+        let other_session_context = MessageContext::new(&parameters, [8; 32], 3).unwrap();
+        assert_eq!(
+            other_session_context.decode::<Curve>(&bytes).err(),
+            header_mismatch("session id")
+        );
+
+        let fewer_players_context = MessageContext::new(&parameters, SESSION, 1).unwrap();
+        assert_eq!(
+            fewer_players_context.decode::<Curve>(&bytes).err(),
+            header_mismatch("sender")
+        );
+
+        let mut unknown_tag = bytes.clone();
+        unknown_tag[77] = 42;
+        assert!(matches!(
+            context.decode::<Curve>(&unknown_tag),
+            Err(CardProtocolError::InvalidMessage(_))
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            context.decode::<Curve>(&trailing),
+            Err(CardProtocolError::InvalidMessage(_))
+        ));
+
+        // The step of a stall report is a shuffle or a reveal: 8 bytes of stalled player after the
+        // header and the tag, then the kind of step
+        let report = Message::<Curve>::StallReport {
+            stalled: 2,
+            step: Step::Reveal { card_index: 0 },
+        };
+        let mut unknown_step = context.encode(1, &report).unwrap();
+        unknown_step[78 + 8] = 2;
+        assert_eq!(
+            context.decode::<Curve>(&unknown_step).err(),
+            Some(CardProtocolError::InvalidMessage(String::from(
+                "unknown step kind 2"
+            )))
+        );
+
+        // Escrow commitments must be valid points
+        let zero_commitment = Message::<Curve>::KeyEscrow {
+            commitments: vec![pk, PublicKey::zero()],
+        };
+        let bytes = context.encode(1, &zero_commitment).unwrap();
+        assert_eq!(
+            context.decode::<Curve>(&bytes).err(),
+            Some(CardProtocolError::InvalidPoint {
+                context: ErrorContext::player(1),
+            })
+        );
+
+        // A reveal token must be a valid point
+        let (_, proof) = reveal();
+        let zero_token = Message::<Curve>::Reveal {
+            card_index: 0,
+            token: el_gamal::Plaintext(PublicKey::zero()),
+            proof,
+        };
+        let bytes = context.encode(1, &zero_token).unwrap();
+        assert_eq!(
+            context.decode::<Curve>(&bytes).err(),
+            Some(CardProtocolError::InvalidPoint {
+                context: ErrorContext::player(1),
+            })
+        );
+    }
+
+    #[test]
+    fn signed_messages() {
+        let rng = &mut thread_rng();
+
+        let parameters = CardProtocol::setup(rng, 2, 4).unwrap();
+        let context = MessageContext::new(&parameters, SESSION, 3).unwrap();
+        let keys = (0..3)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .collect::<Vec<_>>();
+        let players = keys.iter().map(|(pk, _)| *pk).collect::<Vec<_>>();
+
+        let masked_card = MaskedCard::rand(rng);
+        let reveal = |player: usize| {
+            let (pk, sk) = &keys[player];
+            let (token, proof) = CardProtocol::compute_reveal_token(
+                &mut thread_rng(),
+                &parameters,
+                sk,
+                pk,
+                &masked_card,
+            )
+            .unwrap();
+            Message::<Curve>::Reveal {
+                card_index: 5,
+                token,
+                proof,
+            }
+        };
+
+        let (pk, sk) = &keys[1];
+        let bytes = context
+            .sign(rng, &parameters, 1, &reveal(1), pk, sk)
+            .unwrap();
+        let (header, message) = context.open(&parameters, &bytes, &players).unwrap();
+        assert_eq!(header.sender, 1);
+        assert_eq!(message.kind(), "reveal");
+        assert!(context
+            .open_in_turn(&parameters, &bytes, &players, 1)
+            .is_ok());
+
+        // The token is valid, but it is not this player's turn
+        assert_eq!(
+            context.open_in_turn(&parameters, &bytes, &players, 2).err(),
+            Some(CardProtocolError::OutOfTurn {
+                expected: 2,
+                context: ErrorContext::player(1),
+            })
+        );
+
+        // Player 0 cannot post a message in the name of player 1
+        let (pk, sk) = &keys[0];
+        let forged = context
+            .sign(rng, &parameters, 1, &reveal(0), pk, sk)
+            .unwrap();
+        assert!(matches!(
+            context.open(&parameters, &forged, &players),
+            Err(CardProtocolError::MessageSignatureError {
+                context: ErrorContext {
+                    player: Some(1),
+                    card: None
+                },
+                ..
+            })
+        ));
+
+        // The signature covers the whole framed message: 8 bytes of length prefix, then the header and
+        // the tag, then the card index
+        let mut tampered = bytes.clone();
+        tampered[8 + 78] ^= 1;
+        assert!(matches!(
+            context.open(&parameters, &tampered, &players),
+            Err(CardProtocolError::MessageSignatureError { .. })
+        ));
+
+        // Unsigned messages are rejected
+        let unsigned = context.encode(1, &reveal(1)).unwrap();
+        assert!(context.open(&parameters, &unsigned, &players).is_err());
+    }
+
+    #[test]
+    fn key_announcements() {
+        let rng = &mut thread_rng();
+
+        let parameters = CardProtocol::setup(rng, 2, 4).unwrap();
+        let context = MessageContext::new(&parameters, SESSION, 3).unwrap();
+        let keys = (0..2)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .collect::<Vec<_>>();
+
+        let announce = |player: usize, sender: u32| {
+            let (pk, sk) = &keys[player];
+            let proof = CardProtocol::prove_key_ownership(
+                &mut thread_rng(),
+                &parameters,
+                pk,
+                sk,
+                &b"player".to_vec(),
+            )
+            .unwrap();
+            let message = Message::<Curve>::KeyOwnership {
+                pk: *pk,
+                proof,
+                player_public_info: b"player".to_vec(),
+            };
+            context
+                .sign(&mut thread_rng(), &parameters, sender, &message, pk, sk)
+                .unwrap()
+        };
+
+        let (header, _) = context
+            .open_key_announcement(&parameters, &announce(1, 1), &[keys[0].0])
+            .unwrap();
+        assert_eq!(header.sender, 1);
+
+        // A player joining a game in progress announces their key along with the switched deck
+        let (pk, sk) = &keys[1];
+        let deck: Vec<MaskedCard> = sample_vector(rng, 8);
+        let (switched_deck, switch_proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, pk, sk, &deck).unwrap();
+        let addition = Message::<Curve>::KeyAddition {
+            pk: *pk,
+            proof: CardProtocol::prove_key_ownership(rng, &parameters, pk, sk, &b"player".to_vec())
+                .unwrap(),
+            player_public_info: b"player".to_vec(),
+            deck: switched_deck,
+            switch_proof,
+        };
+        let bytes = context
+            .sign(rng, &parameters, 1, &addition, pk, sk)
+            .unwrap();
+        let (_, message) = context
+            .open_key_announcement(&parameters, &bytes, &[keys[0].0])
+            .unwrap();
+        assert_eq!(message.kind(), "key addition");
+
+        // Announcements come in turn order
+        assert_eq!(
+            context
+                .open_key_announcement(&parameters, &announce(1, 1), &[])
+                .err(),
+            Some(CardProtocolError::OutOfTurn {
+                expected: 0,
+                context: ErrorContext::player(1),
+            })
+        );
+
+        // Other messages are not announcements
+        let (pk, sk) = &keys[0];
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &MaskedCard::rand(rng))
+                .unwrap();
+        let reveal = Message::<Curve>::Reveal {
+            card_index: 0,
+            token,
+            proof,
+        };
+        let bytes = context.sign(rng, &parameters, 0, &reveal, pk, sk).unwrap();
+        assert!(matches!(
+            context.open_key_announcement(&parameters, &bytes, &[]),
+            Err(CardProtocolError::InvalidMessage(_))
+        ));
+    }
+}
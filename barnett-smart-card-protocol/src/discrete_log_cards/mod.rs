@@ -1,16 +1,23 @@
-use super::BarnettSmartProtocol;
+use super::{
+    CardMasking, CardProtocolTypes, CardReveal, DeckShuffle, KeyManagement,
+    KeyOwnershipVerification, MaskingVerification, PointValidation, ProtocolSetup,
+    RevealVerification, ShuffleVerification,
+};
 use super::{Mask, Remask, Reveal};
 
-use crate::error::CardProtocolError;
+use crate::derivation::MasterSeed;
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::witness::Witness;
 
 use anyhow::Result;
 use ark_ec::{AffineCurve, ProjectiveCurve};
 use ark_ff::{to_bytes, One, PrimeField, ToBytes};
 use ark_marlin::rng::FiatShamirRng;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::io::{Read, Write};
 use ark_std::rand::Rng;
 use ark_std::Zero;
-use blake2::Blake2s;
-use proof_essentials::error::CryptoError;
+use blake2::{Blake2s, Digest};
 use proof_essentials::homomorphic_encryption::{
     el_gamal, el_gamal::ElGamal, HomomorphicEncryptionScheme,
 };
@@ -22,13 +29,33 @@ use proof_essentials::zkp::{
     proofs::{chaum_pedersen_dl_equality, schnorr_identification},
     ArgumentOfKnowledge,
 };
+use std::collections::HashSet;
+use std::fmt;
 use std::marker::PhantomData;
+use zeroize::Zeroize;
 
 // mod key_ownership;
+mod audit;
+mod key_switching;
+pub mod keystore;
+mod liveness;
 mod masking;
+pub mod message;
 mod remasking;
 mod reveal;
+mod session;
 mod tests;
+mod transcript;
+mod validation;
+mod verifier;
+
+pub use audit::{audit_game, AuditFinding, AuditReport, GameRecord, RevealRecord};
+pub use liveness::{escrow_key, AbortCertificate, Deadlines, KeyEscrow, Recovery, Stall, Step};
+pub use session::{GameSession, SessionPhase};
+pub use transcript::{
+    verify_transcript, ReplayedGame, Transcript, TranscriptStep, TRANSCRIPT_VERSION,
+};
+pub use verifier::Verifier;
 
 pub struct DLCards<'a, C: ProjectiveCurve> {
     _group: &'a PhantomData<C>,
@@ -58,11 +85,97 @@ impl<C: ProjectiveCurve> Parameters<C> {
             generator,
         }
     }
+
+    /// Number of cards in a deck shuffled under these parameters.
+    pub fn deck_size(&self) -> usize {
+        self.m * self.n
+    }
+
+    /// Digest of the canonical serialization of the parameters, identifying them in messages.
+    pub fn fingerprint(&self) -> Result<[u8; 32], CardProtocolError> {
+        let mut bytes = Vec::with_capacity(self.serialized_size());
+        self.serialize(&mut bytes)?;
+
+        let mut fingerprint = [0u8; 32];
+        fingerprint.copy_from_slice(&Blake2s::digest(&bytes));
+        Ok(fingerprint)
+    }
+
+    pub(crate) fn check_deck_size(&self, found: usize) -> Result<(), CardProtocolError> {
+        if found != self.deck_size() {
+            return Err(CardProtocolError::DeckSizeMismatch {
+                expected: self.deck_size(),
+                found,
+                context: ErrorContext::default(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalSerialize for Parameters<C> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        (self.m as u64).serialize(&mut writer)?;
+        (self.n as u64).serialize(&mut writer)?;
+        self.enc_parameters.serialize(&mut writer)?;
+        self.commit_parameters.serialize(&mut writer)?;
+        self.generator.serialize(&mut writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        8 + 8
+            + self.enc_parameters.serialized_size()
+            + self.commit_parameters.serialized_size()
+            + self.generator.serialized_size()
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalDeserialize for Parameters<C> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        Ok(Self::new(
+            u64::deserialize(&mut reader)? as usize,
+            u64::deserialize(&mut reader)? as usize,
+            CanonicalDeserialize::deserialize(&mut reader)?,
+            CanonicalDeserialize::deserialize(&mut reader)?,
+            CanonicalDeserialize::deserialize(&mut reader)?,
+        ))
+    }
 }
 
 pub type PublicKey<C> = el_gamal::PublicKey<C>;
 
-pub type PlayerSecretKey<C> = el_gamal::SecretKey<C>;
+/// Secret key of a player. The key is wiped from memory when dropped, is redacted from `Debug` output
+/// and cannot be cloned. The underlying scalar is only reachable through `expose_secret`.
+pub struct PlayerSecretKey<C: ProjectiveCurve>(el_gamal::SecretKey<C>);
+
+impl<C: ProjectiveCurve> PlayerSecretKey<C> {
+    pub fn new(sk: el_gamal::SecretKey<C>) -> Self {
+        Self(sk)
+    }
+
+    pub fn expose_secret(&self) -> &el_gamal::SecretKey<C> {
+        &self.0
+    }
+}
+
+impl<C: ProjectiveCurve> Zeroize for PlayerSecretKey<C> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<C: ProjectiveCurve> Drop for PlayerSecretKey<C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for PlayerSecretKey<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PlayerSecretKey(<redacted>)")
+    }
+}
 
 /// An open playing card. In this Discrete Log-based implementation of the Barnett-Smart card protocol
 /// a card is an el-Gamal plaintext. We create a type alias to implement the `Mask` trait on it.
@@ -77,13 +190,25 @@ pub type MaskedCard<C> = el_gamal::Ciphertext<C>;
 /// then be aggregated to reveal the card.
 pub type RevealToken<C> = el_gamal::Plaintext<C>;
 
+pub type KeyOwnershipProof<C> = schnorr_identification::proof::Proof<C>;
+pub type MaskingProof<C> = chaum_pedersen_dl_equality::proof::Proof<C>;
+pub type RemaskingProof<C> = chaum_pedersen_dl_equality::proof::Proof<C>;
+pub type RevealProof<C> = chaum_pedersen_dl_equality::proof::Proof<C>;
+pub type ShuffleProof<C> =
+    shuffle::proof::Proof<<C as ProjectiveCurve>::ScalarField, ElGamal<C>, PedersenCommitment<C>>;
+pub type KeySwitchProof<C> = chaum_pedersen_dl_equality::proof::Proof<C>;
+
 const KEY_OWN_RNG_SEED: &'static [u8] = b"Key Ownership Proof";
+const GAME_KEY_OWN_RNG_SEED: &'static [u8] = b"Game Key Ownership Proof";
+const PARTICIPANTS_SEED: &'static [u8] = b"Game Participants";
 const MASKING_RNG_SEED: &'static [u8] = b"Masking Proof";
 const REMASKING_RNG_SEED: &'static [u8] = b"Remasking Proof";
 const REVEAL_RNG_SEED: &'static [u8] = b"Reveal Proof";
 const SHUFFLE_RNG_SEED: &'static [u8] = b"Shuffle Proof";
+const KEY_SWITCH_RNG_SEED: &'static [u8] = b"Key Switch Proof";
+const KEY_SWITCH_BATCH_SEED: &'static [u8] = b"Key Switch Batch";
 
-impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
+impl<'a, C: ProjectiveCurve> CardProtocolTypes for DLCards<'a, C> {
     type Scalar = C::ScalarField;
     type Enc = ElGamal<C>;
     type Comm = PedersenCommitment<C>;
@@ -101,7 +226,9 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
     type ZKProofRemasking = chaum_pedersen_dl_equality::proof::Proof<C>;
     type ZKProofReveal = chaum_pedersen_dl_equality::proof::Proof<C>;
     type ZKProofShuffle = shuffle::proof::Proof<Self::Scalar, Self::Enc, Self::Comm>;
+}
 
+impl<'a, C: ProjectiveCurve> ProtocolSetup for DLCards<'a, C> {
     fn setup<R: Rng>(
         rng: &mut R,
         m: usize,
@@ -119,105 +246,239 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
             generator,
         ))
     }
+}
 
-    fn player_keygen<R: Rng>(
-        rng: &mut R,
-        pp: &Self::Parameters,
-    ) -> Result<(Self::PlayerPublicKey, Self::PlayerSecretKey), CardProtocolError> {
-        let (pk, sk) = Self::Enc::keygen(&pp.enc_parameters, rng)?;
-
-        Ok((pk, sk))
-    }
-
-    fn prove_key_ownership<B: ToBytes, R: Rng>(
-        rng: &mut R,
+impl<'a, C: ProjectiveCurve> KeyOwnershipVerification for DLCards<'a, C> {
+    fn verify_key_ownership<B: ToBytes>(
         pp: &Self::Parameters,
         pk: &Self::PlayerPublicKey,
-        sk: &Self::PlayerSecretKey,
         player_public_info: &B,
-    ) -> Result<Self::ZKProofKeyOwnership, CryptoError> {
-        let mut fs_rng =
-            FiatShamirRng::<Blake2s>::from_seed(&to_bytes![KEY_OWN_RNG_SEED, player_public_info]?);
+        proof: &Self::ZKProofKeyOwnership,
+    ) -> Result<(), CardProtocolError> {
+        Self::validate_public_key(pk)?;
 
-        schnorr_identification::SchnorrIdentification::prove(
-            rng,
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![
+            KEY_OWN_RNG_SEED,
+            pk,
+            player_public_info
+        ]?);
+        schnorr_identification::SchnorrIdentification::verify(
             &pp.enc_parameters.generator,
             pk,
-            sk,
+            proof,
             &mut fs_rng,
         )
+        .map_err(|source| CardProtocolError::KeyOwnershipVerificationError {
+            source,
+            context: ErrorContext::default(),
+        })
     }
 
-    fn verify_key_ownership<B: ToBytes>(
+    fn participants_digest<'b, B: ToBytes + 'b>(
+        participants: impl IntoIterator<Item = (&'b Self::PlayerPublicKey, &'b B)>,
+    ) -> Result<Vec<u8>, CardProtocolError>
+    where
+        Self::PlayerPublicKey: 'b,
+    {
+        let mut entries = participants
+            .into_iter()
+            .map(|(pk, player_public_info)| Ok((to_bytes![pk]?, to_bytes![player_public_info]?)))
+            .collect::<Result<Vec<_>, CardProtocolError>>()?;
+
+        // Sort the entries so that the digest does not depend on the order of the players
+        entries.sort();
+
+        let mut hasher = Blake2s::new();
+        hasher.update(PARTICIPANTS_SEED);
+        for (pk, player_public_info) in entries {
+            hasher.update(&(pk.len() as u64).to_le_bytes());
+            hasher.update(&pk);
+            hasher.update(&(player_public_info.len() as u64).to_le_bytes());
+            hasher.update(&player_public_info);
+        }
+
+        Ok(hasher.finalize().to_vec())
+    }
+
+    fn verify_key_ownership_in_game<B: ToBytes>(
         pp: &Self::Parameters,
         pk: &Self::PlayerPublicKey,
         player_public_info: &B,
+        participants_digest: &[u8],
         proof: &Self::ZKProofKeyOwnership,
-    ) -> Result<(), CryptoError> {
-        let mut fs_rng =
-            FiatShamirRng::<Blake2s>::from_seed(&to_bytes![KEY_OWN_RNG_SEED, player_public_info]?);
+    ) -> Result<(), CardProtocolError> {
+        Self::validate_public_key(pk)?;
+
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![
+            GAME_KEY_OWN_RNG_SEED,
+            participants_digest,
+            pk,
+            player_public_info
+        ]?);
         schnorr_identification::SchnorrIdentification::verify(
             &pp.enc_parameters.generator,
             pk,
             proof,
             &mut fs_rng,
         )
+        .map_err(|source| CardProtocolError::KeyOwnershipVerificationError {
+            source,
+            context: ErrorContext::default(),
+        })
     }
 
-    fn compute_aggregate_key<B: ToBytes>(
+    fn compute_aggregate_key<'b, B: ToBytes + 'b>(
         pp: &Self::Parameters,
-        player_keys_proof_info: &Vec<(Self::PlayerPublicKey, Self::ZKProofKeyOwnership, B)>,
-    ) -> Result<Self::AggregatePublicKey, CardProtocolError> {
+        player_keys_proof_info: impl IntoIterator<
+            Item = &'b (Self::PlayerPublicKey, Self::ZKProofKeyOwnership, B),
+        >,
+    ) -> Result<Self::AggregatePublicKey, CardProtocolError>
+    where
+        Self::PlayerPublicKey: 'b,
+        Self::ZKProofKeyOwnership: 'b,
+    {
+        let players = player_keys_proof_info.into_iter().collect::<Vec<_>>();
+        if players.is_empty() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("cannot aggregate the keys of an empty set of players"),
+                context: ErrorContext::default(),
+            });
+        }
+
+        // Reject degenerate keys and players that appear more than once before looking at the proofs
+        let mut seen_keys = HashSet::new();
+        let mut seen_identities = HashSet::new();
+        for (i, (pk, _, player_public_info)) in players.iter().enumerate() {
+            Self::validate_public_key(pk).map_err(|e| e.with_player(i))?;
+
+            if !seen_keys.insert(to_bytes![pk]?) {
+                return Err(CardProtocolError::DuplicatePlayerKey {
+                    context: ErrorContext::player(i),
+                });
+            }
+
+            if !seen_identities.insert(to_bytes![player_public_info]?) {
+                return Err(CardProtocolError::DuplicatePlayerIdentity {
+                    context: ErrorContext::player(i),
+                });
+            }
+        }
+
+        // Every proof must be bound to the full set of participants. A player choosing their key
+        // after seeing the others (e.g. pk = g^x - sum of other keys) cannot produce such a proof.
+        let participants_digest =
+            Self::participants_digest(players.iter().map(|(pk, _, info)| (pk, info)))?;
+
         let zero = Self::PlayerPublicKey::zero();
 
         let mut acc = zero;
-        for (pk, proof, player_public_info) in player_keys_proof_info {
-            Self::verify_key_ownership(pp, pk, player_public_info, proof)?;
+        for (i, (pk, proof, player_public_info)) in players.into_iter().enumerate() {
+            Self::verify_key_ownership_in_game(
+                pp,
+                pk,
+                player_public_info,
+                &participants_digest,
+                proof,
+            )
+            .map_err(|e| e.with_player(i))?;
             acc = acc + *pk;
         }
 
         Ok(acc)
     }
+}
 
-    fn mask<R: Rng>(
+impl<'a, C: ProjectiveCurve> KeyManagement for DLCards<'a, C> {
+    fn player_keygen<R: Rng>(
         rng: &mut R,
         pp: &Self::Parameters,
-        shared_key: &Self::AggregatePublicKey,
-        original_card: &Self::Card,
-        r: &Self::Scalar,
-    ) -> Result<(Self::MaskedCard, Self::ZKProofMasking), CardProtocolError> {
-        let masked_card = original_card.mask(&pp.enc_parameters, shared_key, r)?;
-        let gen = pp.enc_parameters.generator;
+    ) -> Result<(Self::PlayerPublicKey, Self::PlayerSecretKey), CardProtocolError> {
+        let (pk, mut sk) = Self::Enc::keygen(&pp.enc_parameters, rng)?;
+        let player_sk = PlayerSecretKey::new(sk);
+        sk.zeroize();
 
-        // Map to Chaum-Pedersen parameters
-        let cp_parameters = chaum_pedersen_dl_equality::Parameters::new(&gen, shared_key);
+        Ok((pk, player_sk))
+    }
 
-        // Map to Chaum-Pedersen statement
-        let minus_one = -Self::Scalar::one();
-        let negative_original = original_card.0.mul(minus_one).into_affine();
-        let statement_cipher = masked_card.1 + negative_original;
-        let cp_statement =
-            chaum_pedersen_dl_equality::Statement::new(&masked_card.0, &statement_cipher);
+    fn derive_player_keys(
+        pp: &Self::Parameters,
+        master_seed: &MasterSeed,
+        game_id: &[u8],
+    ) -> Result<(Self::PlayerPublicKey, Self::PlayerSecretKey), CardProtocolError> {
+        let mut sk: C::ScalarField = master_seed.derive_scalar(game_id);
+        let pk = pp
+            .enc_parameters
+            .generator
+            .mul(sk.into_repr())
+            .into_affine();
+        let player_sk = PlayerSecretKey::new(sk);
+        sk.zeroize();
+
+        Ok((pk, player_sk))
+    }
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![MASKING_RNG_SEED]?);
-        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+    fn prove_key_ownership<B: ToBytes, R: Rng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        sk: &Self::PlayerSecretKey,
+        player_public_info: &B,
+    ) -> Result<Self::ZKProofKeyOwnership, CardProtocolError> {
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![
+            KEY_OWN_RNG_SEED,
+            pk,
+            player_public_info
+        ]?);
+
+        let proof = schnorr_identification::SchnorrIdentification::prove(
             rng,
-            &cp_parameters,
-            &cp_statement,
-            r,
+            &pp.enc_parameters.generator,
+            pk,
+            sk.expose_secret(),
             &mut fs_rng,
         )?;
 
-        Ok((masked_card, proof))
+        Ok(proof)
+    }
+
+    fn prove_key_ownership_in_game<B: ToBytes, R: Rng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        sk: &Self::PlayerSecretKey,
+        player_public_info: &B,
+        participants_digest: &[u8],
+    ) -> Result<Self::ZKProofKeyOwnership, CardProtocolError> {
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![
+            GAME_KEY_OWN_RNG_SEED,
+            participants_digest,
+            pk,
+            player_public_info
+        ]?);
+
+        let proof = schnorr_identification::SchnorrIdentification::prove(
+            rng,
+            &pp.enc_parameters.generator,
+            pk,
+            sk.expose_secret(),
+            &mut fs_rng,
+        )?;
+
+        Ok(proof)
     }
+}
 
+impl<'a, C: ProjectiveCurve> MaskingVerification for DLCards<'a, C> {
     fn verify_mask(
         pp: &Self::Parameters,
         shared_key: &Self::AggregatePublicKey,
         card: &Self::Card,
         masked_card: &Self::MaskedCard,
         proof: &Self::ZKProofMasking,
-    ) -> Result<(), CryptoError> {
+    ) -> Result<(), CardProtocolError> {
+        Self::validate_card(card)?;
+        Self::validate_masked_card(masked_card)?;
+
         // Map to Chaum-Pedersen parameters
         let cp_parameters =
             chaum_pedersen_dl_equality::Parameters::new(&pp.enc_parameters.generator, shared_key);
@@ -236,16 +497,21 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
             proof,
             &mut fs_rng,
         )
+        .map_err(|source| CardProtocolError::MaskingVerificationError {
+            source,
+            context: ErrorContext::default(),
+        })
     }
 
-    fn remask<R: Rng>(
-        rng: &mut R,
+    fn verify_remask(
         pp: &Self::Parameters,
         shared_key: &Self::AggregatePublicKey,
-        original_card: &Self::MaskedCard,
-        alpha: &Self::Scalar,
-    ) -> Result<(Self::MaskedCard, Self::ZKProofRemasking), CardProtocolError> {
-        let remasked = original_card.remask(&pp.enc_parameters, shared_key, alpha)?;
+        original_masked: &Self::MaskedCard,
+        remasked: &Self::MaskedCard,
+        proof: &Self::ZKProofRemasking,
+    ) -> Result<(), CardProtocolError> {
+        Self::validate_masked_card(original_masked)?;
+        Self::validate_masked_card(remasked)?;
 
         // Map to Chaum-Pedersen parameters
         let cp_parameters =
@@ -253,88 +519,104 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
         // Map to Chaum-Pedersen statement
         let minus_one = -C::ScalarField::one();
-        let negative_original = *original_card * minus_one;
-        let statement_cipher = remasked + negative_original;
+        let negative_original = *original_masked * minus_one;
+        let statement_cipher = *remasked + negative_original;
         let cp_statement =
             chaum_pedersen_dl_equality::Statement::new(&statement_cipher.0, &statement_cipher.1);
 
         let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REMASKING_RNG_SEED]?);
-        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
-            rng,
+        chaum_pedersen_dl_equality::DLEquality::verify(
             &cp_parameters,
             &cp_statement,
-            alpha,
+            proof,
             &mut fs_rng,
-        )?;
-
-        Ok((remasked, proof))
+        )
+        .map_err(|source| CardProtocolError::RemaskingVerificationError {
+            source,
+            context: ErrorContext::default(),
+        })
     }
+}
 
-    fn verify_remask(
+impl<'a, C: ProjectiveCurve> CardMasking for DLCards<'a, C> {
+    fn mask<R: Rng>(
+        rng: &mut R,
         pp: &Self::Parameters,
         shared_key: &Self::AggregatePublicKey,
-        original_masked: &Self::MaskedCard,
-        remasked: &Self::MaskedCard,
-        proof: &Self::ZKProofRemasking,
-    ) -> Result<(), CryptoError> {
+        original_card: &Self::Card,
+        r: &Witness<Self::Scalar>,
+    ) -> Result<(Self::MaskedCard, Self::ZKProofMasking), CardProtocolError> {
+        let masked_card = original_card.mask(&pp.enc_parameters, shared_key, r.expose_secret())?;
+        let gen = pp.enc_parameters.generator;
+
         // Map to Chaum-Pedersen parameters
-        let cp_parameters =
-            chaum_pedersen_dl_equality::Parameters::new(&pp.enc_parameters.generator, shared_key);
+        let cp_parameters = chaum_pedersen_dl_equality::Parameters::new(&gen, shared_key);
 
         // Map to Chaum-Pedersen statement
-        let minus_one = -C::ScalarField::one();
-        let negative_original = *original_masked * minus_one;
-        let statement_cipher = *remasked + negative_original;
+        let minus_one = -Self::Scalar::one();
+        let negative_original = original_card.0.mul(minus_one).into_affine();
+        let statement_cipher = masked_card.1 + negative_original;
         let cp_statement =
-            chaum_pedersen_dl_equality::Statement::new(&statement_cipher.0, &statement_cipher.1);
+            chaum_pedersen_dl_equality::Statement::new(&masked_card.0, &statement_cipher);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REMASKING_RNG_SEED]?);
-        chaum_pedersen_dl_equality::DLEquality::verify(
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![MASKING_RNG_SEED]?);
+        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+            rng,
             &cp_parameters,
             &cp_statement,
-            proof,
+            r.expose_secret(),
             &mut fs_rng,
-        )
+        )?;
+
+        Ok((masked_card, proof))
     }
 
-    fn compute_reveal_token<R: Rng>(
+    fn remask<R: Rng>(
         rng: &mut R,
         pp: &Self::Parameters,
-        sk: &Self::PlayerSecretKey,
-        pk: &Self::PlayerPublicKey,
-        masked_card: &Self::MaskedCard,
-    ) -> Result<(Self::RevealToken, Self::ZKProofReveal), CardProtocolError> {
-        let reveal_token: RevealToken<C> =
-            el_gamal::Plaintext(masked_card.0.into().mul(sk.into_repr()).into_affine());
+        shared_key: &Self::AggregatePublicKey,
+        original_card: &Self::MaskedCard,
+        alpha: &Witness<Self::Scalar>,
+    ) -> Result<(Self::MaskedCard, Self::ZKProofRemasking), CardProtocolError> {
+        let remasked =
+            original_card.remask(&pp.enc_parameters, shared_key, alpha.expose_secret())?;
 
         // Map to Chaum-Pedersen parameters
-        let cp_parameters = chaum_pedersen_dl_equality::Parameters::new(
-            &masked_card.0,
-            &pp.enc_parameters.generator,
-        );
+        let cp_parameters =
+            chaum_pedersen_dl_equality::Parameters::new(&pp.enc_parameters.generator, shared_key);
 
-        // Map to Chaum-Pedersen parameters
-        let cp_statement = chaum_pedersen_dl_equality::Statement::new(&reveal_token.0, pk);
+        // Map to Chaum-Pedersen statement
+        let minus_one = -C::ScalarField::one();
+        let negative_original = *original_card * minus_one;
+        let statement_cipher = remasked + negative_original;
+        let cp_statement =
+            chaum_pedersen_dl_equality::Statement::new(&statement_cipher.0, &statement_cipher.1);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REVEAL_RNG_SEED]?);
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REMASKING_RNG_SEED]?);
         let proof = chaum_pedersen_dl_equality::DLEquality::prove(
             rng,
             &cp_parameters,
             &cp_statement,
-            sk,
+            alpha.expose_secret(),
             &mut fs_rng,
         )?;
 
-        Ok((reveal_token, proof))
+        Ok((remasked, proof))
     }
+}
 
+impl<'a, C: ProjectiveCurve> RevealVerification for DLCards<'a, C> {
     fn verify_reveal(
         pp: &Self::Parameters,
         pk: &Self::PlayerPublicKey,
         reveal_token: &Self::RevealToken,
         masked_card: &Self::MaskedCard,
         proof: &Self::ZKProofReveal,
-    ) -> Result<(), CryptoError> {
+    ) -> Result<(), CardProtocolError> {
+        Self::validate_public_key(pk)?;
+        Self::validate_reveal_token(reveal_token)?;
+        Self::validate_masked_card(masked_card)?;
+
         // Map to Chaum-Pedersen parameters
         let cp_parameters = chaum_pedersen_dl_equality::Parameters::new(
             &masked_card.0,
@@ -351,23 +633,34 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
             proof,
             &mut fs_rng,
         )
+        .map_err(|source| CardProtocolError::RevealVerificationError {
+            source,
+            context: ErrorContext::default(),
+        })
     }
 
-    fn unmask(
+    fn unmask<'b>(
         pp: &Self::Parameters,
-        decryption_key: &Vec<(
-            Self::RevealToken,
-            Self::ZKProofReveal,
-            Self::PlayerPublicKey,
-        )>,
+        decryption_key: impl IntoIterator<
+            Item = &'b (
+                Self::RevealToken,
+                Self::ZKProofReveal,
+                Self::PlayerPublicKey,
+            ),
+        >,
         masked_card: &Self::MaskedCard,
-    ) -> Result<Self::Card, CardProtocolError> {
+    ) -> Result<Self::Card, CardProtocolError>
+    where
+        Self::RevealToken: 'b,
+        Self::ZKProofReveal: 'b,
+        Self::PlayerPublicKey: 'b,
+    {
         let zero = Self::RevealToken::zero();
 
         let mut aggregate_token = zero;
 
-        for (token, proof, pk) in decryption_key {
-            Self::verify_reveal(pp, pk, token, masked_card, proof)?;
+        for (i, (token, proof, pk)) in decryption_key.into_iter().enumerate() {
+            Self::verify_reveal(pp, pk, token, masked_card, proof).map_err(|e| e.with_player(i))?;
 
             aggregate_token = aggregate_token + *token;
         }
@@ -376,15 +669,106 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
         Ok(decrypted)
     }
+}
+
+impl<'a, C: ProjectiveCurve> CardReveal for DLCards<'a, C> {
+    fn compute_reveal_token<R: Rng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        sk: &Self::PlayerSecretKey,
+        pk: &Self::PlayerPublicKey,
+        masked_card: &Self::MaskedCard,
+    ) -> Result<(Self::RevealToken, Self::ZKProofReveal), CardProtocolError> {
+        let reveal_token: RevealToken<C> = el_gamal::Plaintext(
+            masked_card
+                .0
+                .into()
+                .mul(sk.expose_secret().into_repr())
+                .into_affine(),
+        );
+
+        // Map to Chaum-Pedersen parameters
+        let cp_parameters = chaum_pedersen_dl_equality::Parameters::new(
+            &masked_card.0,
+            &pp.enc_parameters.generator,
+        );
+
+        // Map to Chaum-Pedersen parameters
+        let cp_statement = chaum_pedersen_dl_equality::Statement::new(&reveal_token.0, pk);
+
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REVEAL_RNG_SEED]?);
+        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+            rng,
+            &cp_parameters,
+            &cp_statement,
+            sk.expose_secret(),
+            &mut fs_rng,
+        )?;
+
+        Ok((reveal_token, proof))
+    }
+}
+
+impl<'a, C: ProjectiveCurve> ShuffleVerification for DLCards<'a, C> {
+    fn verify_shuffle(
+        pp: &Self::Parameters,
+        shared_key: &Self::AggregatePublicKey,
+        original_deck: &[Self::MaskedCard],
+        shuffled_deck: &[Self::MaskedCard],
+        proof: &Self::ZKProofShuffle,
+    ) -> Result<(), CardProtocolError> {
+        pp.check_deck_size(original_deck.len())?;
+        pp.check_deck_size(shuffled_deck.len())?;
+
+        for deck in [original_deck, shuffled_deck] {
+            for (i, masked_card) in deck.iter().enumerate() {
+                Self::validate_masked_card(masked_card).map_err(|e| e.with_card(i))?;
+            }
+        }
+
+        // The shuffle argument works on owned vectors
+        let original_deck = original_deck.to_vec();
+        let shuffled_deck = shuffled_deck.to_vec();
+
+        let shuffle_parameters = shuffle::Parameters::new(
+            &pp.enc_parameters,
+            shared_key,
+            &pp.commit_parameters,
+            &pp.generator,
+        );
+
+        let shuffle_statement = shuffle::Statement::new(&original_deck, &shuffled_deck, pp.m, pp.n);
+
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![SHUFFLE_RNG_SEED]?);
+        shuffle::ShuffleArgument::verify(
+            &shuffle_parameters,
+            &shuffle_statement,
+            proof,
+            &mut fs_rng,
+        )
+        .map_err(|source| CardProtocolError::ShuffleVerificationError {
+            source,
+            context: ErrorContext::default(),
+        })
+    }
+}
 
+impl<'a, C: ProjectiveCurve> DeckShuffle for DLCards<'a, C> {
     fn shuffle_and_remask<R: Rng>(
         rng: &mut R,
         pp: &Self::Parameters,
         shared_key: &Self::AggregatePublicKey,
-        deck: &Vec<Self::MaskedCard>,
-        masking_factors: &Vec<Self::Scalar>,
+        deck: &[Self::MaskedCard],
+        masking_factors: &Witness<Vec<Self::Scalar>>,
         permutation: &Permutation,
     ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofShuffle), CardProtocolError> {
+        let masking_factors = masking_factors.expose_secret();
+        pp.check_deck_size(deck.len())?;
+        pp.check_deck_size(masking_factors.len())?;
+
+        // The shuffle argument works on owned vectors
+        let deck = deck.to_vec();
+
         let permuted_deck = permutation.permute_array(&deck);
         let masked_shuffled = permuted_deck
             .iter()
@@ -401,7 +785,7 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
             &pp.generator,
         );
 
-        let shuffle_statement = shuffle::Statement::new(deck, &masked_shuffled, pp.m, pp.n);
+        let shuffle_statement = shuffle::Statement::new(&deck, &masked_shuffled, pp.m, pp.n);
 
         let witness = shuffle::Witness::new(permutation, masking_factors);
 
@@ -416,29 +800,4 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
         Ok((masked_shuffled, proof))
     }
-
-    fn verify_shuffle(
-        pp: &Self::Parameters,
-        shared_key: &Self::AggregatePublicKey,
-        original_deck: &Vec<Self::MaskedCard>,
-        shuffled_deck: &Vec<Self::MaskedCard>,
-        proof: &Self::ZKProofShuffle,
-    ) -> Result<(), CryptoError> {
-        let shuffle_parameters = shuffle::Parameters::new(
-            &pp.enc_parameters,
-            shared_key,
-            &pp.commit_parameters,
-            &pp.generator,
-        );
-
-        let shuffle_statement = shuffle::Statement::new(original_deck, shuffled_deck, pp.m, pp.n);
-
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![SHUFFLE_RNG_SEED]?);
-        shuffle::ShuffleArgument::verify(
-            &shuffle_parameters,
-            &shuffle_statement,
-            proof,
-            &mut fs_rng,
-        )
-    }
 }
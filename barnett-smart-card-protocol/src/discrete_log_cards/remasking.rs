@@ -24,7 +24,9 @@ impl<C: ProjectiveCurve> Remask<C::ScalarField, ElGamal<C>> for MaskedCard<C> {
 #[cfg(test)]
 mod test {
     use crate::discrete_log_cards;
-    use crate::BarnettSmartProtocol;
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{CardMasking, KeyManagement, MaskingVerification, ProtocolSetup};
 
     use ark_ff::UniformRand;
     use ark_std::{rand::Rng, Zero};
@@ -75,7 +77,7 @@ mod test {
         let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players);
 
         let some_masked_card = MaskedCard::rand(rng);
-        let some_random = Scalar::rand(rng);
+        let some_random = Witness::new(Scalar::rand(rng));
 
         let (remasked, remasking_proof): (MaskedCard, RemaskingProof) = CardProtocol::remask(
             rng,
@@ -107,9 +109,10 @@ mod test {
                 &wrong_output,
                 &remasking_proof
             ),
-            Err(CryptoError::ProofVerificationError(String::from(
-                "Chaum-Pedersen"
-            )))
+            Err(CardProtocolError::RemaskingVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Chaum-Pedersen")),
+                context: ErrorContext::default(),
+            })
         )
     }
 }
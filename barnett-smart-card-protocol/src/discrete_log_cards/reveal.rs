@@ -22,7 +22,8 @@ impl<C: ProjectiveCurve> Reveal<C::ScalarField, ElGamal<C>> for RevealToken<C> {
 #[cfg(test)]
 mod test {
     use crate::discrete_log_cards;
-    use crate::BarnettSmartProtocol;
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::{CardReveal, KeyManagement, ProtocolSetup, RevealVerification};
 
     use ark_ff::UniformRand;
     use proof_essentials::error::CryptoError;
@@ -77,9 +78,10 @@ mod test {
                 &some_masked_card,
                 &reveal_proof
             ),
-            Err(CryptoError::ProofVerificationError(String::from(
-                "Chaum-Pedersen"
-            )))
+            Err(CardProtocolError::RevealVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Chaum-Pedersen")),
+                context: ErrorContext::default(),
+            })
         )
     }
 }
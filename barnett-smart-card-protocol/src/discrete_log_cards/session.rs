@@ -0,0 +1,1075 @@
+use crate::discrete_log_cards::liveness::{combine_partial_tokens, open_stall_report};
+use crate::discrete_log_cards::message::{MessageContext, SessionId};
+use crate::discrete_log_cards::{
+    AbortCertificate, Card, DLCards, Deadlines, KeyEscrow, KeyOwnershipProof, KeySwitchProof,
+    MaskedCard, MaskingProof, Parameters, PublicKey, Recovery, RevealProof, RevealToken,
+    ShuffleProof, Stall, Step,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::{
+    KeyOwnershipVerification, KeySwitchVerification, MaskingVerification, PointValidation, Reveal,
+    RevealVerification, ShuffleVerification,
+};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_std::Zero;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Phases of a game, in the order in which a `GameSession` goes through them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionPhase {
+    /// Players announce their keys and proofs of ownership
+    Registration,
+    /// The aggregate key is known, the initial deck is expected
+    KeyAggregation,
+    /// Each player shuffles the deck in turn; `round` is the index of the player expected to shuffle
+    Shuffling { round: usize },
+    /// Cards of the final deck are dealt to the players, who collect reveal tokens for them
+    Dealing,
+    /// Cards are opened in public as the game goes on
+    Play,
+    /// Players open their cards
+    Showdown,
+}
+
+impl SessionPhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Registration => "registration",
+            Self::KeyAggregation => "key aggregation",
+            Self::Shuffling { .. } => "shuffling",
+            Self::Dealing => "dealing",
+            Self::Play => "play",
+            Self::Showdown => "showdown",
+        }
+    }
+}
+
+/// Public state of a game, checking every message of the players as it comes in and enforcing the order
+/// of the protocol steps: keys are aggregated once registration is over, the initial deck is masked under
+/// the aggregate key, every player shuffles it in turn, and only then are cards dealt and revealed.
+/// Operations attempted in the wrong phase fail with `WrongProtocolPhase` and leave the session
+/// unchanged, as do messages that do not verify.
+///
+/// A session never handles secret keys: each player runs the protocol locally with `DLCards` and feeds
+/// the resulting public messages to the session.
+///
+/// Shuffles and requested reveals must come within the session's `Deadlines`. `expire` records the
+/// players who missed one as stalled, after which the game can go on without them: a stalled shuffler
+/// is excluded and the deck rebuilt by the others, and the reveal token of a stalled player is rebuilt
+/// from the shares of their key they escrowed with the other players. Both recoveries need every other
+/// player to report the stall in a signed message of the session set with `set_session_id`, and yield an
+/// `AbortCertificate` carrying these reports.
+pub struct GameSession<C: ProjectiveCurve> {
+    pp: Parameters<C>,
+    phase: SessionPhase,
+    players: Vec<(PublicKey<C>, KeyOwnershipProof<C>, Vec<u8>)>,
+    shared_key: Option<PublicKey<C>>,
+    deck: Vec<MaskedCard<C>>,
+    /// Player each card of the final deck was dealt to
+    owners: Vec<Option<usize>>,
+    /// Cards of the final deck dealt to players who left the game
+    discarded: Vec<bool>,
+    /// Players who left the hand
+    folded: Vec<bool>,
+    /// Reveal tokens received for each card of the final deck, indexed by player
+    tokens: Vec<Vec<Option<(RevealToken<C>, RevealProof<C>)>>>,
+    deadlines: Deadlines,
+    /// Start of the current shuffle round
+    round_started: Option<Instant>,
+    /// When each card of the final deck was requested to be revealed
+    reveal_requests: Vec<Option<Instant>>,
+    stalls: Vec<Stall>,
+    /// Session the signed stall reports belong to
+    session_id: Option<SessionId>,
+    /// Signed reports of each recorded stall, with their sender
+    stall_reports: HashMap<Stall, Vec<(usize, Vec<u8>)>>,
+    /// Escrow of the key of each player, if they published one
+    escrows: Vec<Option<KeyEscrow<C>>>,
+    /// Partial tokens received for the card and the stalled player of the key, with their holder
+    partial_tokens: HashMap<(usize, usize), Vec<(usize, RevealToken<C>, RevealProof<C>)>>,
+    /// Reveal tokens of stalled players rebuilt from partial tokens, by card and player
+    recovered_tokens: HashMap<(usize, usize), RevealToken<C>>,
+}
+
+impl<C: ProjectiveCurve> GameSession<C> {
+    pub fn new(pp: Parameters<C>) -> Self {
+        Self {
+            pp,
+            phase: SessionPhase::Registration,
+            players: Vec::new(),
+            shared_key: None,
+            deck: Vec::new(),
+            owners: Vec::new(),
+            discarded: Vec::new(),
+            folded: Vec::new(),
+            tokens: Vec::new(),
+            deadlines: Deadlines::default(),
+            round_started: None,
+            reveal_requests: Vec::new(),
+            stalls: Vec::new(),
+            session_id: None,
+            stall_reports: HashMap::new(),
+            escrows: Vec::new(),
+            partial_tokens: HashMap::new(),
+            recovered_tokens: HashMap::new(),
+        }
+    }
+
+    /// Set the time given to players to complete each step
+    pub fn set_deadlines(&mut self, deadlines: Deadlines) {
+        self.deadlines = deadlines;
+    }
+
+    /// Set the identifier of the session, which signed stall reports are checked against
+    pub fn set_session_id(&mut self, session_id: SessionId) {
+        self.session_id = Some(session_id);
+    }
+
+    pub fn parameters(&self) -> &Parameters<C> {
+        &self.pp
+    }
+
+    pub fn phase(&self) -> SessionPhase {
+        self.phase
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.players.len()
+    }
+
+    /// Public key of the player at index `player`
+    pub fn player_key(&self, player: usize) -> Option<&PublicKey<C>> {
+        self.players.get(player).map(|(pk, _, _)| pk)
+    }
+
+    /// Aggregate key of the game, once registration is over
+    pub fn shared_key(&self) -> Option<&PublicKey<C>> {
+        self.shared_key.as_ref()
+    }
+
+    /// Current deck: the initial masked deck, then the output of the last verified shuffle
+    pub fn deck(&self) -> &[MaskedCard<C>] {
+        &self.deck
+    }
+
+    /// Player the card at `card_index` of the final deck was dealt to, if any
+    pub fn owner(&self, card_index: usize) -> Option<usize> {
+        self.owners.get(card_index).copied().flatten()
+    }
+
+    /// Verified reveal tokens received so far for the card at `card_index`, with the key of their issuer.
+    /// The owner of a dealt card combines them with their own token to open the card privately.
+    pub fn reveal_tokens(
+        &self,
+        card_index: usize,
+    ) -> Vec<(RevealToken<C>, RevealProof<C>, PublicKey<C>)> {
+        self.tokens
+            .get(card_index)
+            .into_iter()
+            .flatten()
+            .zip(self.players.iter())
+            .filter_map(|(token, (pk, _, _))| {
+                token.as_ref().map(|(token, proof)| (*token, *proof, *pk))
+            })
+            .collect()
+    }
+
+    fn expect_phase(&self, expected: &str, allowed: bool) -> Result<(), CardProtocolError> {
+        if !allowed {
+            return Err(CardProtocolError::WrongProtocolPhase {
+                expected: String::from(expected),
+                found: String::from(self.phase.name()),
+                context: ErrorContext::default(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn check_card_index(&self, card_index: usize) -> Result<(), CardProtocolError> {
+        if card_index >= self.deck.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!("no card at index {}", card_index),
+                context: ErrorContext::card(card_index),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Register a player with their key and proof of ownership, which is checked once all players are
+    /// known. Returns the index of the player in the turn order.
+    pub fn register_player(
+        &mut self,
+        pk: PublicKey<C>,
+        proof: KeyOwnershipProof<C>,
+        player_public_info: Vec<u8>,
+    ) -> Result<usize, CardProtocolError> {
+        self.expect_phase("registration", self.phase == SessionPhase::Registration)?;
+
+        self.players.push((pk, proof, player_public_info));
+        Ok(self.players.len() - 1)
+    }
+
+    /// Close registration: verify the key ownership proofs of all players and compute the aggregate key.
+    pub fn aggregate_keys(&mut self) -> Result<&PublicKey<C>, CardProtocolError> {
+        self.expect_phase("registration", self.phase == SessionPhase::Registration)?;
+        if self.players.is_empty() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("no player registered"),
+                context: ErrorContext::default(),
+            });
+        }
+
+        let shared_key = DLCards::<C>::compute_aggregate_key(&self.pp, &self.players)?;
+        self.folded = vec![false; self.players.len()];
+        self.escrows = vec![None; self.players.len()];
+        self.phase = SessionPhase::KeyAggregation;
+
+        Ok(self.shared_key.insert(shared_key))
+    }
+
+    /// Verify the masking of the initial deck under the aggregate key and start the shuffle rounds.
+    pub fn submit_initial_deck(
+        &mut self,
+        masked_deck: &[(Card<C>, MaskedCard<C>, MaskingProof<C>)],
+    ) -> Result<(), CardProtocolError> {
+        self.expect_phase(
+            "key aggregation",
+            self.phase == SessionPhase::KeyAggregation,
+        )?;
+        let shared_key = self
+            .shared_key
+            .expect("aggregate key is set after registration");
+
+        self.pp.check_deck_size(masked_deck.len())?;
+        for (i, (card, masked, proof)) in masked_deck.iter().enumerate() {
+            DLCards::<C>::verify_mask(&self.pp, &shared_key, card, masked, proof)
+                .map_err(|e| e.with_card(i))?;
+        }
+
+        self.deck = masked_deck.iter().map(|(_, masked, _)| *masked).collect();
+        self.phase = SessionPhase::Shuffling { round: 0 };
+        self.round_started = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// Verify the shuffle of the current deck by the player at index `player`, who must be the player
+    /// expected to shuffle in this round. Dealing starts once every player has shuffled.
+    pub fn submit_shuffle(
+        &mut self,
+        player: usize,
+        shuffled_deck: Vec<MaskedCard<C>>,
+        proof: &ShuffleProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        let round = match self.phase {
+            SessionPhase::Shuffling { round } => round,
+            _ => return self.expect_phase("shuffling", false),
+        };
+        if player != round {
+            return Err(CardProtocolError::OutOfTurn {
+                expected: round,
+                context: ErrorContext::player(player),
+            });
+        }
+        let shared_key = self
+            .shared_key
+            .expect("aggregate key is set after registration");
+
+        DLCards::<C>::verify_shuffle(&self.pp, &shared_key, &self.deck, &shuffled_deck, proof)
+            .map_err(|e| e.with_player(player))?;
+
+        self.deck = shuffled_deck;
+        if round + 1 < self.players.len() {
+            self.phase = SessionPhase::Shuffling { round: round + 1 };
+            self.round_started = Some(Instant::now());
+        } else {
+            self.owners = vec![None; self.deck.len()];
+            self.discarded = vec![false; self.deck.len()];
+            self.tokens = vec![vec![None; self.players.len()]; self.deck.len()];
+            self.reveal_requests = vec![None; self.deck.len()];
+            self.round_started = None;
+            self.phase = SessionPhase::Dealing;
+        }
+
+        Ok(())
+    }
+
+    /// Seat a new player while the deck is being shuffled. The player proves ownership of their key for
+    /// the set of players including them, and moves the current deck to the aggregate key extended with
+    /// their key. The switch leaves the masking of the cards unchanged, so it publishes the partial
+    /// decryptions of the current deck by the new player: players only join before the deck is final and
+    /// shuffle last, so that the deck is remasked before any card is dealt. Returns the index of the new
+    /// player.
+    pub fn admit_player(
+        &mut self,
+        pk: PublicKey<C>,
+        proof: KeyOwnershipProof<C>,
+        player_public_info: Vec<u8>,
+        switched_deck: Vec<MaskedCard<C>>,
+        switch_proof: &KeySwitchProof<C>,
+    ) -> Result<usize, CardProtocolError> {
+        self.expect_phase(
+            "shuffling",
+            matches!(self.phase, SessionPhase::Shuffling { .. }),
+        )?;
+        let player = self.players.len();
+        DLCards::<C>::validate_public_key(&pk).map_err(|e| e.with_player(player))?;
+        if self.players.iter().any(|(key, _, _)| *key == pk) {
+            return Err(CardProtocolError::DuplicatePlayerKey {
+                context: ErrorContext::player(player),
+            });
+        }
+        if self
+            .players
+            .iter()
+            .any(|(_, _, info)| *info == player_public_info)
+        {
+            return Err(CardProtocolError::DuplicatePlayerIdentity {
+                context: ErrorContext::player(player),
+            });
+        }
+
+        let participants_digest = DLCards::<C>::participants_digest(
+            self.players
+                .iter()
+                .map(|(pk, _, info)| (pk, info))
+                .chain(std::iter::once((&pk, &player_public_info))),
+        )?;
+        DLCards::<C>::verify_key_ownership_in_game(
+            &self.pp,
+            &pk,
+            &player_public_info,
+            &participants_digest,
+            &proof,
+        )
+        .map_err(|e| e.with_player(player))?;
+        DLCards::<C>::verify_key_addition(&self.pp, &pk, &self.deck, &switched_deck, switch_proof)
+            .map_err(|e| e.with_player(player))?;
+
+        let shared_key = self
+            .shared_key
+            .expect("aggregate key is set after registration");
+        self.shared_key = Some((shared_key.into_projective() + pk.into_projective()).into_affine());
+        self.deck = switched_deck;
+        self.players.push((pk, proof, player_public_info));
+        self.folded.push(false);
+        self.escrows.push(None);
+        self.stall_reports.clear();
+
+        Ok(player)
+    }
+
+    /// Let the player at index `player` leave once the deck is final. The player strips their key from the
+    /// aggregate key under which the deck is masked, publishing their partial decryption of every card
+    /// with a single proof, so that the remaining players can open cards without them. Players after the
+    /// one leaving move down one index, their reveal tokens stay valid, and the cards dealt to the player
+    /// leaving can no longer be opened. Escrows are dropped, as they were shared with the player leaving.
+    pub fn remove_player(
+        &mut self,
+        player: usize,
+        switched_deck: Vec<MaskedCard<C>>,
+        switch_proof: &KeySwitchProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        self.expect_phase(
+            "dealing or play",
+            matches!(self.phase, SessionPhase::Dealing | SessionPhase::Play),
+        )?;
+        let pk = *self
+            .player_key(player)
+            .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                reason: format!("unknown player {}", player),
+                context: ErrorContext::default(),
+            })?;
+        if self.players.len() == 1 {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("the last player cannot leave"),
+                context: ErrorContext::player(player),
+            });
+        }
+        DLCards::<C>::verify_key_removal(&self.pp, &pk, &self.deck, &switched_deck, switch_proof)
+            .map_err(|e| e.with_player(player))?;
+
+        let shared_key = self
+            .shared_key
+            .expect("aggregate key is set after registration");
+        self.shared_key = Some((shared_key.into_projective() - pk.into_projective()).into_affine());
+        self.deck = switched_deck;
+        self.players.remove(player);
+        self.folded.remove(player);
+        self.escrows = vec![None; self.players.len()];
+        self.partial_tokens.clear();
+        self.stall_reports.clear();
+        for tokens in &mut self.tokens {
+            tokens.remove(player);
+        }
+
+        // Players after the one leaving move down one index
+        let shift = |other: usize| match other.cmp(&player) {
+            std::cmp::Ordering::Less => Some(other),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(other - 1),
+        };
+        for (owner, discarded) in self.owners.iter_mut().zip(&mut self.discarded) {
+            if *owner == Some(player) {
+                *discarded = true;
+            }
+            *owner = owner.and_then(shift);
+        }
+        self.stalls = self
+            .stalls
+            .iter()
+            .filter_map(|stall| {
+                shift(stall.player).map(|player| Stall {
+                    player,
+                    step: stall.step,
+                })
+            })
+            .collect();
+        self.recovered_tokens = self
+            .recovered_tokens
+            .drain()
+            .filter_map(|((card_index, stalled), token)| {
+                shift(stalled).map(|stalled| ((card_index, stalled), token))
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    /// Deal the card at `card_index` of the final deck to the player at index `player`.
+    pub fn deal(&mut self, card_index: usize, player: usize) -> Result<(), CardProtocolError> {
+        self.expect_phase("dealing", self.phase == SessionPhase::Dealing)?;
+        self.check_card_index(card_index)?;
+        if player >= self.players.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!("card dealt to unknown player {}", player),
+                context: ErrorContext::card(card_index),
+            });
+        }
+        if self.owners[card_index].is_some() || self.discarded[card_index] {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("card dealt twice"),
+                context: ErrorContext::card(card_index),
+            });
+        }
+
+        self.owners[card_index] = Some(player);
+
+        Ok(())
+    }
+
+    /// End dealing and start playing.
+    pub fn start_play(&mut self) -> Result<(), CardProtocolError> {
+        self.expect_phase("dealing", self.phase == SessionPhase::Dealing)?;
+        self.phase = SessionPhase::Play;
+
+        Ok(())
+    }
+
+    /// Record that the player at index `player` left the hand. Their cards will never be opened, but
+    /// their reveal tokens are still needed to open any other card.
+    pub fn fold(&mut self, player: usize) -> Result<(), CardProtocolError> {
+        self.expect_phase(
+            "dealing or play",
+            matches!(self.phase, SessionPhase::Dealing | SessionPhase::Play),
+        )?;
+        if player >= self.players.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!("unknown player {}", player),
+                context: ErrorContext::default(),
+            });
+        }
+
+        self.folded[player] = true;
+
+        Ok(())
+    }
+
+    pub fn has_folded(&self, player: usize) -> bool {
+        self.folded.get(player).copied().unwrap_or(false)
+    }
+
+    /// End playing and start the showdown.
+    pub fn start_showdown(&mut self) -> Result<(), CardProtocolError> {
+        self.expect_phase("play", self.phase == SessionPhase::Play)?;
+        self.phase = SessionPhase::Showdown;
+
+        Ok(())
+    }
+
+    /// Verify and record the reveal token of the player at index `player` for the card at `card_index`
+    /// of the final deck. Tokens are accepted once the deck has been shuffled by every player.
+    pub fn submit_reveal(
+        &mut self,
+        card_index: usize,
+        player: usize,
+        token: RevealToken<C>,
+        proof: RevealProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        self.expect_phase(
+            "dealing, play or showdown",
+            matches!(
+                self.phase,
+                SessionPhase::Dealing | SessionPhase::Play | SessionPhase::Showdown
+            ),
+        )?;
+        self.check_card_index(card_index)?;
+        let pk = self
+            .player_key(player)
+            .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                reason: format!("reveal token issued by unknown player {}", player),
+                context: ErrorContext::card(card_index),
+            })?;
+
+        DLCards::<C>::verify_reveal(&self.pp, pk, &token, &self.deck[card_index], &proof)
+            .map_err(|e| e.with_player(player).with_card(card_index))?;
+        self.tokens[card_index][player] = Some((token, proof));
+
+        Ok(())
+    }
+
+    /// Open the card at `card_index` of the final deck, which requires a reveal token from every player.
+    /// Cards dealt to players who folded or left cannot be opened.
+    pub fn open_card(&self, card_index: usize) -> Result<Card<C>, CardProtocolError> {
+        self.expect_phase(
+            "play or showdown",
+            matches!(self.phase, SessionPhase::Play | SessionPhase::Showdown),
+        )?;
+        self.check_card_index(card_index)?;
+        if self.discarded[card_index] {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("card of a player who left"),
+                context: ErrorContext::card(card_index),
+            });
+        }
+        if let Some(owner) = self.owner(card_index).filter(|&owner| self.folded[owner]) {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("card of a folded hand"),
+                context: ErrorContext {
+                    player: Some(owner),
+                    card: Some(card_index),
+                },
+            });
+        }
+
+        // Tokens were verified when submitted, or rebuilt from verified partial tokens
+        let mut aggregate_token = RevealToken::<C>::zero();
+        for (player, token) in self.tokens[card_index].iter().enumerate() {
+            let token = match token {
+                Some((token, _)) => token,
+                None => self
+                    .recovered_tokens
+                    .get(&(card_index, player))
+                    .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                        reason: String::from("missing reveal token"),
+                        context: ErrorContext {
+                            player: Some(player),
+                            card: Some(card_index),
+                        },
+                    })?,
+            };
+            aggregate_token = aggregate_token + *token;
+        }
+
+        aggregate_token.reveal(&self.deck[card_index])
+    }
+
+    /// Ask the players for their reveal tokens for the card at `card_index` of the final deck, starting
+    /// the reveal deadline. The owner of a dealt card only owes their token at the showdown.
+    pub fn request_reveal(&mut self, card_index: usize) -> Result<(), CardProtocolError> {
+        self.expect_phase(
+            "dealing, play or showdown",
+            matches!(
+                self.phase,
+                SessionPhase::Dealing | SessionPhase::Play | SessionPhase::Showdown
+            ),
+        )?;
+        self.check_card_index(card_index)?;
+
+        self.reveal_requests[card_index] = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// Record the players who, at time `now`, missed the deadline of the shuffle round or of a requested
+    /// reveal. Returns the stalls not recorded before.
+    pub fn expire(&mut self, now: Instant) -> Vec<Stall> {
+        let overdue =
+            |started: Instant, deadline| now.saturating_duration_since(started) > deadline;
+        let mut stalls = Vec::new();
+
+        if let (SessionPhase::Shuffling { round }, Some(started)) = (self.phase, self.round_started)
+        {
+            if overdue(started, self.deadlines.shuffle) {
+                stalls.push(Stall {
+                    player: round,
+                    step: Step::Shuffle { round },
+                });
+            }
+        }
+
+        for (card_index, requested) in self.reveal_requests.iter().enumerate() {
+            if !requested.map_or(false, |requested| overdue(requested, self.deadlines.reveal)) {
+                continue;
+            }
+
+            let owner = self.owner(card_index);
+            for player in 0..self.players.len() {
+                let owes_token = self.tokens[card_index][player].is_none()
+                    && !self.recovered_tokens.contains_key(&(card_index, player))
+                    && (owner != Some(player) || self.phase == SessionPhase::Showdown);
+                if owes_token {
+                    stalls.push(Stall {
+                        player,
+                        step: Step::Reveal { card_index },
+                    });
+                }
+            }
+        }
+
+        stalls.retain(|stall| !self.stalls.contains(stall));
+        self.stalls.extend(stalls.iter().copied());
+        stalls
+    }
+
+    /// Players recorded as stalled so far, with the step they missed
+    pub fn stalls(&self) -> &[Stall] {
+        &self.stalls
+    }
+
+    fn session_id(&self) -> Result<SessionId, CardProtocolError> {
+        self.session_id
+            .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                reason: String::from("session id is not set"),
+                context: ErrorContext::default(),
+            })
+    }
+
+    /// Verify and record a signed `StallReport` message, by which a player reports a stall recorded by
+    /// `expire`. Reports are signed for the current players, and must be sent again if a player joins
+    /// or leaves. Returns the reported stall.
+    pub fn submit_stall_report(&mut self, report: Vec<u8>) -> Result<Stall, CardProtocolError> {
+        let players = self.player_keys();
+        let context = MessageContext::new(&self.pp, self.session_id()?, players.len() as u32)?;
+        let (sender, stall) = open_stall_report(&context, &self.pp, &report, &players)?;
+        if !self.stalls.contains(&stall) {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("player did not stall"),
+                context: ErrorContext::player(stall.player),
+            });
+        }
+
+        let reports = self.stall_reports.entry(stall).or_default();
+        if reports.iter().any(|(reporter, _)| *reporter == sender) {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("stall already reported"),
+                context: ErrorContext::player(sender),
+            });
+        }
+        reports.push((sender, report));
+
+        Ok(stall)
+    }
+
+    /// Signed reports of `stall`, which must have been recorded and reported by every other player
+    fn certified_stall(&self, stall: Stall) -> Result<Vec<Vec<u8>>, CardProtocolError> {
+        let context = ErrorContext::player(stall.player);
+        if !self.stalls.contains(&stall) {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("player did not stall"),
+                context,
+            });
+        }
+
+        let reports = self
+            .stall_reports
+            .get(&stall)
+            .map_or(&[][..], Vec::as_slice);
+        if reports.len() + 1 < self.players.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("stall not reported by every other player"),
+                context,
+            });
+        }
+
+        Ok(reports.iter().map(|(_, report)| report.clone()).collect())
+    }
+
+    fn player_keys(&self) -> Vec<PublicKey<C>> {
+        self.players.iter().map(|(pk, _, _)| *pk).collect()
+    }
+
+    /// Exclude a player who stalled before the deck was fully shuffled. The remaining players keep their
+    /// order, the aggregate key becomes theirs, and the initial deck is expected again. Escrows are
+    /// dropped, as they were shared with the excluded player.
+    pub fn exclude_player(
+        &mut self,
+        player: usize,
+    ) -> Result<AbortCertificate<C>, CardProtocolError> {
+        self.expect_phase(
+            "key aggregation or shuffling",
+            matches!(
+                self.phase,
+                SessionPhase::KeyAggregation | SessionPhase::Shuffling { .. }
+            ),
+        )?;
+        let stall = self
+            .stalls
+            .iter()
+            .find(|stall| stall.player == player && matches!(stall.step, Step::Shuffle { .. }))
+            .copied()
+            .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                reason: String::from("player did not stall"),
+                context: ErrorContext::player(player),
+            })?;
+        let reports = self.certified_stall(stall)?;
+        let session_id = self.session_id()?;
+        if self.players.len() == 1 {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("cannot exclude the last player"),
+                context: ErrorContext::player(player),
+            });
+        }
+        let players = self.player_keys();
+
+        // Keys of the remaining players were proven when registering, so their sum is safe to use
+        self.players.remove(player);
+        let shared_key = self
+            .players
+            .iter()
+            .fold(C::zero(), |acc, (pk, _, _)| acc + pk.into_projective())
+            .into_affine();
+        self.shared_key = Some(shared_key);
+        self.folded = vec![false; self.players.len()];
+        self.escrows = vec![None; self.players.len()];
+        self.deck.clear();
+        self.round_started = None;
+        self.stalls.clear();
+        self.stall_reports.clear();
+        self.phase = SessionPhase::KeyAggregation;
+
+        Ok(AbortCertificate {
+            session_id,
+            stall,
+            players,
+            reports,
+            recovery: Recovery::Exclusion { shared_key },
+        })
+    }
+
+    /// Record the escrow of the key of the player at index `player`, which must commit to their key and
+    /// need fewer shares than there are other players.
+    pub fn submit_key_escrow(
+        &mut self,
+        player: usize,
+        escrow: KeyEscrow<C>,
+    ) -> Result<(), CardProtocolError> {
+        self.expect_phase(
+            "key aggregation or shuffling",
+            matches!(
+                self.phase,
+                SessionPhase::KeyAggregation | SessionPhase::Shuffling { .. }
+            ),
+        )?;
+        let pk = self
+            .player_key(player)
+            .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                reason: format!("unknown player {}", player),
+                context: ErrorContext::default(),
+            })?;
+        if escrow.commitments.first() != Some(pk) || escrow.threshold() >= self.players.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("escrow does not commit to the key of the player"),
+                context: ErrorContext::player(player),
+            });
+        }
+
+        self.escrows[player] = Some(escrow);
+
+        Ok(())
+    }
+
+    /// Verify and record a partial token for the card at `card_index`, computed by the player at index
+    /// `holder` with their share of the key of the stalled player at index `stalled`. Once enough partial
+    /// tokens are in, the reveal token of the stalled player is rebuilt and a certificate is returned.
+    pub fn submit_escrowed_reveal(
+        &mut self,
+        card_index: usize,
+        stalled: usize,
+        holder: usize,
+        token: RevealToken<C>,
+        proof: RevealProof<C>,
+    ) -> Result<Option<AbortCertificate<C>>, CardProtocolError> {
+        self.expect_phase(
+            "dealing, play or showdown",
+            matches!(
+                self.phase,
+                SessionPhase::Dealing | SessionPhase::Play | SessionPhase::Showdown
+            ),
+        )?;
+        self.check_card_index(card_index)?;
+        let stall = Stall {
+            player: stalled,
+            step: Step::Reveal { card_index },
+        };
+        let reports = self
+            .certified_stall(stall)
+            .map_err(|e| e.with_card(card_index))?;
+        let session_id = self.session_id()?;
+        let escrow = self
+            .escrows
+            .get(stalled)
+            .cloned()
+            .flatten()
+            .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                reason: String::from("no escrow for the key of the stalled player"),
+                context: ErrorContext::player(stalled),
+            })?;
+        if self.recovered_tokens.contains_key(&(card_index, stalled)) {
+            return Ok(None);
+        }
+
+        let partial_tokens = self
+            .partial_tokens
+            .entry((card_index, stalled))
+            .or_default();
+        if holder >= self.players.len()
+            || holder == stalled
+            || partial_tokens.iter().any(|(h, _, _)| *h == holder)
+        {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!("invalid share holder {}", holder),
+                context: ErrorContext {
+                    player: Some(stalled),
+                    card: Some(card_index),
+                },
+            });
+        }
+
+        DLCards::<C>::verify_reveal(
+            &self.pp,
+            &escrow.share_key(holder),
+            &token,
+            &self.deck[card_index],
+            &proof,
+        )
+        .map_err(|e| e.with_player(holder).with_card(card_index))?;
+        partial_tokens.push((holder, token, proof));
+        if partial_tokens.len() < escrow.threshold() {
+            return Ok(None);
+        }
+
+        let partial_tokens = self
+            .partial_tokens
+            .remove(&(card_index, stalled))
+            .unwrap_or_default();
+        let token = combine_partial_tokens(&partial_tokens);
+        self.recovered_tokens.insert((card_index, stalled), token);
+
+        Ok(Some(AbortCertificate {
+            session_id,
+            stall,
+            players: self.player_keys(),
+            reports,
+            recovery: Recovery::ThresholdReveal {
+                card_index,
+                masked_card: self.deck[card_index],
+                escrow,
+                partial_tokens,
+                token,
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GameSession, SessionPhase};
+    use crate::discrete_log_cards;
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{
+        CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification,
+        ProtocolSetup,
+    };
+
+    use ark_ff::{One, UniformRand};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    fn wrong_phase(expected: &str, found: &str) -> Option<CardProtocolError> {
+        Some(CardProtocolError::WrongProtocolPhase {
+            expected: String::from(expected),
+            found: String::from(found),
+            context: ErrorContext::default(),
+        })
+    }
+
+    #[test]
+    fn enforce_step_order() {
+        let rng = &mut thread_rng();
+        let m = 2;
+        let n = 4;
+        let num_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let mut serialized = Vec::new();
+        parameters.serialize(&mut serialized).unwrap();
+        let mut session = GameSession::new(CardParameters::deserialize(&serialized[..]).unwrap());
+
+        let players = (0..num_players)
+            .map(|i| {
+                let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+                (pk, sk, vec![i as u8])
+            })
+            .collect::<Vec<_>>();
+        let digest =
+            CardProtocol::participants_digest(players.iter().map(|(pk, _, info)| (pk, info)))
+                .unwrap();
+
+        // Nothing can be revealed or masked before the keys are known
+        let masked_card = MaskedCard::rand(rng);
+        let (token, proof) = CardProtocol::compute_reveal_token(
+            rng,
+            &parameters,
+            &players[0].1,
+            &players[0].0,
+            &masked_card,
+        )
+        .unwrap();
+        assert_eq!(
+            session.submit_reveal(0, 0, token, proof).err(),
+            wrong_phase("dealing, play or showdown", "registration")
+        );
+        assert_eq!(
+            session.submit_initial_deck(&[]).err(),
+            wrong_phase("key aggregation", "registration")
+        );
+
+        for (i, (pk, sk, info)) in players.iter().enumerate() {
+            let proof =
+                CardProtocol::prove_key_ownership_in_game(rng, &parameters, pk, sk, info, &digest)
+                    .unwrap();
+            assert_eq!(session.register_player(*pk, proof, info.clone()), Ok(i));
+        }
+        let shared_key = *session.aggregate_keys().unwrap();
+        assert_eq!(session.phase(), SessionPhase::KeyAggregation);
+        assert_eq!(
+            session.aggregate_keys().err(),
+            wrong_phase("registration", "key aggregation")
+        );
+
+        let cards: Vec<Card> = sample_vector(rng, m * n);
+        let initial_deck = cards
+            .iter()
+            .map(|card| {
+                let (masked, proof) = CardProtocol::mask(
+                    rng,
+                    &parameters,
+                    &shared_key,
+                    card,
+                    &Witness::new(Scalar::one()),
+                )
+                .unwrap();
+                (*card, masked, proof)
+            })
+            .collect::<Vec<_>>();
+        session.submit_initial_deck(&initial_deck).unwrap();
+
+        // Cards cannot be dealt from a deck that has not been shuffled by everyone
+        assert_eq!(
+            session.deal(0, 0).err(),
+            wrong_phase("dealing", "shuffling")
+        );
+
+        for player in 0..num_players {
+            assert_eq!(session.phase(), SessionPhase::Shuffling { round: player });
+
+            let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
+            let (shuffled_deck, proof) = CardProtocol::shuffle_and_remask(
+                rng,
+                &parameters,
+                &shared_key,
+                session.deck(),
+                &masking_factors,
+                &Permutation::new(rng, m * n),
+            )
+            .unwrap();
+
+            // Only the player whose turn it is may shuffle
+            let other = (player + 1) % num_players;
+            assert_eq!(
+                session
+                    .submit_shuffle(other, shuffled_deck.clone(), &proof)
+                    .err(),
+                Some(CardProtocolError::OutOfTurn {
+                    expected: player,
+                    context: ErrorContext::player(other),
+                })
+            );
+            session
+                .submit_shuffle(player, shuffled_deck, &proof)
+                .unwrap();
+        }
+        assert_eq!(session.phase(), SessionPhase::Dealing);
+
+        session.deal(0, 0).unwrap();
+        assert_eq!(session.owner(0), Some(0));
+        assert!(session.deal(0, 1).is_err());
+
+        for (player, (pk, sk, _)) in players.iter().enumerate().skip(1) {
+            let (token, proof) =
+                CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &session.deck()[0])
+                    .unwrap();
+            session.submit_reveal(0, player, token, proof).unwrap();
+        }
+        assert_eq!(
+            session.open_card(0).err(),
+            wrong_phase("play or showdown", "dealing")
+        );
+        assert_eq!(
+            session.start_showdown().err(),
+            wrong_phase("play", "dealing")
+        );
+
+        session.start_play().unwrap();
+
+        // The owner of the card has not revealed it yet
+        assert_eq!(
+            session.open_card(0).err(),
+            Some(CardProtocolError::ParameterMismatch {
+                reason: String::from("missing reveal token"),
+                context: ErrorContext {
+                    player: Some(0),
+                    card: Some(0),
+                },
+            })
+        );
+
+        session.start_showdown().unwrap();
+        let (pk, sk, _) = &players[0];
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &session.deck()[0])
+                .unwrap();
+        session.submit_reveal(0, 0, token, proof).unwrap();
+
+        let card = session.open_card(0).unwrap();
+        assert!(cards.contains(&card));
+    }
+}
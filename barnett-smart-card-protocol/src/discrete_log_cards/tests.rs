@@ -1,16 +1,25 @@
 #[cfg(test)]
 mod test {
+    use crate::derivation::MasterSeed;
     use crate::discrete_log_cards;
-    use crate::error::CardProtocolError;
-    use crate::BarnettSmartProtocol;
-
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{
+        CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification,
+        ProtocolSetup, RevealVerification, ShuffleVerification,
+    };
+
+    use ark_ec::{AffineCurve, ProjectiveCurve};
     use ark_ff::UniformRand;
     use ark_std::{rand::Rng, Zero};
     use proof_essentials::error::CryptoError;
     use proof_essentials::utils::permutation::Permutation;
     use proof_essentials::utils::rand::sample_vector;
+    use proof_essentials::zkp::proofs::schnorr_identification;
     use rand::thread_rng;
+    use std::collections::VecDeque;
     use std::iter::Iterator;
+    use zeroize::Zeroize;
 
     // Choose elliptic curve setting
     type Curve = starknet_curve::Projective;
@@ -26,6 +35,8 @@ mod test {
     type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
     type RevealToken = discrete_log_cards::RevealToken<Curve>;
 
+    type KeyOwnershipProof = schnorr_identification::proof::Proof<Curve>;
+
     /// Setup `n` players. We use a Scalar to represent player public information
     fn setup_players<R: Rng>(
         rng: &mut R,
@@ -64,51 +75,182 @@ mod test {
             CardProtocol::verify_key_ownership(&parameters, &pk, &player_name, &p1_keyproof)
         );
 
-        let other_key = Scalar::rand(rng);
+        let other_key = SecretKey::new(Scalar::rand(rng));
         let wrong_proof =
             CardProtocol::prove_key_ownership(rng, &parameters, &pk, &other_key, &player_name)
                 .unwrap();
 
         assert_eq!(
             CardProtocol::verify_key_ownership(&parameters, &pk, &player_name, &wrong_proof),
-            Err(CryptoError::ProofVerificationError(String::from(
-                "Schnorr Identification"
-            )))
+            Err(CardProtocolError::KeyOwnershipVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Schnorr Identification")),
+                context: ErrorContext::default(),
+            })
         )
     }
 
     #[test]
-    fn aggregate_keys() {
+    fn secret_key_hygiene() {
         let rng = &mut thread_rng();
         let m = 4;
         let n = 13;
 
-        let num_of_players = 10;
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let (_, mut sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let printed = format!("{:?}", sk);
+        assert_eq!(printed, "PlayerSecretKey(<redacted>)");
+        assert!(!printed.contains(&sk.expose_secret().to_string()));
+
+        sk.zeroize();
+        assert!(sk.expose_secret().is_zero());
+
+        // Masking factors are handled the same way
+        let mut masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
+        assert_eq!(format!("{:?}", masking_factors), "Witness(<redacted>)");
+
+        masking_factors.zeroize();
+        assert!(masking_factors.expose_secret().iter().all(|x| x.is_zero()));
+    }
+
+    #[test]
+    fn derive_game_keys() {
+        let rng = &mut thread_rng();
+        let m = 4;
+        let n = 13;
 
         let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let seed = MasterSeed::generate(rng);
 
-        let (players, expected_shared_key) = setup_players(rng, &parameters, num_of_players);
+        let (pk, sk) = CardProtocol::derive_player_keys(&parameters, &seed, b"table 1").unwrap();
+
+        // Derivation is deterministic
+        let (same_pk, same_sk) =
+            CardProtocol::derive_player_keys(&parameters, &seed, b"table 1").unwrap();
+        assert_eq!(same_pk, pk);
+        assert_eq!(same_sk.expose_secret(), sk.expose_secret());
+
+        // ... and yields distinct keys across games and across seeds
+        let (other_game_pk, _) =
+            CardProtocol::derive_player_keys(&parameters, &seed, b"table 2").unwrap();
+        assert_ne!(other_game_pk, pk);
 
-        let proofs = players
+        let other_seed = MasterSeed::generate(rng);
+        let (other_seed_pk, _) =
+            CardProtocol::derive_player_keys(&parameters, &other_seed, b"table 1").unwrap();
+        assert_ne!(other_seed_pk, pk);
+
+        // Derived keys are proven like any other key
+        let player_name = b"Alice";
+        let proof =
+            CardProtocol::prove_key_ownership(rng, &parameters, &pk, &sk, &player_name).unwrap();
+        assert_eq!(
+            CardProtocol::verify_key_ownership(&parameters, &pk, &player_name, &proof),
+            Ok(())
+        );
+
+        assert_eq!(format!("{:?}", seed), "MasterSeed(<redacted>)");
+    }
+
+    #[test]
+    fn aggregate_derived_keys() {
+        let rng = &mut thread_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        // Every player derives their key for the same game from their own seed
+        let players = (0..4)
+            .map(|_| {
+                let seed = MasterSeed::generate(rng);
+                let (pk, sk) =
+                    CardProtocol::derive_player_keys(&parameters, &seed, b"table 1").unwrap();
+                (pk, sk, Scalar::rand(rng))
+            })
+            .collect::<Vec<_>>();
+        let expected_shared_key = players
             .iter()
-            .map(|player| {
-                CardProtocol::prove_key_ownership(rng, &parameters, &player.0, &player.1, &player.2)
-                    .unwrap()
+            .fold(PublicKey::zero(), |acc, (pk, _, _)| acc + *pk);
+
+        let key_proof_info = prove_keys(rng, &parameters, &players);
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info),
+            Ok(expected_shared_key)
+        );
+
+        // Like any other key, a derived key needs a proof bound to the game
+        let standalone_key_proof_info = players
+            .iter()
+            .map(|(pk, sk, info)| {
+                let proof =
+                    CardProtocol::prove_key_ownership(rng, &parameters, pk, sk, info).unwrap();
+                (*pk, proof, *info)
             })
             .collect::<Vec<_>>();
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &standalone_key_proof_info),
+            Err(CardProtocolError::KeyOwnershipVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Schnorr Identification")),
+                context: ErrorContext::player(0),
+            })
+        );
+    }
 
-        let key_proof_info = players
+    /// Compute proofs of key ownership bound to the set of all `players`
+    fn prove_keys<R: Rng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        players: &[(PublicKey, SecretKey, Scalar)],
+    ) -> Vec<(PublicKey, KeyOwnershipProof, Scalar)> {
+        let participants_digest =
+            CardProtocol::participants_digest(players.iter().map(|(pk, _, info)| (pk, info)))
+                .unwrap();
+
+        players
             .iter()
-            .zip(proofs.iter())
-            .map(|(player, &proof)| (player.0, proof.clone(), player.2))
-            .collect::<Vec<(PublicKey, _, _)>>();
+            .map(|(pk, sk, info)| {
+                let proof = CardProtocol::prove_key_ownership_in_game(
+                    rng,
+                    parameters,
+                    pk,
+                    sk,
+                    info,
+                    &participants_digest,
+                )
+                .unwrap();
+                (*pk, proof, *info)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aggregate_keys() {
+        let rng = &mut thread_rng();
+        let m = 4;
+        let n = 13;
+
+        let num_of_players = 10;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let (players, expected_shared_key) = setup_players(rng, &parameters, num_of_players);
+
+        let key_proof_info = prove_keys(rng, &parameters, &players);
 
         let test_aggregate =
             CardProtocol::compute_aggregate_key(&parameters, &key_proof_info).unwrap();
 
         assert_eq!(test_aggregate, expected_shared_key);
 
-        let mut bad_key_proof_pairs = key_proof_info;
+        // The order in which players are listed does not matter
+        let reordered_aggregate =
+            CardProtocol::compute_aggregate_key(&parameters, key_proof_info.iter().rev()).unwrap();
+
+        assert_eq!(reordered_aggregate, expected_shared_key);
+
+        let mut bad_key_proof_pairs = key_proof_info.clone();
         bad_key_proof_pairs[0].0 = PublicKey::zero();
 
         let test_fail_aggregate =
@@ -116,10 +258,154 @@ mod test {
 
         assert_eq!(
             test_fail_aggregate,
-            Err(CardProtocolError::ProofVerificationError(
-                CryptoError::ProofVerificationError(String::from("Schnorr Identification"))
-            ))
-        )
+            Err(CardProtocolError::InvalidPoint {
+                context: ErrorContext::player(0),
+            })
+        );
+
+        // Standalone proofs are not bound to the game and cannot be used to aggregate keys
+        let standalone_key_proof_info = players
+            .iter()
+            .map(|(pk, sk, info)| {
+                let proof =
+                    CardProtocol::prove_key_ownership(rng, &parameters, pk, sk, info).unwrap();
+                (*pk, proof, *info)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &standalone_key_proof_info),
+            Err(CardProtocolError::KeyOwnershipVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Schnorr Identification")),
+                context: ErrorContext::player(0),
+            })
+        );
+
+        // There is no aggregate key without players
+        assert!(matches!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info[..0]),
+            Err(CardProtocolError::ParameterMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn reject_duplicate_players() {
+        let rng = &mut thread_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let (mut players, _) = setup_players(rng, &parameters, 3);
+
+        // A player registering the same key twice under different identities
+        let duplicate_key = (
+            players[0].0,
+            SecretKey::new(*players[0].1.expose_secret()),
+            Scalar::rand(rng),
+        );
+        players.push(duplicate_key);
+        let key_proof_info = prove_keys(rng, &parameters, &players);
+
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info),
+            Err(CardProtocolError::DuplicatePlayerKey {
+                context: ErrorContext::player(3),
+            })
+        );
+
+        // Two different keys registered under the same identity
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        players[3] = (pk, sk, players[1].2);
+        let key_proof_info = prove_keys(rng, &parameters, &players);
+
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info),
+            Err(CardProtocolError::DuplicatePlayerIdentity {
+                context: ErrorContext::player(3),
+            })
+        );
+    }
+
+    #[test]
+    fn reject_rogue_key() {
+        let rng = &mut thread_rng();
+        let m = 4;
+        let n = 13;
+
+        let num_of_players = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let (mut players, others_key) = setup_players(rng, &parameters, num_of_players);
+        let honest_key_proof_info = prove_keys(rng, &parameters, &players);
+
+        // The last player picks their key after seeing all the others, so that the aggregate key is
+        // g^x for an `x` they know. They do not know the secret key of the rogue key itself.
+        let x = Scalar::rand(rng);
+        let rogue_key = (parameters.enc_parameters.generator.mul(x) - others_key.into_projective())
+            .into_affine();
+        players.push((rogue_key, SecretKey::new(x), Scalar::rand(rng)));
+
+        let key_proof_info = prove_keys(rng, &parameters, &players);
+
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info),
+            Err(CardProtocolError::KeyOwnershipVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Schnorr Identification")),
+                context: ErrorContext::player(num_of_players),
+            })
+        );
+
+        // A player who knows their secret key still needs a proof bound to this game: neither a valid
+        // standalone proof nor a valid proof for another set of participants is accepted
+        players.pop();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let info = Scalar::rand(rng);
+        let standalone_proof =
+            CardProtocol::prove_key_ownership(rng, &parameters, &pk, &sk, &info).unwrap();
+        players.push((pk, sk, info));
+
+        let mut key_proof_info = prove_keys(rng, &parameters, &players);
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info),
+            Ok(players
+                .iter()
+                .fold(PublicKey::zero(), |acc, (pk, _, _)| acc + *pk))
+        );
+
+        let unbound = Err(CardProtocolError::KeyOwnershipVerificationError {
+            source: CryptoError::ProofVerificationError(String::from("Schnorr Identification")),
+            context: ErrorContext::player(num_of_players),
+        });
+        key_proof_info[num_of_players].1 = standalone_proof;
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info),
+            unbound
+        );
+
+        let other_game_key_proof_info = prove_keys(rng, &parameters, &players[1..]);
+        key_proof_info[num_of_players].1 = other_game_key_proof_info[num_of_players - 1].1;
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info),
+            unbound
+        );
+
+        // Honest proofs cannot be replayed in a game with a different set of participants, even if
+        // the new participant is inserted anywhere in the ordering
+        players.pop();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        players.insert(0, (pk, sk, Scalar::rand(rng)));
+        let mut replayed_key_proof_info = prove_keys(rng, &parameters, &players);
+        replayed_key_proof_info[1..].clone_from_slice(&honest_key_proof_info);
+
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, &replayed_key_proof_info),
+            Err(CardProtocolError::KeyOwnershipVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Schnorr Identification")),
+                context: ErrorContext::player(1),
+            })
+        );
     }
 
     #[test]
@@ -135,7 +421,7 @@ mod test {
         let (players, expected_shared_key) = setup_players(rng, &parameters, num_of_players);
 
         let card = Card::rand(rng);
-        let alpha = Scalar::rand(rng);
+        let alpha = Witness::new(Scalar::rand(rng));
         let (masked, _) =
             CardProtocol::mask(rng, &parameters, &expected_shared_key, &card, &alpha).unwrap();
 
@@ -159,6 +445,12 @@ mod test {
 
         assert_eq!(card, unmasked);
 
+        // Tokens can be provided from any collection of borrowed tuples
+        let queued_tokens = decryption_key.iter().cloned().collect::<VecDeque<_>>();
+        let unmasked = CardProtocol::unmask(&parameters, &queued_tokens, &masked).unwrap();
+
+        assert_eq!(card, unmasked);
+
         let mut bad_decryption_key = decryption_key;
         bad_decryption_key[0].0 = RevealToken::rand(rng);
 
@@ -166,9 +458,10 @@ mod test {
 
         assert_eq!(
             failed_decryption,
-            Err(CardProtocolError::ProofVerificationError(
-                CryptoError::ProofVerificationError(String::from("Chaum-Pedersen"))
-            ))
+            Err(CardProtocolError::RevealVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Chaum-Pedersen")),
+                context: ErrorContext::player(0),
+            })
         )
     }
 
@@ -187,7 +480,7 @@ mod test {
         let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
 
         let permutation = Permutation::new(rng, m * n);
-        let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+        let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
 
         let (shuffled_deck, shuffle_proof) = CardProtocol::shuffle_and_remask(
             rng,
@@ -220,9 +513,27 @@ mod test {
                 &wrong_output,
                 &shuffle_proof
             ),
-            Err(CryptoError::ProofVerificationError(String::from(
-                "Hadamard Product (5.1)"
-            )))
+            Err(CardProtocolError::ShuffleVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Hadamard Product (5.1)")),
+                context: ErrorContext::default(),
+            })
+        );
+
+        let short_deck = &shuffled_deck[1..];
+
+        assert_eq!(
+            CardProtocol::verify_shuffle(
+                &parameters,
+                &aggregate_key,
+                &deck,
+                short_deck,
+                &shuffle_proof
+            ),
+            Err(CardProtocolError::DeckSizeMismatch {
+                expected: m * n,
+                found: m * n - 1,
+                context: ErrorContext::default(),
+            })
         )
     }
 }
@@ -0,0 +1,806 @@
+use crate::discrete_log_cards::{
+    Card, DLCards, GameRecord, KeyOwnershipProof, MaskedCard, MaskingProof, Parameters, PublicKey,
+    RevealProof, RevealRecord, RevealToken, ShuffleProof,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::{
+    KeyOwnershipVerification, MaskingVerification, RevealVerification, ShuffleVerification,
+};
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::io::{BufReader, BufWriter, Read, Write};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+/// Version of the serialized transcript format
+pub const TRANSCRIPT_VERSION: u8 = 1;
+
+/// A single public message of a game.
+pub enum TranscriptStep<C: ProjectiveCurve> {
+    /// A player's key and their proof of ownership, bound to the set of all participants
+    KeyOwnership {
+        pk: PublicKey<C>,
+        proof: KeyOwnershipProof<C>,
+        player_public_info: Vec<u8>,
+    },
+    /// The open cards, their initial masking and the corresponding proofs
+    InitialDeck {
+        deck: Vec<(Card<C>, MaskedCard<C>, MaskingProof<C>)>,
+    },
+    /// A shuffle of the current deck
+    Shuffle {
+        deck: Vec<MaskedCard<C>>,
+        proof: ShuffleProof<C>,
+    },
+    /// The card at `card_index` of the final deck is dealt to the player at index `player`
+    Deal { card_index: usize, player: usize },
+    /// A reveal token for the card at `card_index` of the final deck
+    Reveal {
+        card_index: usize,
+        token: RevealToken<C>,
+        proof: RevealProof<C>,
+        pk: PublicKey<C>,
+    },
+}
+
+impl<C: ProjectiveCurve> TranscriptStep<C> {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::KeyOwnership { .. } => 0,
+            Self::InitialDeck { .. } => 1,
+            Self::Shuffle { .. } => 2,
+            Self::Deal { .. } => 3,
+            Self::Reveal { .. } => 4,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::KeyOwnership { .. } => "key ownership",
+            Self::InitialDeck { .. } => "initial deck",
+            Self::Shuffle { .. } => "shuffle",
+            Self::Deal { .. } => "deal",
+            Self::Reveal { .. } => "reveal",
+        }
+    }
+}
+
+/// Sequence of all the public messages of a game, in the order in which they were produced. A transcript
+/// is enough for anyone holding the public parameters to replay and verify the whole game offline.
+pub struct Transcript<C: ProjectiveCurve> {
+    m: usize,
+    n: usize,
+    steps: Vec<TranscriptStep<C>>,
+}
+
+impl<C: ProjectiveCurve> Transcript<C> {
+    /// Start an empty transcript for a game played with parameters `pp`
+    pub fn new(pp: &Parameters<C>) -> Self {
+        Self {
+            m: pp.m,
+            n: pp.n,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn append(&mut self, step: TranscriptStep<C>) {
+        self.steps.push(step);
+    }
+
+    pub fn steps(&self) -> &[TranscriptStep<C>] {
+        &self.steps
+    }
+
+    /// Write the transcript to the file at `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CardProtocolError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.serialize(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Read a transcript from the file at `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CardProtocolError> {
+        let reader = BufReader::new(File::open(path)?);
+
+        Ok(Self::deserialize(reader)?)
+    }
+}
+
+/// State of a game after replaying its transcript.
+pub struct ReplayedGame<C: ProjectiveCurve> {
+    pub shared_key: PublicKey<C>,
+    pub deck: Vec<MaskedCard<C>>,
+    /// (card index, player index) for every dealt card
+    pub deals: Vec<(usize, usize)>,
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    Keys,
+    /// Number of shuffles so far
+    Shuffling(usize),
+    Dealing,
+}
+
+impl Phase {
+    /// Steps that may come next
+    fn expected(&self) -> &'static str {
+        match self {
+            Self::Keys => "initial deck",
+            Self::Shuffling(_) => "shuffle",
+            Self::Dealing => "deal or reveal",
+        }
+    }
+}
+
+/// Replay a transcript and verify every step in order. The game must go through the following phases:
+/// key ownership proofs, a single initial deck, one shuffle per player, then deals and reveals. The first
+/// step that does not verify, or does not belong to the current phase, is reported as an
+/// `InvalidTranscriptStep` holding its index. A transcript that stops before the deck has been shuffled
+/// by every player is reported as a `WrongProtocolPhase` at the index following its last step.
+pub fn verify_transcript<C: ProjectiveCurve>(
+    pp: &Parameters<C>,
+    transcript: &Transcript<C>,
+) -> Result<ReplayedGame<C>, CardProtocolError> {
+    if (transcript.m, transcript.n) != (pp.m, pp.n) {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: format!(
+                "transcript recorded with m = {}, n = {}",
+                transcript.m, transcript.n
+            ),
+            context: ErrorContext::default(),
+        });
+    }
+
+    let invalid_step = |step: usize| {
+        move |source: CardProtocolError| CardProtocolError::InvalidTranscriptStep {
+            step,
+            source: Box::new(source),
+        }
+    };
+    let wrong_phase = |step: usize, phase: Phase, found: &TranscriptStep<C>| {
+        invalid_step(step)(CardProtocolError::WrongProtocolPhase {
+            expected: String::from(phase.expected()),
+            found: String::from(found.name()),
+            context: ErrorContext::default(),
+        })
+    };
+
+    let steps = transcript.steps();
+
+    // Key ownership proofs come first and are verified together, as each proof is bound to all players
+    let num_players = steps
+        .iter()
+        .take_while(|step| matches!(step, TranscriptStep::KeyOwnership { .. }))
+        .count();
+    let players = steps[..num_players]
+        .iter()
+        .filter_map(|step| match step {
+            TranscriptStep::KeyOwnership {
+                pk,
+                proof,
+                player_public_info,
+            } => Some((*pk, *proof, player_public_info.clone())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let shared_key = DLCards::<C>::compute_aggregate_key(pp, &players).map_err(|e| {
+        // Players are numbered in the order of their key ownership steps, which open the transcript.
+        // A failure that cannot be attributed to a single player (e.g. no player at all) is reported
+        // at the end of the key ownership steps.
+        let step = e
+            .context()
+            .and_then(|context| context.player)
+            .unwrap_or(num_players);
+        invalid_step(step)(e)
+    })?;
+
+    let mut phase = Phase::Keys;
+    let mut deck = Vec::new();
+    let mut deals = Vec::new();
+
+    for (i, step) in steps.iter().enumerate().skip(num_players) {
+        match (step, phase) {
+            (TranscriptStep::InitialDeck { deck: initial_deck }, Phase::Keys) => {
+                pp.check_deck_size(initial_deck.len())
+                    .map_err(invalid_step(i))?;
+                for (j, (card, masked, proof)) in initial_deck.iter().enumerate() {
+                    DLCards::<C>::verify_mask(pp, &shared_key, card, masked, proof)
+                        .map_err(|e| invalid_step(i)(e.with_card(j)))?;
+                }
+                deck = initial_deck.iter().map(|(_, masked, _)| *masked).collect();
+                phase = Phase::Shuffling(0);
+            }
+            (
+                TranscriptStep::Shuffle {
+                    deck: shuffled,
+                    proof,
+                },
+                Phase::Shuffling(shuffles),
+            ) => {
+                DLCards::<C>::verify_shuffle(pp, &shared_key, &deck, shuffled, proof)
+                    .map_err(|e| invalid_step(i)(e.with_player(shuffles)))?;
+                deck = shuffled.clone();
+                phase = if shuffles + 1 == num_players {
+                    Phase::Dealing
+                } else {
+                    Phase::Shuffling(shuffles + 1)
+                };
+            }
+            (TranscriptStep::Deal { card_index, player }, Phase::Dealing) => {
+                check_card_index(*card_index, deck.len()).map_err(invalid_step(i))?;
+                if *player >= num_players {
+                    return Err(invalid_step(i)(CardProtocolError::ParameterMismatch {
+                        reason: format!("card dealt to unknown player {}", player),
+                        context: ErrorContext::card(*card_index),
+                    }));
+                }
+                if deals.iter().any(|(dealt, _)| dealt == card_index) {
+                    return Err(invalid_step(i)(CardProtocolError::ParameterMismatch {
+                        reason: String::from("card dealt twice"),
+                        context: ErrorContext::card(*card_index),
+                    }));
+                }
+                deals.push((*card_index, *player));
+            }
+            (
+                TranscriptStep::Reveal {
+                    card_index,
+                    token,
+                    proof,
+                    pk,
+                },
+                Phase::Dealing,
+            ) => {
+                check_card_index(*card_index, deck.len()).map_err(invalid_step(i))?;
+                let player = players
+                    .iter()
+                    .position(|(player_pk, _, _)| player_pk == pk)
+                    .ok_or_else(|| {
+                        invalid_step(i)(CardProtocolError::ParameterMismatch {
+                            reason: String::from("reveal token issued by an unknown player"),
+                            context: ErrorContext::card(*card_index),
+                        })
+                    })?;
+                DLCards::<C>::verify_reveal(pp, pk, token, &deck[*card_index], proof)
+                    .map_err(|e| invalid_step(i)(e.with_player(player).with_card(*card_index)))?;
+            }
+            _ => return Err(wrong_phase(i, phase, step)),
+        }
+    }
+
+    if !matches!(phase, Phase::Dealing) {
+        return Err(invalid_step(steps.len())(
+            CardProtocolError::WrongProtocolPhase {
+                expected: String::from(phase.expected()),
+                found: String::from("end of transcript"),
+                context: ErrorContext::default(),
+            },
+        ));
+    }
+
+    Ok(ReplayedGame {
+        shared_key,
+        deck,
+        deals,
+    })
+}
+
+fn check_card_index(card_index: usize, deck_size: usize) -> Result<(), CardProtocolError> {
+    if card_index >= deck_size {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: format!("card index out of a deck of {} cards", deck_size),
+            context: ErrorContext::card(card_index),
+        });
+    }
+
+    Ok(())
+}
+
+impl<C: ProjectiveCurve> From<Transcript<C>> for GameRecord<C> {
+    /// Extract the parts of a transcript needed to audit the game with `audit_game`
+    fn from(transcript: Transcript<C>) -> Self {
+        let mut players = Vec::new();
+        let mut masked_deck = Vec::new();
+        let mut shuffles = Vec::new();
+        let mut reveals = BTreeMap::<usize, Vec<_>>::new();
+
+        for step in transcript.steps {
+            match step {
+                TranscriptStep::KeyOwnership { pk, .. } => players.push(pk),
+                TranscriptStep::InitialDeck { deck } => masked_deck = deck,
+                TranscriptStep::Shuffle { deck, proof } => shuffles.push((deck, proof)),
+                TranscriptStep::Deal { .. } => {}
+                TranscriptStep::Reveal {
+                    card_index,
+                    token,
+                    proof,
+                    pk,
+                } => reveals
+                    .entry(card_index)
+                    .or_default()
+                    .push((token, proof, pk)),
+            }
+        }
+
+        Self {
+            players,
+            masked_deck,
+            shuffles,
+            reveals: reveals
+                .into_iter()
+                .map(|(card_index, tokens)| RevealRecord { card_index, tokens })
+                .collect(),
+        }
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalSerialize for TranscriptStep<C> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        self.tag().serialize(&mut writer)?;
+        match self {
+            Self::KeyOwnership {
+                pk,
+                proof,
+                player_public_info,
+            } => {
+                pk.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+                player_public_info.serialize(&mut writer)
+            }
+            Self::InitialDeck { deck } => {
+                (deck.len() as u64).serialize(&mut writer)?;
+                for (card, masked, proof) in deck {
+                    card.serialize(&mut writer)?;
+                    masked.serialize(&mut writer)?;
+                    proof.serialize(&mut writer)?;
+                }
+                Ok(())
+            }
+            Self::Shuffle { deck, proof } => {
+                deck.serialize(&mut writer)?;
+                proof.serialize(&mut writer)
+            }
+            Self::Deal { card_index, player } => {
+                (*card_index as u64).serialize(&mut writer)?;
+                (*player as u64).serialize(&mut writer)
+            }
+            Self::Reveal {
+                card_index,
+                token,
+                proof,
+                pk,
+            } => {
+                (*card_index as u64).serialize(&mut writer)?;
+                token.serialize(&mut writer)?;
+                proof.serialize(&mut writer)?;
+                pk.serialize(&mut writer)
+            }
+        }
+    }
+
+    fn serialized_size(&self) -> usize {
+        let body = match self {
+            Self::KeyOwnership {
+                pk,
+                proof,
+                player_public_info,
+            } => {
+                pk.serialized_size()
+                    + proof.serialized_size()
+                    + player_public_info.serialized_size()
+            }
+            Self::InitialDeck { deck } => {
+                8 + deck
+                    .iter()
+                    .map(|(card, masked, proof)| {
+                        card.serialized_size() + masked.serialized_size() + proof.serialized_size()
+                    })
+                    .sum::<usize>()
+            }
+            Self::Shuffle { deck, proof } => deck.serialized_size() + proof.serialized_size(),
+            Self::Deal { .. } => 16,
+            Self::Reveal {
+                token, proof, pk, ..
+            } => 8 + token.serialized_size() + proof.serialized_size() + pk.serialized_size(),
+        };
+
+        1 + body
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalDeserialize for TranscriptStep<C> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let step = match u8::deserialize(&mut reader)? {
+            0 => Self::KeyOwnership {
+                pk: CanonicalDeserialize::deserialize(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+                player_public_info: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            1 => {
+                let len = u64::deserialize(&mut reader)? as usize;
+                let deck = (0..len)
+                    .map(|_| {
+                        Ok((
+                            CanonicalDeserialize::deserialize(&mut reader)?,
+                            CanonicalDeserialize::deserialize(&mut reader)?,
+                            CanonicalDeserialize::deserialize(&mut reader)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, SerializationError>>()?;
+                Self::InitialDeck { deck }
+            }
+            2 => Self::Shuffle {
+                deck: CanonicalDeserialize::deserialize(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            3 => Self::Deal {
+                card_index: u64::deserialize(&mut reader)? as usize,
+                player: u64::deserialize(&mut reader)? as usize,
+            },
+            4 => Self::Reveal {
+                card_index: u64::deserialize(&mut reader)? as usize,
+                token: CanonicalDeserialize::deserialize(&mut reader)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+                pk: CanonicalDeserialize::deserialize(&mut reader)?,
+            },
+            _ => return Err(SerializationError::InvalidData),
+        };
+
+        Ok(step)
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalSerialize for Transcript<C> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        TRANSCRIPT_VERSION.serialize(&mut writer)?;
+        (self.m as u64).serialize(&mut writer)?;
+        (self.n as u64).serialize(&mut writer)?;
+        self.steps.serialize(&mut writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        1 + 8 + 8 + self.steps.serialized_size()
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalDeserialize for Transcript<C> {
+    /// Fails with `InvalidData` on transcripts written with an unsupported format version
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        if u8::deserialize(&mut reader)? != TRANSCRIPT_VERSION {
+            return Err(SerializationError::InvalidData);
+        }
+
+        Ok(Self {
+            m: u64::deserialize(&mut reader)? as usize,
+            n: u64::deserialize(&mut reader)? as usize,
+            steps: CanonicalDeserialize::deserialize(&mut reader)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{verify_transcript, Transcript, TranscriptStep, TRANSCRIPT_VERSION};
+    use crate::discrete_log_cards::{self, audit_game, GameRecord};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{
+        CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification,
+        ProtocolSetup,
+    };
+
+    use ark_ff::UniformRand;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_std::Zero;
+    use proof_essentials::utils::permutation::Permutation;
+    use rand::{thread_rng, Rng};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+    type SecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
+
+    type Card = discrete_log_cards::Card<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    /// Record a hand with three players in which the first card of the deck is dealt to player 0
+    fn record_game() -> (CardParameters, Transcript<Curve>, Vec<SecretKey>) {
+        let rng = &mut thread_rng();
+        let m = 2;
+        let n = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let mut transcript = Transcript::new(&parameters);
+
+        let keys = (0..3)
+            .map(|i| {
+                let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+                (pk, sk, vec![i as u8])
+            })
+            .collect::<Vec<_>>();
+        let participants_digest =
+            CardProtocol::participants_digest(keys.iter().map(|(pk, _, info)| (pk, info))).unwrap();
+
+        let mut shared_key = PublicKey::zero();
+        let mut secret_keys = Vec::new();
+        let mut players = Vec::new();
+        for (pk, sk, info) in keys {
+            let proof = CardProtocol::prove_key_ownership_in_game(
+                rng,
+                &parameters,
+                &pk,
+                &sk,
+                &info,
+                &participants_digest,
+            )
+            .unwrap();
+            transcript.append(TranscriptStep::KeyOwnership {
+                pk,
+                proof,
+                player_public_info: info,
+            });
+            shared_key = shared_key + pk;
+            players.push(pk);
+            secret_keys.push(sk);
+        }
+
+        let deck = (0..m * n)
+            .map(|_| {
+                let card = Card::rand(rng);
+                let alpha = Witness::new(Scalar::rand(rng));
+                let (masked, proof) =
+                    CardProtocol::mask(rng, &parameters, &shared_key, &card, &alpha).unwrap();
+                (card, masked, proof)
+            })
+            .collect::<Vec<_>>();
+        let mut current_deck = deck.iter().map(|x| x.1).collect::<Vec<_>>();
+        transcript.append(TranscriptStep::InitialDeck { deck });
+
+        for _ in 0..players.len() {
+            let permutation = Permutation::new(rng, m * n);
+            let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
+            let (shuffled, proof) = CardProtocol::shuffle_and_remask(
+                rng,
+                &parameters,
+                &shared_key,
+                &current_deck,
+                &masking_factors,
+                &permutation,
+            )
+            .unwrap();
+            current_deck = shuffled.clone();
+            transcript.append(TranscriptStep::Shuffle {
+                deck: shuffled,
+                proof,
+            });
+        }
+
+        transcript.append(TranscriptStep::Deal {
+            card_index: 0,
+            player: 0,
+        });
+        for (pk, sk) in players.iter().zip(secret_keys.iter()).skip(1) {
+            let (token, proof) =
+                CardProtocol::compute_reveal_token(rng, &parameters, sk, pk, &current_deck[0])
+                    .unwrap();
+            transcript.append(TranscriptStep::Reveal {
+                card_index: 0,
+                token,
+                proof,
+                pk: *pk,
+            });
+        }
+
+        (parameters, transcript, secret_keys)
+    }
+
+    #[test]
+    fn replay_recorded_game() {
+        let (parameters, transcript, secret_keys) = record_game();
+
+        let replayed = verify_transcript(&parameters, &transcript).unwrap();
+        assert_eq!(replayed.deals, vec![(0, 0)]);
+        assert_eq!(replayed.deck.len(), 8);
+
+        // A verified transcript can be audited once the keys are disclosed
+        let record = GameRecord::from(transcript);
+        assert!(audit_game(&parameters, &record, &secret_keys)
+            .unwrap()
+            .is_clean());
+    }
+
+    fn serialize(transcript: &Transcript<Curve>) -> Vec<u8> {
+        let mut serialized = Vec::new();
+        transcript.serialize(&mut serialized).unwrap();
+        serialized
+    }
+
+    #[test]
+    fn save_and_load() {
+        let (parameters, transcript, _) = record_game();
+
+        let serialized = serialize(&transcript);
+        assert_eq!(serialized.len(), transcript.serialized_size());
+        assert_eq!(serialized[0], TRANSCRIPT_VERSION);
+        let deserialized = Transcript::<Curve>::deserialize(&serialized[..]).unwrap();
+        assert_eq!(serialize(&deserialized), serialized);
+
+        let mut serialized = serialize(&transcript);
+        serialized[0] = TRANSCRIPT_VERSION + 1;
+        assert!(Transcript::<Curve>::deserialize(&serialized[..]).is_err());
+
+        let path =
+            std::env::temp_dir().join(format!("transcript-{}.bin", thread_rng().gen::<u64>()));
+        transcript.save(&path).unwrap();
+        let loaded = Transcript::<Curve>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(serialize(&loaded), serialize(&transcript));
+        assert!(verify_transcript(&parameters, &loaded).is_ok());
+    }
+
+    #[test]
+    fn report_first_invalid_step() {
+        let rng = &mut thread_rng();
+
+        // Steps: 0-2 key ownership, 3 initial deck, 4-6 shuffles, 7 deal, 8-9 reveals
+        let (parameters, mut transcript, _) = record_game();
+        if let TranscriptStep::Shuffle { deck, .. } = &mut transcript.steps[5] {
+            deck[0] = MaskedCard::rand(rng);
+        }
+        if let TranscriptStep::Reveal { token, .. } = &mut transcript.steps[9] {
+            *token = RevealToken::rand(rng);
+        }
+        match verify_transcript(&parameters, &transcript) {
+            Err(CardProtocolError::InvalidTranscriptStep { step, source }) => {
+                assert_eq!(step, 5);
+                assert!(matches!(
+                    *source,
+                    CardProtocolError::ShuffleVerificationError { .. }
+                ));
+            }
+            _ => panic!("expected an invalid shuffle"),
+        }
+
+        let (parameters, mut transcript, _) = record_game();
+        if let TranscriptStep::Reveal { token, .. } = &mut transcript.steps[9] {
+            *token = RevealToken::rand(rng);
+        }
+        match verify_transcript(&parameters, &transcript) {
+            Err(CardProtocolError::InvalidTranscriptStep { step, source }) => {
+                assert_eq!(step, 9);
+                assert_eq!(
+                    source.context(),
+                    Some(&ErrorContext {
+                        player: Some(2),
+                        card: Some(0)
+                    })
+                );
+            }
+            _ => panic!("expected an invalid reveal"),
+        }
+
+        // Dealing before every player has shuffled is out of order
+        let (parameters, mut transcript, _) = record_game();
+        transcript.steps.swap(6, 7);
+        match verify_transcript(&parameters, &transcript) {
+            Err(CardProtocolError::InvalidTranscriptStep { step, source }) => {
+                assert_eq!(step, 6);
+                assert!(matches!(
+                    *source,
+                    CardProtocolError::WrongProtocolPhase { .. }
+                ));
+            }
+            _ => panic!("expected a step out of order"),
+        }
+
+        // A key ownership proof that does not verify is reported at the step of its player
+        let (parameters, mut transcript, _) = record_game();
+        let other_proof = match &transcript.steps[2] {
+            TranscriptStep::KeyOwnership { proof, .. } => *proof,
+            _ => unreachable!(),
+        };
+        if let TranscriptStep::KeyOwnership { proof, .. } = &mut transcript.steps[1] {
+            *proof = other_proof;
+        }
+        match verify_transcript(&parameters, &transcript) {
+            Err(CardProtocolError::InvalidTranscriptStep { step, source }) => {
+                assert_eq!(step, 1);
+                assert!(matches!(
+                    *source,
+                    CardProtocolError::KeyOwnershipVerificationError { .. }
+                ));
+            }
+            _ => panic!("expected an invalid key ownership proof"),
+        }
+
+        // A game needs at least one player
+        let (parameters, mut transcript, _) = record_game();
+        transcript.steps.drain(..3);
+        match verify_transcript(&parameters, &transcript) {
+            Err(CardProtocolError::InvalidTranscriptStep { step, source }) => {
+                assert_eq!(step, 0);
+                assert!(matches!(
+                    *source,
+                    CardProtocolError::ParameterMismatch { .. }
+                ));
+            }
+            _ => panic!("expected a transcript without players to be rejected"),
+        }
+    }
+
+    #[test]
+    fn reject_incomplete_transcript() {
+        let rng = &mut thread_rng();
+
+        // Steps: 0-2 key ownership, 3 initial deck, 4-6 shuffles, 7 deal, 8-9 reveals
+        for (len, expected) in [(3, "initial deck"), (4, "shuffle"), (6, "shuffle")] {
+            let (parameters, mut transcript, _) = record_game();
+            transcript.steps.truncate(len);
+            match verify_transcript(&parameters, &transcript) {
+                Err(CardProtocolError::InvalidTranscriptStep { step, source }) => {
+                    assert_eq!(step, len);
+                    assert!(matches!(
+                        *source,
+                        CardProtocolError::WrongProtocolPhase { expected: e, .. } if e == expected
+                    ));
+                }
+                _ => panic!("expected a transcript of {} steps to be incomplete", len),
+            }
+        }
+
+        // Once every player has shuffled, the transcript may stop at any point
+        let (parameters, mut transcript, _) = record_game();
+        transcript.steps.truncate(7);
+        assert!(verify_transcript(&parameters, &transcript).is_ok());
+
+        // A shuffle more than the number of players is out of order
+        let (parameters, mut transcript, _) = record_game();
+        let deck = match &transcript.steps[6] {
+            TranscriptStep::Shuffle { deck, .. } => deck.clone(),
+            _ => unreachable!(),
+        };
+        let permutation = Permutation::new(rng, deck.len());
+        let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, deck.len());
+        let shared_key = verify_transcript(&parameters, &transcript)
+            .unwrap()
+            .shared_key;
+        let (shuffled, proof) = CardProtocol::shuffle_and_remask(
+            rng,
+            &parameters,
+            &shared_key,
+            &deck,
+            &masking_factors,
+            &permutation,
+        )
+        .unwrap();
+        transcript.steps.insert(
+            7,
+            TranscriptStep::Shuffle {
+                deck: shuffled,
+                proof,
+            },
+        );
+        match verify_transcript(&parameters, &transcript) {
+            Err(CardProtocolError::InvalidTranscriptStep { step, source }) => {
+                assert_eq!(step, 7);
+                assert!(matches!(
+                    *source,
+                    CardProtocolError::WrongProtocolPhase { .. }
+                ));
+            }
+            _ => panic!("expected an extra shuffle to be rejected"),
+        }
+    }
+}
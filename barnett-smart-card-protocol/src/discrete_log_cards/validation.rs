@@ -0,0 +1,231 @@
+use crate::discrete_log_cards::DLCards;
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::{invalid_data_to_point, PointValidation};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{FpParameters, PrimeField};
+use ark_serialize::CanonicalDeserialize;
+use ark_std::io::Read;
+use ark_std::Zero;
+
+/// Check that a point is not the identity.
+fn check_not_identity<G: AffineCurve>(point: &G) -> Result<(), CardProtocolError> {
+    if point.is_zero() {
+        return Err(CardProtocolError::InvalidPoint {
+            context: ErrorContext::default(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check that a point is not the identity and that it belongs to the prime-order subgroup.
+/// The subgroup check is a full scalar multiplication, so it is only done for curves with a cofactor
+/// (e.g. BLS12-377 G1 or Edwards curves): on a prime-order curve every point on the curve is in the group.
+pub(crate) fn check_point<G: AffineCurve>(point: &G) -> Result<(), CardProtocolError> {
+    check_not_identity(point)?;
+
+    let prime_order = G::COFACTOR.first() == Some(&1) && G::COFACTOR[1..].iter().all(|l| *l == 0);
+    if !prime_order {
+        let order = <<G::ScalarField as PrimeField>::Params as FpParameters>::MODULUS;
+        if !point.mul(order).is_zero() {
+            return Err(CardProtocolError::InvalidPoint {
+                context: ErrorContext::default(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a, C: ProjectiveCurve> PointValidation for DLCards<'a, C> {
+    fn validate_public_key(pk: &Self::PlayerPublicKey) -> Result<(), CardProtocolError> {
+        check_point(pk)
+    }
+
+    fn validate_card(card: &Self::Card) -> Result<(), CardProtocolError> {
+        check_point(&card.0)
+    }
+
+    fn validate_masked_card(masked_card: &Self::MaskedCard) -> Result<(), CardProtocolError> {
+        check_point(&masked_card.0)?;
+        check_point(&masked_card.1)
+    }
+
+    fn validate_reveal_token(reveal_token: &Self::RevealToken) -> Result<(), CardProtocolError> {
+        check_point(&reveal_token.0)
+    }
+
+    // Checked deserialization already rejects points outside of the prime-order subgroup, so
+    // deserialized objects are only checked for the identity
+
+    fn deserialize_public_key<R: Read>(
+        reader: R,
+    ) -> Result<Self::PlayerPublicKey, CardProtocolError> {
+        let pk = Self::PlayerPublicKey::deserialize(reader).map_err(invalid_data_to_point)?;
+        check_not_identity(&pk)?;
+
+        Ok(pk)
+    }
+
+    fn deserialize_card<R: Read>(reader: R) -> Result<Self::Card, CardProtocolError> {
+        let card = Self::Card::deserialize(reader).map_err(invalid_data_to_point)?;
+        check_not_identity(&card.0)?;
+
+        Ok(card)
+    }
+
+    fn deserialize_masked_card<R: Read>(reader: R) -> Result<Self::MaskedCard, CardProtocolError> {
+        let masked_card = Self::MaskedCard::deserialize(reader).map_err(invalid_data_to_point)?;
+        check_not_identity(&masked_card.0)?;
+        check_not_identity(&masked_card.1)?;
+
+        Ok(masked_card)
+    }
+
+    fn deserialize_deck<R: Read>(reader: R) -> Result<Vec<Self::MaskedCard>, CardProtocolError> {
+        let deck = Vec::<Self::MaskedCard>::deserialize(reader).map_err(invalid_data_to_point)?;
+        for (i, masked_card) in deck.iter().enumerate() {
+            check_not_identity(&masked_card.0)
+                .and_then(|_| check_not_identity(&masked_card.1))
+                .map_err(|e| e.with_card(i))?;
+        }
+
+        Ok(deck)
+    }
+
+    fn deserialize_reveal_token<R: Read>(
+        reader: R,
+    ) -> Result<Self::RevealToken, CardProtocolError> {
+        let reveal_token = Self::RevealToken::deserialize(reader).map_err(invalid_data_to_point)?;
+        check_not_identity(&reveal_token.0)?;
+
+        Ok(reveal_token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discrete_log_cards;
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{CardMasking, KeyManagement, MaskingVerification, PointValidation, ProtocolSetup};
+
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{FpParameters, PrimeField, UniformRand};
+    use ark_serialize::CanonicalSerialize;
+    use ark_std::{rand::Rng, Zero};
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+
+    type Card = discrete_log_cards::Card<Curve>;
+
+    // A curve with a cofactor
+    type CofactorCurve = ark_bls12_377::G1Projective;
+    type CofactorAffine = ark_bls12_377::G1Affine;
+    type CofactorProtocol<'a> = discrete_log_cards::DLCards<'a, CofactorCurve>;
+
+    fn invalid_point<T>() -> Result<T, CardProtocolError> {
+        Err(CardProtocolError::InvalidPoint {
+            context: ErrorContext::default(),
+        })
+    }
+
+    #[test]
+    fn reject_identity() {
+        let rng = &mut thread_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let card = Card::rand(rng);
+        let alpha = Witness::new(Scalar::rand(rng));
+        let (masked, proof) = CardProtocol::mask(rng, &parameters, &pk, &card, &alpha).unwrap();
+
+        let identity_card = el_gamal::Plaintext(PublicKey::zero());
+
+        assert_eq!(CardProtocol::validate_card(&identity_card), invalid_point());
+        assert_eq!(
+            CardProtocol::verify_mask(&parameters, &pk, &identity_card, &masked, &proof),
+            invalid_point()
+        );
+
+        let identity_masked = el_gamal::Ciphertext(PublicKey::zero(), masked.1);
+        assert_eq!(
+            CardProtocol::verify_mask(&parameters, &pk, &card, &identity_masked, &proof),
+            invalid_point()
+        );
+
+        let mut serialized = Vec::new();
+        PublicKey::zero().serialize(&mut serialized).unwrap();
+        assert_eq!(
+            CardProtocol::deserialize_public_key(&serialized[..]),
+            invalid_point()
+        );
+
+        let mut serialized = Vec::new();
+        masked.serialize(&mut serialized).unwrap();
+        assert_eq!(
+            CardProtocol::deserialize_masked_card(&serialized[..]),
+            Ok(masked)
+        );
+
+        let deck = vec![masked, identity_masked];
+        let mut serialized = Vec::new();
+        deck.serialize(&mut serialized).unwrap();
+        assert_eq!(
+            CardProtocol::deserialize_deck(&serialized[..]),
+            Err(CardProtocolError::InvalidPoint {
+                context: ErrorContext::card(1),
+            })
+        );
+    }
+
+    #[test]
+    fn reject_points_outside_subgroup() {
+        let rng = &mut thread_rng();
+        let order = <<ark_bls12_377::Fr as PrimeField>::Params as FpParameters>::MODULUS;
+
+        // Sample points on the curve without clearing the cofactor until one lies outside the subgroup
+        let outside_subgroup = loop {
+            let bytes = (0..64).map(|_| rng.gen()).collect::<Vec<u8>>();
+            if let Some(point) = CofactorAffine::from_random_bytes(&bytes) {
+                if !point.mul(order).is_zero() {
+                    break point;
+                }
+            }
+        };
+
+        assert_eq!(
+            CofactorProtocol::validate_public_key(&outside_subgroup),
+            invalid_point()
+        );
+        assert_eq!(
+            CofactorProtocol::validate_masked_card(&el_gamal::Ciphertext(
+                CofactorCurve::rand(rng).into_affine(),
+                outside_subgroup
+            )),
+            invalid_point()
+        );
+
+        let mut serialized = Vec::new();
+        outside_subgroup.serialize(&mut serialized).unwrap();
+        assert_eq!(
+            CofactorProtocol::deserialize_public_key(&serialized[..]),
+            invalid_point()
+        );
+
+        let valid = CofactorCurve::rand(rng).into_affine();
+        assert_eq!(CofactorProtocol::validate_public_key(&valid), Ok(()));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::discrete_log_cards::{
+    Card, DLCards, KeyOwnershipProof, MaskedCard, MaskingProof, Parameters, PublicKey,
+    RemaskingProof, RevealProof, RevealToken, ShuffleProof,
+};
+use crate::error::CardProtocolError;
+use crate::{
+    KeyOwnershipVerification, MaskingVerification, RevealVerification, ShuffleVerification,
+};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::ToBytes;
+
+/// Audit entry point for parties that only check the work of the players (spectators, arbiters, ...).
+/// A `Verifier` holds the public parameters and the aggregate key of a game and exposes the verification
+/// half of the protocol only: it never handles a player secret key or an RNG.
+pub struct Verifier<C: ProjectiveCurve> {
+    pp: Parameters<C>,
+    shared_key: PublicKey<C>,
+}
+
+impl<C: ProjectiveCurve> Verifier<C> {
+    pub fn new(pp: Parameters<C>, shared_key: PublicKey<C>) -> Self {
+        Self { pp, shared_key }
+    }
+
+    /// Verify all key ownership proofs of the players and build a verifier for the resulting aggregate key.
+    pub fn from_key_proofs<'b, B: ToBytes + 'b>(
+        pp: Parameters<C>,
+        player_keys_proof_info: impl IntoIterator<Item = &'b (PublicKey<C>, KeyOwnershipProof<C>, B)>,
+    ) -> Result<Self, CardProtocolError> {
+        let shared_key = DLCards::<C>::compute_aggregate_key(&pp, player_keys_proof_info)?;
+
+        Ok(Self::new(pp, shared_key))
+    }
+
+    pub fn parameters(&self) -> &Parameters<C> {
+        &self.pp
+    }
+
+    pub fn shared_key(&self) -> &PublicKey<C> {
+        &self.shared_key
+    }
+
+    /// Verify a proof of key ownership
+    pub fn verify_key_ownership<B: ToBytes>(
+        &self,
+        pk: &PublicKey<C>,
+        player_public_info: &B,
+        proof: &KeyOwnershipProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        DLCards::<C>::verify_key_ownership(&self.pp, pk, player_public_info, proof)
+    }
+
+    /// Verify a proof of masking under the aggregate key
+    pub fn verify_mask(
+        &self,
+        card: &Card<C>,
+        masked_card: &MaskedCard<C>,
+        proof: &MaskingProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        DLCards::<C>::verify_mask(&self.pp, &self.shared_key, card, masked_card, proof)
+    }
+
+    /// Verify a proof of remasking under the aggregate key
+    pub fn verify_remask(
+        &self,
+        original_masked: &MaskedCard<C>,
+        remasked: &MaskedCard<C>,
+        proof: &RemaskingProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        DLCards::<C>::verify_remask(&self.pp, &self.shared_key, original_masked, remasked, proof)
+    }
+
+    /// Verify a proof of correctly computed reveal token issued by the owner of `pk`
+    pub fn verify_reveal(
+        &self,
+        pk: &PublicKey<C>,
+        reveal_token: &RevealToken<C>,
+        masked_card: &MaskedCard<C>,
+        proof: &RevealProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        DLCards::<C>::verify_reveal(&self.pp, pk, reveal_token, masked_card, proof)
+    }
+
+    /// Verify a proof of correct shuffle under the aggregate key
+    pub fn verify_shuffle(
+        &self,
+        original_deck: &[MaskedCard<C>],
+        shuffled_deck: &[MaskedCard<C>],
+        proof: &ShuffleProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        DLCards::<C>::verify_shuffle(
+            &self.pp,
+            &self.shared_key,
+            original_deck,
+            shuffled_deck,
+            proof,
+        )
+    }
+
+    /// Verify the key ownership proofs of all the players of a game. Each proof must be bound to the
+    /// full set of participants. Stops at the first invalid proof, which is attributed to the index
+    /// of the player in `key_proof_info`.
+    pub fn verify_key_ownership_batch<B: ToBytes>(
+        &self,
+        key_proof_info: &[(PublicKey<C>, KeyOwnershipProof<C>, B)],
+    ) -> Result<(), CardProtocolError> {
+        let participants_digest = DLCards::<C>::participants_digest(
+            key_proof_info.iter().map(|(pk, _, info)| (pk, info)),
+        )?;
+
+        key_proof_info
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, (pk, proof, info))| {
+                DLCards::<C>::verify_key_ownership_in_game(
+                    &self.pp,
+                    pk,
+                    info,
+                    &participants_digest,
+                    proof,
+                )
+                .map_err(|e| e.with_player(i))
+            })
+    }
+
+    /// Verify the masking of a whole deck, given as (card, masked card, proof) triples. A failure is
+    /// attributed to the index of the card in the deck.
+    pub fn verify_mask_batch(
+        &self,
+        masked_deck: &[(Card<C>, MaskedCard<C>, MaskingProof<C>)],
+    ) -> Result<(), CardProtocolError> {
+        masked_deck
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, (card, masked, proof))| {
+                self.verify_mask(card, masked, proof)
+                    .map_err(|e| e.with_card(i))
+            })
+    }
+
+    /// Verify several remaskings, given as (original, remasked, proof) triples. A failure is
+    /// attributed to the index of the card in `remasked_cards`.
+    pub fn verify_remask_batch(
+        &self,
+        remasked_cards: &[(MaskedCard<C>, MaskedCard<C>, RemaskingProof<C>)],
+    ) -> Result<(), CardProtocolError> {
+        remasked_cards
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, (original, remasked, proof))| {
+                self.verify_remask(original, remasked, proof)
+                    .map_err(|e| e.with_card(i))
+            })
+    }
+
+    /// Verify all reveal tokens issued for a given masked card. A failure is attributed to the index
+    /// of the player in `decryption_key`.
+    pub fn verify_reveal_batch(
+        &self,
+        masked_card: &MaskedCard<C>,
+        decryption_key: &[(RevealToken<C>, RevealProof<C>, PublicKey<C>)],
+    ) -> Result<(), CardProtocolError> {
+        decryption_key
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, (token, proof, pk))| {
+                self.verify_reveal(pk, token, masked_card, proof)
+                    .map_err(|e| e.with_player(i))
+            })
+    }
+
+    /// Verify a chain of consecutive shuffles starting from `initial_deck`. Each shuffle is checked
+    /// against the deck output by the previous one and a failure is attributed to the index of the
+    /// shuffle in `shuffles` (i.e. the position of the shuffling player in the turn order).
+    pub fn verify_shuffle_batch(
+        &self,
+        initial_deck: &[MaskedCard<C>],
+        shuffles: &[(Vec<MaskedCard<C>>, ShuffleProof<C>)],
+    ) -> Result<(), CardProtocolError> {
+        let mut current_deck = initial_deck;
+        for (i, (shuffled_deck, proof)) in shuffles.iter().enumerate() {
+            self.verify_shuffle(current_deck, shuffled_deck, proof)
+                .map_err(|e| e.with_player(i))?;
+            current_deck = shuffled_deck.as_slice();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discrete_log_cards::{self, Verifier};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::witness::Witness;
+    use crate::{
+        CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification,
+        ProtocolSetup,
+    };
+
+    use ark_ff::UniformRand;
+    use proof_essentials::error::CryptoError;
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    type Card = discrete_log_cards::Card<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    #[test]
+    fn verify_game_steps() {
+        let rng = &mut thread_rng();
+        let m = 2;
+        let n = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let keys = (0..3)
+            .map(|i| {
+                let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+                (pk, sk, vec![i as u8])
+            })
+            .collect::<Vec<_>>();
+
+        let participants_digest =
+            CardProtocol::participants_digest(keys.iter().map(|(pk, _, info)| (pk, info))).unwrap();
+
+        let players = keys
+            .into_iter()
+            .map(|(pk, sk, info)| {
+                let proof = CardProtocol::prove_key_ownership_in_game(
+                    rng,
+                    &parameters,
+                    &pk,
+                    &sk,
+                    &info,
+                    &participants_digest,
+                )
+                .unwrap();
+                (pk, sk, proof, info)
+            })
+            .collect::<Vec<_>>();
+
+        let key_proof_info = players
+            .iter()
+            .map(|(pk, _, proof, info)| (*pk, *proof, info.clone()))
+            .collect::<Vec<_>>();
+
+        let verifier = Verifier::from_key_proofs(parameters, &key_proof_info).unwrap();
+        let shared_key = *verifier.shared_key();
+        let parameters = verifier.parameters();
+
+        assert_eq!(Ok(()), verifier.verify_key_ownership_batch(&key_proof_info));
+
+        let masked_deck = (0..m * n)
+            .map(|_| {
+                let card = Card::rand(rng);
+                let alpha = Witness::new(Scalar::rand(rng));
+                let (masked, proof) =
+                    CardProtocol::mask(rng, parameters, &shared_key, &card, &alpha).unwrap();
+                (card, masked, proof)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(Ok(()), verifier.verify_mask_batch(&masked_deck));
+
+        let deck = masked_deck.iter().map(|x| x.1).collect::<Vec<_>>();
+        let mut shuffles = Vec::new();
+        let mut current_deck = deck.clone();
+        for _ in 0..players.len() {
+            let permutation = Permutation::new(rng, m * n);
+            let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
+            let (shuffled, proof) = CardProtocol::shuffle_and_remask(
+                rng,
+                parameters,
+                &shared_key,
+                &current_deck,
+                &masking_factors,
+                &permutation,
+            )
+            .unwrap();
+            current_deck = shuffled.clone();
+            shuffles.push((shuffled, proof));
+        }
+
+        assert_eq!(Ok(()), verifier.verify_shuffle_batch(&deck, &shuffles));
+
+        let card = current_deck[0];
+        let tokens = players
+            .iter()
+            .map(|(pk, sk, _, _)| {
+                let (token, proof) =
+                    CardProtocol::compute_reveal_token(rng, parameters, sk, pk, &card).unwrap();
+                (token, proof, *pk)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(Ok(()), verifier.verify_reveal_batch(&card, &tokens));
+
+        let mut bad_tokens = tokens;
+        bad_tokens[1].0 = RevealToken::rand(rng);
+        assert_eq!(
+            verifier.verify_reveal_batch(&card, &bad_tokens),
+            Err(CardProtocolError::RevealVerificationError {
+                source: CryptoError::ProofVerificationError(String::from("Chaum-Pedersen")),
+                context: ErrorContext::player(1),
+            })
+        );
+
+        let mut bad_shuffles = shuffles;
+        let wrong_output: Vec<MaskedCard> = sample_vector(rng, m * n);
+        bad_shuffles[1].0 = wrong_output;
+        let failed_shuffle = verifier
+            .verify_shuffle_batch(&deck, &bad_shuffles)
+            .unwrap_err();
+        assert_eq!(failed_shuffle.context(), Some(&ErrorContext::player(1)));
+    }
+}
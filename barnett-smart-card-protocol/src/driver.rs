@@ -0,0 +1,714 @@
+//! Asynchronous driver running the protocol for one player, on tokio.
+//!
+//! A [`PlayerDriver`] exchanges signed messages with the other players through a pair of channels, fed by
+//! whatever transport the application uses ([`connect_relay`] bridges them to the TCP relay of the
+//! [`net`](crate::net) module). The transport must deliver every message, including the player's own, to
+//! all the players in the same order, as a bulletin board does.
+//!
+//! Each step of the protocol is a future which posts the player's message when their turn comes, and
+//! resolves once the messages of the other players have been received and checked. Proving and
+//! verifying, which take seconds for a shuffle, run on tokio's blocking thread pool so that they never
+//! stall the executor. Waiting for another player fails with a `Timeout` error, naming the player waited
+//! for, when no message arrives within the configured delay.
+
+use crate::discrete_log_cards::message::{Message, MessageContext, SessionId, DEALER};
+use crate::discrete_log_cards::{
+    Card, DLCards, GameSession, MaskedCard, MaskingProof, Parameters, PlayerSecretKey, PublicKey,
+    SessionPhase,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::net::MAX_FRAME_LEN;
+use crate::witness::Witness;
+use crate::{CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::One;
+use proof_essentials::utils::permutation::Permutation;
+use rand::thread_rng;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio::time;
+
+/// Delay after which waiting for another player fails, unless set with
+/// [`with_timeout`](PlayerDriver::with_timeout)
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const CHANNEL_CAPACITY: usize = 64;
+
+fn transport_closed() -> CardProtocolError {
+    CardProtocolError::IoError(String::from("the transport is closed"))
+}
+
+/// Connect to a [`Relay`](crate::net::Relay). Returns the channels to hand to a [`PlayerDriver`]: frames
+/// sent on the first one are posted on the relay, frames of the board are delivered on the second one.
+/// Both channels close when the connection fails.
+pub async fn connect_relay<A: ToSocketAddrs>(
+    addr: A,
+) -> Result<(mpsc::Sender<Vec<u8>>, mpsc::Receiver<Vec<u8>>), CardProtocolError> {
+    let stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    let (outgoing, mut to_post) = mpsc::channel::<Vec<u8>>(CHANNEL_CAPACITY);
+    task::spawn(async move {
+        while let Some(frame) = to_post.recv().await {
+            if frame.len() > MAX_FRAME_LEN
+                || writer.write_u32_le(frame.len() as u32).await.is_err()
+                || writer.write_all(&frame).await.is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let (delivered, incoming) = mpsc::channel(CHANNEL_CAPACITY);
+    task::spawn(async move {
+        while let Ok(len) = reader.read_u32_le().await {
+            let len = len as usize;
+            if len > MAX_FRAME_LEN {
+                break;
+            }
+
+            let mut frame = vec![0u8; len];
+            if reader.read_exact(&mut frame).await.is_err() || delivered.send(frame).await.is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    Ok((outgoing, incoming))
+}
+
+/// Runs the protocol for one player.
+pub struct PlayerDriver<C: ProjectiveCurve> {
+    index: usize,
+    sk: Arc<PlayerSecretKey<C>>,
+    /// Keys and public information of all the players in turn order, as agreed before the game
+    players: Vec<(PublicKey<C>, Vec<u8>)>,
+    keys: Vec<PublicKey<C>>,
+    context: MessageContext,
+    session: Arc<Mutex<GameSession<C>>>,
+    outgoing: mpsc::Sender<Vec<u8>>,
+    incoming: mpsc::Receiver<Vec<u8>>,
+    timeout: Duration,
+    /// Masked cards received from the dealer so far
+    initial_deck: Vec<(Card<C>, MaskedCard<C>, MaskingProof<C>)>,
+}
+
+impl<C: ProjectiveCurve> PlayerDriver<C> {
+    /// Driver for the player at `index` of `players`, who holds `sk`. Messages are posted on `outgoing`
+    /// and read from `incoming`.
+    pub fn new(
+        pp: Parameters<C>,
+        session_id: SessionId,
+        players: Vec<(PublicKey<C>, Vec<u8>)>,
+        index: usize,
+        sk: PlayerSecretKey<C>,
+        outgoing: mpsc::Sender<Vec<u8>>,
+        incoming: mpsc::Receiver<Vec<u8>>,
+    ) -> Result<Self, CardProtocolError> {
+        if index >= players.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!("no player {} among {}", index, players.len()),
+                context: ErrorContext::default(),
+            });
+        }
+
+        let context = MessageContext::new(&pp, session_id, players.len() as u32)?;
+        let mut session = GameSession::new(pp);
+        session.set_session_id(session_id);
+
+        Ok(Self {
+            index,
+            sk: Arc::new(sk),
+            keys: players.iter().map(|(pk, _)| *pk).collect(),
+            context,
+            players,
+            session: Arc::new(Mutex::new(session)),
+            outgoing,
+            incoming,
+            timeout: DEFAULT_TIMEOUT,
+            initial_deck: Vec::new(),
+        })
+    }
+
+    /// Set the delay after which waiting for another player fails
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.players.len()
+    }
+
+    /// The game as seen by this player
+    pub fn session(&self) -> MutexGuard<'_, GameSession<C>> {
+        self.session.lock().expect("session lock poisoned")
+    }
+
+    /// Run `f` on the session, on the blocking thread pool
+    async fn blocking<T, F>(&self, f: F) -> Result<T, CardProtocolError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut GameSession<C>) -> Result<T, CardProtocolError> + Send + 'static,
+    {
+        let session = Arc::clone(&self.session);
+        task::spawn_blocking(move || f(&mut session.lock().expect("session lock poisoned")))
+            .await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+
+    async fn post(&self, message: Message<C>) -> Result<(), CardProtocolError> {
+        let bytes = self.context.sign(
+            &mut thread_rng(),
+            self.session().parameters(),
+            self.index as u32,
+            &message,
+            &self.keys[self.index],
+            &self.sk,
+        )?;
+
+        self.outgoing
+            .send(bytes)
+            .await
+            .map_err(|_| transport_closed())
+    }
+
+    /// Wait for the next message of the board. `waiting_for` is the player expected to send it, if known.
+    async fn next(
+        &mut self,
+        step: &str,
+        waiting_for: Option<usize>,
+    ) -> Result<(usize, Message<C>), CardProtocolError> {
+        let bytes = time::timeout(self.timeout, self.incoming.recv())
+            .await
+            .map_err(|_| CardProtocolError::Timeout {
+                step: String::from(step),
+                context: ErrorContext {
+                    player: waiting_for,
+                    card: None,
+                },
+            })?
+            .ok_or_else(transport_closed)?;
+
+        let (header, message) =
+            self.context
+                .open(self.session().parameters(), &bytes, &self.keys)?;
+
+        Ok((header.sender as usize, message))
+    }
+
+    /// Check a message and apply it to the session
+    async fn apply(&mut self, sender: usize, message: Message<C>) -> Result<(), CardProtocolError> {
+        let out_of_turn = |expected| CardProtocolError::OutOfTurn {
+            expected,
+            context: ErrorContext::player(sender),
+        };
+
+        match message {
+            Message::KeyOwnership {
+                pk,
+                proof,
+                player_public_info,
+            } => {
+                let expected = self.session().num_players();
+                if sender != expected {
+                    return Err(out_of_turn(expected));
+                }
+                if (pk, &player_public_info) != (self.players[sender].0, &self.players[sender].1) {
+                    return Err(CardProtocolError::ParameterMismatch {
+                        reason: String::from(
+                            "key or public information differs from the one agreed",
+                        ),
+                        context: ErrorContext::player(sender),
+                    });
+                }
+
+                let num_players = self.num_players();
+                self.blocking(move |session| {
+                    session.register_player(pk, proof, player_public_info)?;
+                    if session.num_players() == num_players {
+                        session.aggregate_keys()?;
+                    }
+                    Ok(())
+                })
+                .await
+            }
+            Message::Mask {
+                card,
+                masked_card,
+                proof,
+            } => {
+                if sender != DEALER {
+                    return Err(out_of_turn(DEALER));
+                }
+
+                self.initial_deck.push((card, masked_card, proof));
+                if self.initial_deck.len() < self.session().parameters().deck_size() {
+                    return Ok(());
+                }
+                let initial_deck = std::mem::take(&mut self.initial_deck);
+                self.blocking(move |session| session.submit_initial_deck(&initial_deck))
+                    .await
+            }
+            Message::Shuffle { deck, proof } => {
+                self.blocking(move |session| {
+                    session.submit_shuffle(sender, deck, &proof)?;
+                    if session.phase() == SessionPhase::Dealing {
+                        session.start_play()?;
+                    }
+                    Ok(())
+                })
+                .await
+            }
+            Message::Reveal {
+                card_index,
+                token,
+                proof,
+            } => {
+                self.blocking(move |session| {
+                    session.submit_reveal(card_index as usize, sender, token, proof)
+                })
+                .await
+            }
+            message => Err(CardProtocolError::InvalidMessage(format!(
+                "unexpected {} message",
+                message.kind()
+            ))),
+        }
+    }
+
+    /// Handle incoming messages until `done` holds. `waiting_for` gives the player whose message is
+    /// awaited, if known.
+    async fn wait_until<D, W>(
+        &mut self,
+        step: &str,
+        done: D,
+        waiting_for: W,
+    ) -> Result<(), CardProtocolError>
+    where
+        D: Fn(&GameSession<C>) -> bool,
+        W: Fn(&GameSession<C>) -> Option<usize>,
+    {
+        loop {
+            let awaited = {
+                let session = self.session();
+                if done(&session) {
+                    return Ok(());
+                }
+                waiting_for(&session)
+            };
+
+            let (sender, message) = self.next(step, awaited).await?;
+            self.apply(sender, message).await?;
+        }
+    }
+
+    /// Register with a proof of key ownership bound to the game, in turn order. Resolves with the
+    /// aggregate key once every player has registered.
+    pub async fn register(&mut self) -> Result<PublicKey<C>, CardProtocolError> {
+        let index = self.index;
+        let step = "registration";
+        self.wait_until(
+            step,
+            |session| session.num_players() >= index,
+            |session| Some(session.num_players()),
+        )
+        .await?;
+
+        let (pk, player_public_info) = self.players[index].clone();
+        let participants_digest =
+            DLCards::<C>::participants_digest(self.players.iter().map(|(pk, info)| (pk, info)))?;
+        let sk = Arc::clone(&self.sk);
+        let info = player_public_info.clone();
+        let proof = self
+            .blocking(move |session| {
+                DLCards::<C>::prove_key_ownership_in_game(
+                    &mut thread_rng(),
+                    session.parameters(),
+                    &pk,
+                    &sk,
+                    &info,
+                    &participants_digest,
+                )
+            })
+            .await?;
+        self.post(Message::KeyOwnership {
+            pk,
+            proof,
+            player_public_info,
+        })
+        .await?;
+
+        self.wait_until(
+            step,
+            |session| session.shared_key().is_some(),
+            |session| Some(session.num_players()),
+        )
+        .await?;
+
+        Ok(*self
+            .session()
+            .shared_key()
+            .expect("aggregate key is set after registration"))
+    }
+
+    /// Have the dealer mask the open cards of `encoding` under the aggregate key. Resolves once the
+    /// masking of every card has been checked.
+    pub async fn mask_deck(&mut self, encoding: &[Card<C>]) -> Result<(), CardProtocolError> {
+        if self.index == DEALER {
+            let cards = encoding.to_vec();
+            let masked_cards = self
+                .blocking(move |session| {
+                    let shared_key = *session.shared_key().ok_or_else(|| {
+                        CardProtocolError::WrongProtocolPhase {
+                            expected: String::from("key aggregation"),
+                            found: String::from(session.phase().name()),
+                            context: ErrorContext::default(),
+                        }
+                    })?;
+
+                    cards
+                        .into_iter()
+                        .map(|card| {
+                            let (masked_card, proof) = DLCards::<C>::mask(
+                                &mut thread_rng(),
+                                session.parameters(),
+                                &shared_key,
+                                &card,
+                                &Witness::new(C::ScalarField::one()),
+                            )?;
+                            Ok((card, masked_card, proof))
+                        })
+                        .collect::<Result<Vec<_>, CardProtocolError>>()
+                })
+                .await?;
+
+            for (card, masked_card, proof) in masked_cards {
+                self.post(Message::Mask {
+                    card,
+                    masked_card,
+                    proof,
+                })
+                .await?;
+            }
+        }
+
+        self.wait_until(
+            "masking",
+            |session| matches!(session.phase(), SessionPhase::Shuffling { .. }),
+            |_| Some(DEALER),
+        )
+        .await
+    }
+
+    /// Shuffle the deck when this player's turn comes. Resolves with the final deck once every player has
+    /// shuffled and every shuffle has been checked.
+    pub async fn shuffle(&mut self) -> Result<Vec<MaskedCard<C>>, CardProtocolError> {
+        let index = self.index;
+        let step = "shuffling";
+        let round = |session: &GameSession<C>| match session.phase() {
+            SessionPhase::Shuffling { round } => Some(round),
+            _ => None,
+        };
+
+        self.wait_until(
+            step,
+            |session| round(session).map_or(true, |round| round >= index),
+            round,
+        )
+        .await?;
+
+        let my_turn = round(&self.session()) == Some(index);
+        if my_turn {
+            let (deck, proof) = self
+                .blocking(|session| {
+                    let shared_key = *session
+                        .shared_key()
+                        .expect("aggregate key is set after registration");
+                    let deck = session.deck();
+                    let rng = &mut thread_rng();
+                    let permutation = Permutation::new(rng, deck.len());
+                    let masking_factors: Witness<Vec<C::ScalarField>> =
+                        Witness::sample(rng, deck.len());
+
+                    DLCards::<C>::shuffle_and_remask(
+                        rng,
+                        session.parameters(),
+                        &shared_key,
+                        deck,
+                        &masking_factors,
+                        &permutation,
+                    )
+                })
+                .await?;
+            self.post(Message::Shuffle { deck, proof }).await?;
+        }
+
+        self.wait_until(step, |session| session.phase() == SessionPhase::Play, round)
+            .await?;
+
+        Ok(self.session().deck().to_vec())
+    }
+
+    /// Post this player's reveal token for the card at `card_index` of the final deck, without waiting
+    /// for the other players
+    pub async fn send_reveal_token(&mut self, card_index: usize) -> Result<(), CardProtocolError> {
+        let sk = Arc::clone(&self.sk);
+        let pk = self.keys[self.index];
+        let (token, proof) = self
+            .blocking(move |session| {
+                let masked_card = session.deck().get(card_index).ok_or_else(|| {
+                    CardProtocolError::ParameterMismatch {
+                        reason: format!("no card at index {}", card_index),
+                        context: ErrorContext::card(card_index),
+                    }
+                })?;
+                DLCards::<C>::compute_reveal_token(
+                    &mut thread_rng(),
+                    session.parameters(),
+                    &sk,
+                    &pk,
+                    masked_card,
+                )
+            })
+            .await?;
+
+        self.post(Message::Reveal {
+            card_index: card_index as u64,
+            token,
+            proof,
+        })
+        .await
+    }
+
+    /// Wait for the reveal tokens of the players for which `needed` holds
+    async fn wait_for_tokens<N>(
+        &mut self,
+        card_index: usize,
+        needed: N,
+    ) -> Result<(), CardProtocolError>
+    where
+        N: Fn(usize) -> bool + Copy,
+    {
+        let keys = self.keys.clone();
+        let missing = move |session: &GameSession<C>| {
+            let received = session.reveal_tokens(card_index);
+            (0..keys.len())
+                .filter(|&player| needed(player))
+                .find(|&player| !received.iter().any(|(_, _, pk)| *pk == keys[player]))
+        };
+
+        self.wait_until("reveal", |session| missing(session).is_none(), missing)
+            .await
+    }
+
+    /// Open the card at `card_index` of the final deck in public. Resolves with the card once every player
+    /// has provided their reveal token.
+    pub async fn reveal(&mut self, card_index: usize) -> Result<Card<C>, CardProtocolError> {
+        self.send_reveal_token(card_index).await?;
+        self.wait_for_tokens(card_index, |_| true).await?;
+
+        self.session().open_card(card_index)
+    }
+
+    /// Open the card at `card_index` of the final deck in private, with the reveal tokens of every other
+    /// player and this player's own token, which is never posted.
+    pub async fn peek(&mut self, card_index: usize) -> Result<Card<C>, CardProtocolError> {
+        let index = self.index;
+        self.wait_for_tokens(card_index, move |player| player != index)
+            .await?;
+
+        let sk = Arc::clone(&self.sk);
+        let pk = self.keys[index];
+        self.blocking(move |session| {
+            let masked_card = *session.deck().get(card_index).ok_or_else(|| {
+                CardProtocolError::ParameterMismatch {
+                    reason: format!("no card at index {}", card_index),
+                    context: ErrorContext::card(card_index),
+                }
+            })?;
+            let (token, proof) = DLCards::<C>::compute_reveal_token(
+                &mut thread_rng(),
+                session.parameters(),
+                &sk,
+                &pk,
+                &masked_card,
+            )?;
+            let mut decryption_key = session.reveal_tokens(card_index);
+            decryption_key.push((token, proof, pk));
+
+            DLCards::<C>::unmask(session.parameters(), &decryption_key, &masked_card)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{connect_relay, PlayerDriver};
+    use crate::discrete_log_cards;
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::net::Relay;
+    use crate::{KeyManagement, ProtocolSetup};
+
+    use ark_ff::UniformRand;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use rand::thread_rng;
+    use std::thread;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+
+    const SESSION: [u8; 32] = [3; 32];
+
+    /// Drivers for `num_players` players connected by an in-memory bulletin board
+    fn seat_players(num_players: usize) -> Vec<PlayerDriver<Curve>> {
+        let rng = &mut thread_rng();
+        let parameters = CardProtocol::setup(rng, 2, 4).unwrap();
+        let mut serialized = Vec::new();
+        parameters.serialize(&mut serialized).unwrap();
+
+        let key_pairs = (0..num_players)
+            .map(|_| CardProtocol::player_keygen(rng, &parameters).unwrap())
+            .collect::<Vec<_>>();
+        let players = key_pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (pk, _))| (*pk, format!("player {}", i).into_bytes()))
+            .collect::<Vec<_>>();
+
+        let (outgoing, mut posted) = mpsc::channel::<Vec<u8>>(16);
+        let (inboxes, receivers): (Vec<_>, Vec<_>) =
+            (0..num_players).map(|_| mpsc::channel(1024)).unzip();
+        tokio::spawn(async move {
+            while let Some(bytes) = posted.recv().await {
+                for inbox in &inboxes {
+                    // Players who left do not read their inbox anymore
+                    let _ = inbox.send(bytes.clone()).await;
+                }
+            }
+        });
+
+        key_pairs
+            .into_iter()
+            .zip(receivers)
+            .enumerate()
+            .map(|(index, ((_, sk), incoming))| {
+                PlayerDriver::new(
+                    CardParameters::deserialize(&serialized[..]).unwrap(),
+                    SESSION,
+                    players.clone(),
+                    index,
+                    sk,
+                    outgoing.clone(),
+                    incoming,
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn complete_game() {
+        let encoding = (0..8)
+            .map(|_| Card::rand(&mut thread_rng()))
+            .collect::<Vec<_>>();
+
+        let games = seat_players(3).into_iter().map(|mut driver| {
+            let encoding = encoding.clone();
+            tokio::spawn(async move {
+                driver.register().await?;
+                driver.mask_deck(&encoding).await?;
+                let deck = driver.shuffle().await?;
+                assert_eq!(deck.len(), 8);
+
+                // Card 0 goes to player 0 in private, cards 1 and 2 are opened in public
+                let private = if driver.index() == 0 {
+                    Some(driver.peek(0).await?)
+                } else {
+                    driver.send_reveal_token(0).await?;
+                    None
+                };
+                let public = vec![driver.reveal(1).await?, driver.reveal(2).await?];
+
+                Ok::<_, CardProtocolError>((private, public))
+            })
+        });
+
+        let mut results = Vec::new();
+        for game in games.collect::<Vec<_>>() {
+            results.push(game.await.unwrap().unwrap());
+        }
+
+        let (private, public) = &results[0];
+        let private = private.unwrap();
+        assert!(encoding.contains(&private));
+        for card in public {
+            assert!(encoding.contains(card));
+            assert_ne!(*card, private);
+        }
+        assert_ne!(public[0], public[1]);
+        for (peeked, opened) in &results[1..] {
+            assert_eq!(*peeked, None);
+            assert_eq!(opened, public);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn timeout_names_the_stalled_player() {
+        let mut drivers = seat_players(3);
+        // Player 2 never shows up
+        drivers.pop();
+
+        let registrations = drivers.into_iter().map(|driver| {
+            tokio::spawn(async move {
+                driver
+                    .with_timeout(Duration::from_millis(500))
+                    .register()
+                    .await
+            })
+        });
+
+        for registration in registrations.collect::<Vec<_>>() {
+            assert_eq!(
+                registration.await.unwrap().err(),
+                Some(CardProtocolError::Timeout {
+                    step: String::from("registration"),
+                    context: ErrorContext::player(2),
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn relay_channels() {
+        let relay = Relay::bind("127.0.0.1:0").unwrap();
+        let addr = relay.local_addr().unwrap();
+        thread::spawn(move || relay.run());
+
+        let (first, mut first_board) = connect_relay(addr).await.unwrap();
+        let (_second, mut second_board) = connect_relay(addr).await.unwrap();
+
+        first.send(b"frame".to_vec()).await.unwrap();
+        assert_eq!(first_board.recv().await.unwrap(), b"frame");
+        assert_eq!(second_board.recv().await.unwrap(), b"frame");
+    }
+}
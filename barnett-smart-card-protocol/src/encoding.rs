@@ -0,0 +1,350 @@
+//! Serde support for the protocol objects, enabled with the `serde` feature.
+//!
+//! Every object is encoded as a typed envelope holding the hex encoding of its compressed canonical
+//! bytes, e.g. `{"type":"MaskedCard","hex":"0a1b..."}`. The encoding only depends on `CanonicalSerialize`
+//! and is therefore stable across releases of this crate. Decoding checks the type tag, so that a reveal
+//! token cannot be mistaken for a card, and runs the checked canonical deserialization.
+//!
+//! The protocol types are defined by other crates, so the encodings are exposed as modules to be used
+//! with `#[serde(with = "...")]`:
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Deal<C: ProjectiveCurve> {
+//!     #[serde(with = "encoding::masked_card")]
+//!     card: MaskedCard<C>,
+//!     #[serde(with = "encoding::reveal_proof")]
+//!     proof: RevealProof<C>,
+//! }
+//! ```
+//!
+//! Secret keys are deliberately not covered: use the `keystore` to persist them.
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<'a> {
+    #[serde(rename = "type")]
+    kind: Cow<'a, str>,
+    hex: String,
+}
+
+/// Encode `value` in an envelope tagged with `kind`
+pub fn serialize<S: Serializer, T: CanonicalSerialize>(
+    kind: &str,
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut bytes = Vec::with_capacity(value.serialized_size());
+    value.serialize(&mut bytes).map_err(S::Error::custom)?;
+
+    Envelope {
+        kind: Cow::Borrowed(kind),
+        hex: hex::encode(bytes),
+    }
+    .serialize(serializer)
+}
+
+/// Decode a value from an envelope, which must be tagged with `kind`
+pub fn deserialize<'de, D: Deserializer<'de>, T: CanonicalDeserialize>(
+    kind: &str,
+    deserializer: D,
+) -> Result<T, D::Error> {
+    let envelope = Envelope::deserialize(deserializer)?;
+    if envelope.kind != kind {
+        return Err(D::Error::custom(format!(
+            "expected {}, found {}",
+            kind, envelope.kind
+        )));
+    }
+
+    let bytes = hex::decode(&envelope.hex).map_err(D::Error::custom)?;
+    let mut reader = &bytes[..];
+    let value = T::deserialize(&mut reader).map_err(D::Error::custom)?;
+    if !reader.is_empty() {
+        return Err(D::Error::custom(format!("trailing bytes after {}", kind)));
+    }
+
+    Ok(value)
+}
+
+macro_rules! encoding {
+    ($(#[$doc:meta])* $module:ident, $kind:literal) => {
+        $(#[$doc])*
+        pub mod $module {
+            use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+            use serde::{Deserializer, Serializer};
+
+            pub fn serialize<S: Serializer, T: CanonicalSerialize>(
+                value: &T,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                super::serialize($kind, value, serializer)
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>, T: CanonicalDeserialize>(
+                deserializer: D,
+            ) -> Result<T, D::Error> {
+                super::deserialize($kind, deserializer)
+            }
+        }
+    };
+}
+
+encoding!(
+    /// Public parameters of a game
+    parameters,
+    "Parameters"
+);
+encoding!(
+    /// Scalars, e.g. masking factors
+    scalar,
+    "Scalar"
+);
+encoding!(
+    /// Player public keys
+    public_key,
+    "PublicKey"
+);
+encoding!(
+    /// Aggregate public keys
+    aggregate_public_key,
+    "AggregatePublicKey"
+);
+encoding!(
+    /// Open cards
+    card,
+    "Card"
+);
+encoding!(
+    /// Masked cards
+    masked_card,
+    "MaskedCard"
+);
+encoding!(
+    /// Whole decks of masked cards
+    deck,
+    "Deck"
+);
+encoding!(
+    /// Reveal tokens
+    reveal_token,
+    "RevealToken"
+);
+encoding!(
+    /// Proofs of key ownership
+    key_ownership_proof,
+    "KeyOwnershipProof"
+);
+encoding!(
+    /// Proofs of masking
+    masking_proof,
+    "MaskingProof"
+);
+encoding!(
+    /// Proofs of remasking
+    remasking_proof,
+    "RemaskingProof"
+);
+encoding!(
+    /// Proofs of correct reveal token
+    reveal_proof,
+    "RevealProof"
+);
+encoding!(
+    /// Shuffle arguments
+    shuffle_proof,
+    "ShuffleProof"
+);
+encoding!(
+    /// Proofs of key addition or removal
+    key_switch_proof,
+    "KeySwitchProof"
+);
+
+#[cfg(test)]
+mod test {
+    use crate::discrete_log_cards;
+    use crate::encoding;
+    use crate::witness::Witness;
+    use crate::{CardMasking, CardReveal, DeckShuffle, KeyManagement, KeySwitching, ProtocolSetup};
+
+    use ark_ff::UniformRand;
+    use ark_serialize::CanonicalSerialize;
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+    use rand::thread_rng;
+    use serde::{Deserialize, Serialize};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    #[derive(Serialize, Deserialize)]
+    struct Objects {
+        #[serde(with = "encoding::parameters")]
+        parameters: CardParameters,
+        #[serde(with = "encoding::scalar")]
+        scalar: Scalar,
+        #[serde(with = "encoding::public_key")]
+        pk: PublicKey,
+        #[serde(with = "encoding::aggregate_public_key")]
+        shared_key: PublicKey,
+        #[serde(with = "encoding::card")]
+        card: Card,
+        #[serde(with = "encoding::masked_card")]
+        masked_card: MaskedCard,
+        #[serde(with = "encoding::deck")]
+        deck: Vec<MaskedCard>,
+        #[serde(with = "encoding::reveal_token")]
+        reveal_token: RevealToken,
+        #[serde(with = "encoding::key_ownership_proof")]
+        key_ownership_proof: discrete_log_cards::KeyOwnershipProof<Curve>,
+        #[serde(with = "encoding::masking_proof")]
+        masking_proof: discrete_log_cards::MaskingProof<Curve>,
+        #[serde(with = "encoding::remasking_proof")]
+        remasking_proof: discrete_log_cards::RemaskingProof<Curve>,
+        #[serde(with = "encoding::reveal_proof")]
+        reveal_proof: discrete_log_cards::RevealProof<Curve>,
+        #[serde(with = "encoding::shuffle_proof")]
+        shuffle_proof: discrete_log_cards::ShuffleProof<Curve>,
+        #[serde(with = "encoding::key_switch_proof")]
+        key_switch_proof: discrete_log_cards::KeySwitchProof<Curve>,
+    }
+
+    impl Objects {
+        /// Canonical bytes of every field, to compare objects that do not implement `PartialEq`
+        fn canonical_bytes(&self) -> Vec<Vec<u8>> {
+            fn bytes<T: CanonicalSerialize>(value: &T) -> Vec<u8> {
+                let mut bytes = Vec::new();
+                value.serialize(&mut bytes).unwrap();
+                bytes
+            }
+
+            vec![
+                bytes(&self.parameters),
+                bytes(&self.scalar),
+                bytes(&self.pk),
+                bytes(&self.shared_key),
+                bytes(&self.card),
+                bytes(&self.masked_card),
+                bytes(&self.deck),
+                bytes(&self.reveal_token),
+                bytes(&self.key_ownership_proof),
+                bytes(&self.masking_proof),
+                bytes(&self.remasking_proof),
+                bytes(&self.reveal_proof),
+                bytes(&self.shuffle_proof),
+                bytes(&self.key_switch_proof),
+            ]
+        }
+    }
+
+    fn objects() -> Objects {
+        let rng = &mut thread_rng();
+        let m = 2;
+        let n = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let key_ownership_proof =
+            CardProtocol::prove_key_ownership(rng, &parameters, &pk, &sk, &b"Alice").unwrap();
+        let shared_key = pk;
+
+        let card = Card::rand(rng);
+        let scalar = Scalar::rand(rng);
+        let alpha = Witness::new(scalar);
+        let (masked_card, masking_proof) =
+            CardProtocol::mask(rng, &parameters, &shared_key, &card, &alpha).unwrap();
+        let (_, remasking_proof) =
+            CardProtocol::remask(rng, &parameters, &shared_key, &masked_card, &alpha).unwrap();
+        let (reveal_token, reveal_proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, &sk, &pk, &masked_card).unwrap();
+
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        let permutation = Permutation::new(rng, m * n);
+        let masking_factors: Witness<Vec<Scalar>> = Witness::sample(rng, m * n);
+        let (_, shuffle_proof) = CardProtocol::shuffle_and_remask(
+            rng,
+            &parameters,
+            &shared_key,
+            &deck,
+            &masking_factors,
+            &permutation,
+        )
+        .unwrap();
+
+        let (new_pk, new_sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let (_, key_switch_proof) =
+            CardProtocol::add_key_to_deck(rng, &parameters, &new_pk, &new_sk, &deck).unwrap();
+
+        Objects {
+            parameters,
+            scalar,
+            pk,
+            shared_key,
+            card,
+            masked_card,
+            deck,
+            reveal_token,
+            key_ownership_proof,
+            masking_proof,
+            remasking_proof,
+            reveal_proof,
+            shuffle_proof,
+            key_switch_proof,
+        }
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let objects = objects();
+
+        let json = serde_json::to_string(&objects).unwrap();
+        let decoded: Objects = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.canonical_bytes(), objects.canonical_bytes());
+
+        // The encoding is stable: encoding the decoded objects yields the same JSON
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+    }
+
+    #[test]
+    fn reject_wrong_type() {
+        let objects = objects();
+
+        let json = serde_json::to_value(&objects).unwrap();
+        assert_eq!(json["card"]["type"], "Card");
+
+        // A reveal token is a point like a card, but it must not be accepted as one
+        let mut swapped = json.clone();
+        swapped["card"] = json["reveal_token"].clone();
+        assert!(serde_json::from_value::<Objects>(swapped).is_err());
+
+        // Key switch proofs share the type of the other Chaum-Pedersen proofs, but not their tag
+        assert_eq!(json["key_switch_proof"]["type"], "KeySwitchProof");
+        let mut swapped = json.clone();
+        swapped["reveal_proof"] = json["key_switch_proof"].clone();
+        assert!(serde_json::from_value::<Objects>(swapped).is_err());
+
+        let mut truncated = json.clone();
+        let hex = json["masked_card"]["hex"].as_str().unwrap();
+        truncated["masked_card"]["hex"] = hex[..hex.len() - 2].into();
+        assert!(serde_json::from_value::<Objects>(truncated).is_err());
+
+        let mut extended = json;
+        extended["pk"]["hex"] = format!("{}00", extended["pk"]["hex"].as_str().unwrap()).into();
+        assert!(serde_json::from_value::<Objects>(extended).is_err());
+    }
+}
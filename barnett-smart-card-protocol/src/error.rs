@@ -1,14 +1,255 @@
+use ark_serialize::SerializationError;
 use proof_essentials::error::CryptoError;
+use std::fmt;
 use thiserror::Error;
 
-/// This is an error that could occur when running a cryptographic primitive
+/// Location of a failure within a game: the index of the player who produced the faulty data
+/// and/or the index of the card it relates to, when these are known.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub player: Option<usize>,
+    pub card: Option<usize>,
+}
+
+impl ErrorContext {
+    pub fn player(player: usize) -> Self {
+        Self {
+            player: Some(player),
+            card: None,
+        }
+    }
+
+    pub fn card(card: usize) -> Self {
+        Self {
+            player: None,
+            card: Some(card),
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.player, self.card) {
+            (None, None) => Ok(()),
+            (Some(player), None) => write!(f, " (player {})", player),
+            (None, Some(card)) => write!(f, " (card {})", card),
+            (Some(player), Some(card)) => write!(f, " (player {}, card {})", player, card),
+        }
+    }
+}
+
+/// This is an error that could occur when running the card protocol
 #[derive(Error, Debug, PartialEq)]
 pub enum CardProtocolError {
-    #[error("Failed to verify proof")]
-    ProofVerificationError(#[from] CryptoError),
+    #[error("Parameter mismatch{context}: {reason}")]
+    ParameterMismatch {
+        reason: String,
+        context: ErrorContext,
+    },
+
+    #[error("Deck size mismatch{context}: expected {expected} cards, found {found}")]
+    DeckSizeMismatch {
+        expected: usize,
+        found: usize,
+        context: ErrorContext,
+    },
+
+    #[error("Invalid curve point{context}")]
+    InvalidPoint { context: ErrorContext },
+
+    #[error("Unknown card encoding{context}")]
+    UnknownCardEncoding { context: ErrorContext },
+
+    #[error("Duplicate player key{context}")]
+    DuplicatePlayerKey { context: ErrorContext },
+
+    #[error("Duplicate player identity{context}")]
+    DuplicatePlayerIdentity { context: ErrorContext },
+
+    #[error("Wrong protocol phase{context}: expected {expected}, found {found}")]
+    WrongProtocolPhase {
+        expected: String,
+        found: String,
+        context: ErrorContext,
+    },
+
+    #[error("Failed to verify key ownership proof{context}")]
+    KeyOwnershipVerificationError {
+        source: CryptoError,
+        context: ErrorContext,
+    },
+
+    #[error("Failed to verify masking proof{context}")]
+    MaskingVerificationError {
+        source: CryptoError,
+        context: ErrorContext,
+    },
+
+    #[error("Failed to verify remasking proof{context}")]
+    RemaskingVerificationError {
+        source: CryptoError,
+        context: ErrorContext,
+    },
+
+    #[error("Failed to verify reveal proof{context}")]
+    RevealVerificationError {
+        source: CryptoError,
+        context: ErrorContext,
+    },
+
+    #[error("Failed to verify shuffle argument{context}")]
+    ShuffleVerificationError {
+        source: CryptoError,
+        context: ErrorContext,
+    },
+
+    #[error("Failed to verify key switch proof{context}")]
+    KeySwitchVerificationError {
+        source: CryptoError,
+        context: ErrorContext,
+    },
+
+    #[error("Cryptographic primitive failed: {0}")]
+    CryptoError(#[from] CryptoError),
+
+    #[error("Invalid transcript step {step}: {source}")]
+    InvalidTranscriptStep {
+        step: usize,
+        source: Box<CardProtocolError>,
+    },
+
+    #[error("Unsupported message version {0}")]
+    UnsupportedMessageVersion(u8),
+
+    #[error("Message header mismatch: unexpected {field}")]
+    MessageHeaderMismatch { field: String },
+
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+
+    #[error("Failed to verify message signature{context}")]
+    MessageSignatureError {
+        source: CryptoError,
+        context: ErrorContext,
+    },
+
+    #[error("Message out of turn{context}: expected player {expected}")]
+    OutOfTurn {
+        expected: usize,
+        context: ErrorContext,
+    },
+
+    #[error("Equivocation{context}: conflicting messages for the same protocol step")]
+    Equivocation { context: ErrorContext },
+
+    #[error("Timeout{context}: no message received during {step}")]
+    Timeout { step: String, context: ErrorContext },
+
+    #[error("Invalid bet{context}: {reason}")]
+    InvalidBet {
+        reason: String,
+        context: ErrorContext,
+    },
+
+    #[error("Keystore error: {0}")]
+    KeystoreError(String),
+
+    #[error("Unsupported keystore version {0}")]
+    UnsupportedKeystoreVersion(u8),
+
+    #[error("Failed to decrypt keystore: wrong passphrase or corrupted keystore")]
+    KeystoreDecryptionError,
 
     #[error("IoError: {0}")]
     IoError(String),
+
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+}
+
+impl CardProtocolError {
+    /// Context attached to this error, if the variant carries one.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::ParameterMismatch { context, .. }
+            | Self::DeckSizeMismatch { context, .. }
+            | Self::InvalidPoint { context }
+            | Self::UnknownCardEncoding { context }
+            | Self::DuplicatePlayerKey { context }
+            | Self::DuplicatePlayerIdentity { context }
+            | Self::WrongProtocolPhase { context, .. }
+            | Self::KeyOwnershipVerificationError { context, .. }
+            | Self::MaskingVerificationError { context, .. }
+            | Self::RemaskingVerificationError { context, .. }
+            | Self::RevealVerificationError { context, .. }
+            | Self::ShuffleVerificationError { context, .. }
+            | Self::KeySwitchVerificationError { context, .. }
+            | Self::MessageSignatureError { context, .. }
+            | Self::OutOfTurn { context, .. }
+            | Self::Equivocation { context }
+            | Self::Timeout { context, .. }
+            | Self::InvalidBet { context, .. } => Some(context),
+            Self::InvalidTranscriptStep { .. }
+            | Self::CryptoError(_)
+            | Self::UnsupportedMessageVersion(_)
+            | Self::MessageHeaderMismatch { .. }
+            | Self::InvalidMessage(_)
+            | Self::KeystoreError(_)
+            | Self::UnsupportedKeystoreVersion(_)
+            | Self::KeystoreDecryptionError
+            | Self::IoError(_)
+            | Self::SerializationError(_) => None,
+        }
+    }
+
+    fn context_mut(&mut self) -> Option<&mut ErrorContext> {
+        match self {
+            Self::ParameterMismatch { context, .. }
+            | Self::DeckSizeMismatch { context, .. }
+            | Self::InvalidPoint { context }
+            | Self::UnknownCardEncoding { context }
+            | Self::DuplicatePlayerKey { context }
+            | Self::DuplicatePlayerIdentity { context }
+            | Self::WrongProtocolPhase { context, .. }
+            | Self::KeyOwnershipVerificationError { context, .. }
+            | Self::MaskingVerificationError { context, .. }
+            | Self::RemaskingVerificationError { context, .. }
+            | Self::RevealVerificationError { context, .. }
+            | Self::ShuffleVerificationError { context, .. }
+            | Self::KeySwitchVerificationError { context, .. }
+            | Self::MessageSignatureError { context, .. }
+            | Self::OutOfTurn { context, .. }
+            | Self::Equivocation { context }
+            | Self::Timeout { context, .. }
+            | Self::InvalidBet { context, .. } => Some(context),
+            Self::InvalidTranscriptStep { .. }
+            | Self::CryptoError(_)
+            | Self::UnsupportedMessageVersion(_)
+            | Self::MessageHeaderMismatch { .. }
+            | Self::InvalidMessage(_)
+            | Self::KeystoreError(_)
+            | Self::UnsupportedKeystoreVersion(_)
+            | Self::KeystoreDecryptionError
+            | Self::IoError(_)
+            | Self::SerializationError(_) => None,
+        }
+    }
+
+    /// Attribute this error to the player at index `player`.
+    pub fn with_player(mut self, player: usize) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.player = Some(player);
+        }
+        self
+    }
+
+    /// Attach the index of the card this error relates to.
+    pub fn with_card(mut self, card: usize) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.card = Some(card);
+        }
+        self
+    }
 }
 
 impl From<std::io::Error> for CardProtocolError {
@@ -16,3 +257,9 @@ impl From<std::io::Error> for CardProtocolError {
         Self::IoError(err.to_string())
     }
 }
+
+impl From<SerializationError> for CardProtocolError {
+    fn from(err: SerializationError) -> Self {
+        Self::SerializationError(err.to_string())
+    }
+}
@@ -0,0 +1,342 @@
+//! Poker hand ranking for decoded cards.
+//!
+//! Hands of 5 to 7 cards are ranked by their best 5-card combination. Ranks compare with the usual poker
+//! rules: first the category of the hand, then the values that break ties within a category (the value
+//! of the groups of cards by decreasing size, then the kickers). Suites never break ties.
+
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::playing_cards::{ClassicPlayingCard, Value};
+
+use std::collections::HashSet;
+
+/// Categories of poker hands, from weakest to strongest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HandCategory {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+}
+
+/// Strength of a hand. Stronger hands compare greater, equal ranks split the pot.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HandRank {
+    category: HandCategory,
+    tiebreak: Vec<Value>,
+}
+
+impl HandRank {
+    pub fn category(&self) -> HandCategory {
+        self.category
+    }
+
+    /// Values breaking ties between hands of the same category, most significant first
+    pub fn tiebreak(&self) -> &[Value] {
+        &self.tiebreak
+    }
+}
+
+/// Rank exactly five cards
+fn rank_five(cards: &[ClassicPlayingCard; 5]) -> HandRank {
+    let mut values = cards.iter().map(|card| card.value()).collect::<Vec<_>>();
+    values.sort_unstable_by(|a, b| b.cmp(a));
+
+    // Groups of equal values, largest groups first, then highest values first
+    let mut groups: Vec<(usize, Value)> = Vec::with_capacity(5);
+    for &value in &values {
+        match groups.last_mut() {
+            Some((count, last)) if *last == value => *count += 1,
+            _ => groups.push((1, value)),
+        }
+    }
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+    let group_values = groups.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+
+    let is_flush = cards.iter().all(|card| card.suite() == cards[0].suite());
+    let straight_high = if groups.len() < 5 {
+        None
+    } else if values[0] as usize - values[4] as usize == 4 {
+        Some(values[0])
+    } else if values
+        == [
+            Value::Ace,
+            Value::Five,
+            Value::Four,
+            Value::Three,
+            Value::Two,
+        ]
+    {
+        // The ace plays low in the wheel
+        Some(Value::Five)
+    } else {
+        None
+    };
+
+    let (category, tiebreak) = match (straight_high, is_flush, groups[0].0, groups.len()) {
+        (Some(high), true, _, _) => (HandCategory::StraightFlush, vec![high]),
+        (_, _, 4, _) => (HandCategory::FourOfAKind, group_values),
+        (_, _, 3, 2) => (HandCategory::FullHouse, group_values),
+        (_, true, _, _) => (HandCategory::Flush, values),
+        (Some(high), _, _, _) => (HandCategory::Straight, vec![high]),
+        (_, _, 3, _) => (HandCategory::ThreeOfAKind, group_values),
+        (_, _, 2, 3) => (HandCategory::TwoPair, group_values),
+        (_, _, 2, _) => (HandCategory::OnePair, group_values),
+        _ => (HandCategory::HighCard, values),
+    };
+
+    HandRank { category, tiebreak }
+}
+
+/// Rank a hand of 5 to 7 distinct cards by its best 5-card combination.
+pub fn evaluate(cards: &[ClassicPlayingCard]) -> Result<HandRank, CardProtocolError> {
+    if !(5..=7).contains(&cards.len()) {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: format!("hands have 5 to 7 cards, found {}", cards.len()),
+            context: ErrorContext::default(),
+        });
+    }
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = cards.iter().position(|card| !seen.insert(*card)) {
+        return Err(CardProtocolError::ParameterMismatch {
+            reason: format!("{:?} appears twice in the hand", cards[duplicate]),
+            context: ErrorContext::card(duplicate),
+        });
+    }
+
+    let n = cards.len();
+    let mut best: Option<HandRank> = None;
+    for a in 0..n {
+        for b in a + 1..n {
+            for c in b + 1..n {
+                for d in c + 1..n {
+                    for e in d + 1..n {
+                        let rank = rank_five(&[cards[a], cards[b], cards[c], cards[d], cards[e]]);
+                        if best.as_ref().map_or(true, |best| rank > *best) {
+                            best = Some(rank);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(best.expect("a hand has at least one 5-card combination"))
+}
+
+/// Pick the winners of a showdown. Each player's hand is made of the `board` and their hole cards, or is
+/// `None` if the player mucked. Returns the indices of all the players holding the best hand.
+pub fn winners(
+    board: &[ClassicPlayingCard],
+    hands: &[Option<Vec<ClassicPlayingCard>>],
+) -> Result<Vec<usize>, CardProtocolError> {
+    let mut best: Option<HandRank> = None;
+    let mut winners = Vec::new();
+
+    for (player, hole_cards) in hands.iter().enumerate() {
+        let hole_cards = match hole_cards {
+            Some(hole_cards) => hole_cards,
+            None => continue,
+        };
+
+        let cards = board
+            .iter()
+            .chain(hole_cards.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        let rank = evaluate(&cards).map_err(|e| e.with_player(player))?;
+
+        match best.as_ref().map(|best| rank.cmp(best)) {
+            Some(std::cmp::Ordering::Less) => {}
+            Some(std::cmp::Ordering::Equal) => winners.push(player),
+            _ => {
+                best = Some(rank);
+                winners = vec![player];
+            }
+        }
+    }
+
+    Ok(winners)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate, rank_five, winners, HandCategory};
+    use crate::error::{CardProtocolError, ErrorContext};
+    use crate::playing_cards::{ClassicPlayingCard, Suite, Value};
+
+    use std::collections::{HashMap, HashSet};
+
+    /// Parse cards written as e.g. "As Td 9c 2h"
+    fn cards(hand: &str) -> Vec<ClassicPlayingCard> {
+        hand.split_whitespace()
+            .map(|card| {
+                let mut chars = card.chars();
+                let value = match chars.next().unwrap() {
+                    '2' => Value::Two,
+                    '3' => Value::Three,
+                    '4' => Value::Four,
+                    '5' => Value::Five,
+                    '6' => Value::Six,
+                    '7' => Value::Seven,
+                    '8' => Value::Eight,
+                    '9' => Value::Nine,
+                    'T' => Value::Ten,
+                    'J' => Value::Jack,
+                    'Q' => Value::Queen,
+                    'K' => Value::King,
+                    'A' => Value::Ace,
+                    c => panic!("unknown value {}", c),
+                };
+                let suite = match chars.next().unwrap() {
+                    'c' => Suite::Club,
+                    'd' => Suite::Diamond,
+                    'h' => Suite::Heart,
+                    's' => Suite::Spade,
+                    c => panic!("unknown suite {}", c),
+                };
+                ClassicPlayingCard::new(value, suite)
+            })
+            .collect()
+    }
+
+    fn rank(hand: &str) -> super::HandRank {
+        evaluate(&cards(hand)).unwrap()
+    }
+
+    #[test]
+    fn all_five_card_hands() {
+        let deck = ClassicPlayingCard::deck();
+        let mut categories = HashMap::new();
+        let mut ranks = HashSet::new();
+
+        for a in 0..52 {
+            for b in a + 1..52 {
+                for c in b + 1..52 {
+                    for d in c + 1..52 {
+                        for e in d + 1..52 {
+                            let rank = rank_five(&[deck[a], deck[b], deck[c], deck[d], deck[e]]);
+                            *categories.entry(rank.category()).or_insert(0usize) += 1;
+                            ranks.insert(rank);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Number of 5-card hands in each category
+        let expected = [
+            (HandCategory::StraightFlush, 40),
+            (HandCategory::FourOfAKind, 624),
+            (HandCategory::FullHouse, 3744),
+            (HandCategory::Flush, 5108),
+            (HandCategory::Straight, 10200),
+            (HandCategory::ThreeOfAKind, 54912),
+            (HandCategory::TwoPair, 123552),
+            (HandCategory::OnePair, 1098240),
+            (HandCategory::HighCard, 1302540),
+        ];
+        for (category, count) in expected {
+            assert_eq!(categories[&category], count, "{:?}", category);
+        }
+
+        // Number of distinct hand strengths
+        assert_eq!(ranks.len(), 7462);
+    }
+
+    #[test]
+    fn categories_and_ties() {
+        assert_eq!(
+            rank("As Ks Qs Js Ts").category(),
+            HandCategory::StraightFlush
+        );
+        assert_eq!(
+            rank("5d 4d 3d 2d Ad").category(),
+            HandCategory::StraightFlush
+        );
+        assert_eq!(rank("Ah 2c 3d 4s 5h").tiebreak(), &[Value::Five]);
+        assert_eq!(rank("Kh Ac Qd Js Th").category(), HandCategory::Straight);
+        assert_eq!(rank("Qh Kc Ad 2s 3h").category(), HandCategory::HighCard);
+
+        // Stronger categories win
+        assert!(rank("2c 2d 2h 2s 3c") > rank("Ac Ad Ah Kc Kd"));
+        assert!(rank("2c 3d 4h 5s 6c") > rank("Ac Ad Ah Kc Qd"));
+        assert!(rank("2c 7c 4c 5c 9c") > rank("Tc Jd Qh Ks Ac"));
+
+        // The wheel is the lowest straight
+        assert!(rank("2c 3d 4h 5s 6c") > rank("Ac 2d 3h 4s 5c"));
+
+        // Groups break ties before kickers
+        assert!(rank("3c 3d 3h 2c 2d") > rank("2c 2d 2h Ac Ad"));
+        assert!(rank("Ac Ad 3h 3s 2c") > rank("Kc Kd Qh Qs Jc"));
+        assert!(rank("Ac Ad 3h 3s 5c") > rank("Ah As 3c 3d 4c"));
+        assert!(rank("9c 9d Ah 3s 2c") > rank("9h 9s Kh Qs Jc"));
+        assert!(rank("Ac Kc 9c 3c 2c") > rank("Ad Kd 8d 7d 6d"));
+
+        // Suites do not break ties
+        assert_eq!(rank("Ac Kd 9h 7s 2c"), rank("As Kh 9d 7c 2h"));
+
+        // The best five cards of seven are used
+        assert_eq!(
+            rank("Ac Ad Ah 2c 2d 3s 3h").category(),
+            HandCategory::FullHouse
+        );
+        assert_eq!(
+            rank("Ac Ad Ah 2c 2d 3s 3h").tiebreak(),
+            &[Value::Ace, Value::Three]
+        );
+        assert_eq!(rank("2h 3h 4h 5h 6h 7h Ac").tiebreak(), &[Value::Seven]);
+        assert_eq!(rank("Ac Kd 9h 7s 2c 3d"), rank("Ac Kd 9h 7s 3h"));
+    }
+
+    #[test]
+    fn invalid_hands() {
+        assert!(matches!(
+            evaluate(&cards("Ac Kd 9h 7s")),
+            Err(CardProtocolError::ParameterMismatch { .. })
+        ));
+        assert!(matches!(
+            evaluate(&cards("Ac Kd 9h 7s 2c 3d 4h 5s")),
+            Err(CardProtocolError::ParameterMismatch { .. })
+        ));
+        assert_eq!(
+            evaluate(&cards("Ac Kd 9h Kd 2c")).err().unwrap().context(),
+            Some(&ErrorContext::card(3))
+        );
+    }
+
+    #[test]
+    fn showdown_winners() {
+        let board = cards("Ah Kh 7c 4d 2s");
+
+        let hands = vec![
+            Some(cards("Ac 3s")),
+            None,
+            Some(cards("Kc Kd")),
+            Some(cards("Qd Jd")),
+        ];
+        assert_eq!(winners(&board, &hands), Ok(vec![2]));
+
+        // Split pot: both players play the board
+        let board = cards("As Ks Qs Js Ts");
+        let hands = vec![Some(cards("2c 3d")), Some(cards("4c 5d")), None];
+        assert_eq!(winners(&board, &hands), Ok(vec![0, 1]));
+
+        // Everybody mucked
+        assert_eq!(winners(&board, &[None, None]), Ok(vec![]));
+
+        // Invalid hands are attributed to their player
+        let hands = vec![Some(cards("2c 3d")), Some(cards("As 5d"))];
+        assert_eq!(
+            winners(&board, &hands).err().unwrap().context(),
+            Some(&ErrorContext {
+                player: Some(1),
+                card: Some(5),
+            })
+        );
+    }
+}
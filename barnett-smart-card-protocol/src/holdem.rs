@@ -0,0 +1,560 @@
+//! Texas Hold'em hands played with the discrete log card protocol.
+//!
+//! A [`Table`] drives a [`GameSession`] through a complete hand: every player shuffles the 52-card deck
+//! in turn, two hole cards are dealt privately to each player, the flop, turn and river are opened in
+//! public after a burn card each, and players either show their hole cards at the showdown or muck them
+//! without revealing anything. The table also runs the [`Betting`] of the hand: a street is only dealt
+//! once the previous betting round is over, and players who fold are folded in the protocol as well.
+//!
+//! Cards are identified by their index in the open deck, from 0 to 51, which is also their id as a
+//! [`ClassicPlayingCard`].
+
+use crate::betting::{Action, Betting, Chips};
+use crate::discrete_log_cards::{
+    Card, DLCards, GameSession, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealProof,
+    RevealToken, ShuffleProof,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::evaluator;
+use crate::playing_cards::ClassicPlayingCard;
+use crate::witness::Witness;
+use crate::{
+    CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification, ProtocolSetup,
+    RevealVerification,
+};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{One, UniformRand};
+use ark_std::rand::Rng;
+use proof_essentials::utils::permutation::Permutation;
+
+/// Number of cards in a deck
+pub const DECK_SIZE: usize = 52;
+/// Number of hole cards dealt to each player
+pub const HOLE_CARDS: usize = 2;
+/// Largest table a single deck can serve: hole cards, three burn cards and five community cards
+pub const MAX_PLAYERS: usize = (DECK_SIZE - 3 - 5) / HOLE_CARDS;
+
+/// Streets of a hand, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Street {
+    /// The deck is shuffled, hole cards are not dealt yet
+    Deal,
+    Preflop,
+    Flop,
+    Turn,
+    River,
+    Showdown,
+}
+
+impl Street {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Deal => "deal",
+            Self::Preflop => "preflop",
+            Self::Flop => "flop",
+            Self::Turn => "turn",
+            Self::River => "river",
+            Self::Showdown => "showdown",
+        }
+    }
+}
+
+/// A player sitting at the table, holding their own secret key.
+pub struct HoldemPlayer<C: ProjectiveCurve> {
+    name: Vec<u8>,
+    pk: PublicKey<C>,
+    sk: PlayerSecretKey<C>,
+    hole_cards: Vec<usize>,
+}
+
+impl<C: ProjectiveCurve> HoldemPlayer<C> {
+    pub fn new<R: Rng>(
+        rng: &mut R,
+        pp: &Parameters<C>,
+        name: &[u8],
+    ) -> Result<Self, CardProtocolError> {
+        let (pk, sk) = DLCards::<C>::player_keygen(rng, pp)?;
+
+        Ok(Self {
+            name: name.to_vec(),
+            pk,
+            sk,
+            hole_cards: Vec::new(),
+        })
+    }
+
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    pub fn pk(&self) -> &PublicKey<C> {
+        &self.pk
+    }
+
+    /// Hole cards of the player, only known to them until the showdown
+    pub fn hole_cards(&self) -> &[usize] {
+        &self.hole_cards
+    }
+
+    fn shuffle<R: Rng>(
+        &self,
+        rng: &mut R,
+        pp: &Parameters<C>,
+        shared_key: &PublicKey<C>,
+        deck: &[MaskedCard<C>],
+    ) -> Result<(Vec<MaskedCard<C>>, ShuffleProof<C>), CardProtocolError> {
+        let permutation = Permutation::new(rng, deck.len());
+        let masking_factors: Witness<Vec<C::ScalarField>> = Witness::sample(rng, deck.len());
+
+        DLCards::<C>::shuffle_and_remask(rng, pp, shared_key, deck, &masking_factors, &permutation)
+    }
+
+    fn reveal_token<R: Rng>(
+        &self,
+        rng: &mut R,
+        pp: &Parameters<C>,
+        masked_card: &MaskedCard<C>,
+    ) -> Result<(RevealToken<C>, RevealProof<C>), CardProtocolError> {
+        DLCards::<C>::compute_reveal_token(rng, pp, &self.sk, &self.pk, masked_card)
+    }
+}
+
+/// A hand of Texas Hold'em between in-process players.
+pub struct Table<C: ProjectiveCurve> {
+    session: GameSession<C>,
+    betting: Betting,
+    /// Open cards, indexed by card id
+    encoding: Vec<Card<C>>,
+    street: Street,
+    /// Index in the final deck of the next card to deal
+    next_card: usize,
+    /// Index in the final deck of the hole cards of each player
+    hole_cards: Vec<Vec<usize>>,
+    board: Vec<usize>,
+}
+
+impl<C: ProjectiveCurve> Table<C> {
+    /// Parameters for a 52-card deck
+    pub fn setup<R: Rng>(rng: &mut R) -> Result<Parameters<C>, CardProtocolError> {
+        DLCards::<C>::setup(rng, 4, 13)
+    }
+
+    /// Register the players, mask the deck under their aggregate key and have every player shuffle it in
+    /// turn, in the order of `players`. The hand is bet with `betting`, whose seats are those of
+    /// `players`.
+    pub fn new<R: Rng>(
+        rng: &mut R,
+        pp: Parameters<C>,
+        players: &[HoldemPlayer<C>],
+        betting: Betting,
+    ) -> Result<Self, CardProtocolError> {
+        pp.check_deck_size(DECK_SIZE)?;
+        if !(2..=MAX_PLAYERS).contains(&players.len()) {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!(
+                    "{} players at the table, expected 2 to {}",
+                    players.len(),
+                    MAX_PLAYERS
+                ),
+                context: ErrorContext::default(),
+            });
+        }
+        if betting.num_players() != players.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!(
+                    "{} players at the table but {} seats in the betting",
+                    players.len(),
+                    betting.num_players()
+                ),
+                context: ErrorContext::default(),
+            });
+        }
+
+        let mut session = GameSession::new(pp);
+
+        let participants_digest =
+            DLCards::<C>::participants_digest(players.iter().map(|p| (&p.pk, &p.name)))?;
+        for player in players {
+            let proof = DLCards::<C>::prove_key_ownership_in_game(
+                rng,
+                session.parameters(),
+                &player.pk,
+                &player.sk,
+                &player.name,
+                &participants_digest,
+            )?;
+            session.register_player(player.pk, proof, player.name.clone())?;
+        }
+        let shared_key = *session.aggregate_keys()?;
+
+        let encoding: Vec<Card<C>> = (0..DECK_SIZE).map(|_| Card::<C>::rand(rng)).collect();
+        let initial_deck = encoding
+            .iter()
+            .map(|card| {
+                let (masked, proof) = DLCards::<C>::mask(
+                    rng,
+                    session.parameters(),
+                    &shared_key,
+                    card,
+                    &Witness::new(C::ScalarField::one()),
+                )?;
+                Ok((*card, masked, proof))
+            })
+            .collect::<Result<Vec<_>, CardProtocolError>>()?;
+        session.submit_initial_deck(&initial_deck)?;
+
+        for (i, player) in players.iter().enumerate() {
+            let (deck, proof) =
+                player.shuffle(rng, session.parameters(), &shared_key, session.deck())?;
+            session.submit_shuffle(i, deck, &proof)?;
+        }
+
+        Ok(Self {
+            session,
+            betting,
+            encoding,
+            street: Street::Deal,
+            next_card: 0,
+            hole_cards: vec![Vec::new(); players.len()],
+            board: Vec::new(),
+        })
+    }
+
+    pub fn session(&self) -> &GameSession<C> {
+        &self.session
+    }
+
+    pub fn betting(&self) -> &Betting {
+        &self.betting
+    }
+
+    pub fn street(&self) -> Street {
+        self.street
+    }
+
+    /// Community cards opened so far
+    pub fn board(&self) -> &[usize] {
+        &self.board
+    }
+
+    fn advance_check(&self, expected: Street) -> Result<(), CardProtocolError> {
+        if self.street != expected {
+            return Err(CardProtocolError::WrongProtocolPhase {
+                expected: String::from(expected.name()),
+                found: String::from(self.street.name()),
+                context: ErrorContext::default(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn advance(&mut self, from: Street, to: Street) -> Result<(), CardProtocolError> {
+        self.advance_check(from)?;
+        self.street = to;
+
+        Ok(())
+    }
+
+    /// Move on from a betting street, once its betting round is over
+    fn advance_betting(&mut self, from: Street, to: Street) -> Result<(), CardProtocolError> {
+        self.advance_check(from)?;
+        if to != Street::Showdown {
+            self.betting.next_street()?;
+        } else if let Some(player) = self.betting.to_act() {
+            return Err(CardProtocolError::InvalidBet {
+                reason: String::from("the betting round is not over"),
+                context: ErrorContext::player(player),
+            });
+        }
+        self.street = to;
+
+        Ok(())
+    }
+
+    fn check_players(&self, players: &[HoldemPlayer<C>]) -> Result<(), CardProtocolError> {
+        for (i, player) in players.iter().enumerate() {
+            if self.session.player_key(i) != Some(&player.pk) {
+                return Err(CardProtocolError::ParameterMismatch {
+                    reason: String::from("player is not seated at this table"),
+                    context: ErrorContext::player(i),
+                });
+            }
+        }
+        if players.len() != self.session.num_players() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!(
+                    "expected {} players, found {}",
+                    self.session.num_players(),
+                    players.len()
+                ),
+                context: ErrorContext::default(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Card id of an open card
+    fn decode(&self, card: &Card<C>) -> Result<usize, CardProtocolError> {
+        self.encoding.iter().position(|c| c == card).ok_or_else(|| {
+            CardProtocolError::UnknownCardEncoding {
+                context: ErrorContext::default(),
+            }
+        })
+    }
+
+    fn take_card(&mut self) -> usize {
+        let card_index = self.next_card;
+        self.next_card += 1;
+        card_index
+    }
+
+    /// Submit the reveal token of `player` for the card at `card_index` of the final deck
+    fn reveal<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        player: usize,
+        players: &[HoldemPlayer<C>],
+        card_index: usize,
+    ) -> Result<(), CardProtocolError> {
+        let (token, proof) = players[player].reveal_token(
+            rng,
+            self.session.parameters(),
+            &self.session.deck()[card_index],
+        )?;
+        self.session.submit_reveal(card_index, player, token, proof)
+    }
+
+    /// Deal two hole cards to every player. All other players provide their reveal tokens for each hole
+    /// card, which its owner combines with their own token to look at the card in private.
+    pub fn deal_hole_cards<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        players: &mut [HoldemPlayer<C>],
+    ) -> Result<(), CardProtocolError> {
+        self.check_players(players)?;
+        self.advance(Street::Deal, Street::Preflop)?;
+
+        for _ in 0..HOLE_CARDS {
+            for player in 0..players.len() {
+                let card_index = self.take_card();
+                self.session.deal(card_index, player)?;
+                self.hole_cards[player].push(card_index);
+            }
+        }
+
+        for owner in 0..players.len() {
+            for card_index in self.hole_cards[owner].clone() {
+                for player in (0..players.len()).filter(|&player| player != owner) {
+                    self.reveal(rng, player, players, card_index)?;
+                }
+
+                let masked_card = &self.session.deck()[card_index];
+                let (token, proof) =
+                    players[owner].reveal_token(rng, self.session.parameters(), masked_card)?;
+                let mut decryption_key = self.session.reveal_tokens(card_index);
+                decryption_key.push((token, proof, players[owner].pk));
+
+                let card =
+                    DLCards::<C>::unmask(self.session.parameters(), &decryption_key, masked_card)
+                        .map_err(|e| e.with_player(owner).with_card(card_index))?;
+                let card_id = self
+                    .decode(&card)
+                    .map_err(|e| e.with_player(owner).with_card(card_index))?;
+                players[owner].hole_cards.push(card_id);
+            }
+        }
+
+        self.session.start_play()
+    }
+
+    /// Burn a card, then open `count` community cards
+    fn open_board<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        players: &[HoldemPlayer<C>],
+        count: usize,
+    ) -> Result<(), CardProtocolError> {
+        self.take_card();
+
+        for _ in 0..count {
+            let card_index = self.take_card();
+            for player in 0..players.len() {
+                self.reveal(rng, player, players, card_index)?;
+            }
+
+            let card = self.session.open_card(card_index)?;
+            let card_id = self.decode(&card).map_err(|e| e.with_card(card_index))?;
+            self.board.push(card_id);
+        }
+
+        Ok(())
+    }
+
+    /// Burn a card and open the flop, once the preflop betting round is over
+    pub fn deal_flop<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        players: &[HoldemPlayer<C>],
+    ) -> Result<(), CardProtocolError> {
+        self.check_players(players)?;
+        self.advance_betting(Street::Preflop, Street::Flop)?;
+
+        self.open_board(rng, players, 3)
+    }
+
+    /// Burn a card and open the turn, once the betting round on the flop is over
+    pub fn deal_turn<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        players: &[HoldemPlayer<C>],
+    ) -> Result<(), CardProtocolError> {
+        self.check_players(players)?;
+        self.advance_betting(Street::Flop, Street::Turn)?;
+
+        self.open_board(rng, players, 1)
+    }
+
+    /// Burn a card and open the river, once the betting round on the turn is over
+    pub fn deal_river<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        players: &[HoldemPlayer<C>],
+    ) -> Result<(), CardProtocolError> {
+        self.check_players(players)?;
+        self.advance_betting(Street::Turn, Street::River)?;
+
+        self.open_board(rng, players, 1)
+    }
+
+    /// Play `action` for `player` in the betting round of the current street. A player who folds keeps
+    /// providing reveal tokens for the community cards, but their hole cards are never opened.
+    pub fn act(&mut self, player: usize, action: Action) -> Result<(), CardProtocolError> {
+        if !matches!(
+            self.street,
+            Street::Preflop | Street::Flop | Street::Turn | Street::River
+        ) {
+            return Err(CardProtocolError::WrongProtocolPhase {
+                expected: String::from("preflop, flop, turn or river"),
+                found: String::from(self.street.name()),
+                context: ErrorContext::default(),
+            });
+        }
+
+        self.betting.act(player, action)?;
+        if action == Action::Fold {
+            self.session.fold(player)?;
+        }
+
+        Ok(())
+    }
+
+    /// Players for which `mucked` is false show their hole cards by publishing their own reveal tokens;
+    /// the others, and the players who folded, muck and their cards stay masked. Returns the hole cards
+    /// shown by each player.
+    pub fn showdown<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        players: &[HoldemPlayer<C>],
+        mucked: &[bool],
+    ) -> Result<Vec<Option<Vec<usize>>>, CardProtocolError> {
+        self.check_players(players)?;
+        if mucked.len() != players.len() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!(
+                    "expected {} muck decisions, found {}",
+                    players.len(),
+                    mucked.len()
+                ),
+                context: ErrorContext::default(),
+            });
+        }
+        self.advance_betting(Street::River, Street::Showdown)?;
+        self.session.start_showdown()?;
+
+        let mut shown = Vec::with_capacity(players.len());
+        for (player, &mucked) in mucked.iter().enumerate() {
+            if mucked || self.session.has_folded(player) {
+                shown.push(None);
+                continue;
+            }
+
+            let mut cards = Vec::with_capacity(HOLE_CARDS);
+            for card_index in self.hole_cards[player].clone() {
+                self.reveal(rng, player, players, card_index)?;
+                let card = self.session.open_card(card_index)?;
+                cards.push(
+                    self.decode(&card)
+                        .map_err(|e| e.with_player(player).with_card(card_index))?,
+                );
+            }
+            shown.push(Some(cards));
+        }
+
+        Ok(shown)
+    }
+
+    /// Playing cards of the given card ids
+    fn playing_cards(ids: &[usize]) -> Result<Vec<ClassicPlayingCard>, CardProtocolError> {
+        ids.iter()
+            .map(|id| {
+                ClassicPlayingCard::from_id(*id).ok_or_else(|| {
+                    CardProtocolError::UnknownCardEncoding {
+                        context: ErrorContext::default(),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Hands shown at the showdown, as playing cards
+    fn shown_hands(
+        shown: &[Option<Vec<usize>>],
+    ) -> Result<Vec<Option<Vec<ClassicPlayingCard>>>, CardProtocolError> {
+        shown
+            .iter()
+            .map(|cards| cards.as_deref().map(Self::playing_cards).transpose())
+            .collect()
+    }
+
+    /// Pick the winners among the hands shown at the showdown, as returned by [`showdown`](Self::showdown).
+    pub fn winners(&self, shown: &[Option<Vec<usize>>]) -> Result<Vec<usize>, CardProtocolError> {
+        self.advance_check(Street::Showdown)?;
+
+        let board = Self::playing_cards(&self.board)?;
+        let hands = Self::shown_hands(shown)?;
+        evaluator::winners(&board, &hands)
+    }
+
+    /// Award the pots, either to the last player left once all others folded, or to the best hands
+    /// shown at the showdown, as returned by [`showdown`](Self::showdown). Returns the chips won by each
+    /// player.
+    pub fn settle(
+        &mut self,
+        shown: &[Option<Vec<usize>>],
+    ) -> Result<Vec<Chips>, CardProtocolError> {
+        if self.betting.is_hand_over() {
+            return self.betting.settle(&[]);
+        }
+        self.advance_check(Street::Showdown)?;
+
+        let board = Self::playing_cards(&self.board)?;
+        let hands = Self::shown_hands(shown)?;
+        let ranks = hands
+            .iter()
+            .map(|hand| {
+                hand.as_ref()
+                    .map(|hand| {
+                        let mut cards = board.clone();
+                        cards.extend_from_slice(hand);
+                        evaluator::evaluate(&cards)
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.betting.settle(&ranks)
+    }
+}
@@ -1,17 +1,32 @@
-use crate::error::CardProtocolError;
+use crate::derivation::MasterSeed;
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::witness::Witness;
 
 use ark_ff::{Field, ToBytes};
-use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::io::Read;
 use ark_std::rand::Rng;
-use proof_essentials::error::CryptoError;
 use proof_essentials::homomorphic_encryption::HomomorphicEncryptionScheme;
 use proof_essentials::utils::permutation::Permutation;
 use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
 use std::hash::Hash;
 use std::ops::{Add, Mul};
 
+pub mod betting;
+pub mod derivation;
 pub mod discrete_log_cards;
+#[cfg(feature = "async")]
+pub mod driver;
+#[cfg(feature = "serde")]
+pub mod encoding;
 pub mod error;
+pub mod evaluator;
+pub mod holdem;
+pub mod net;
+pub mod playing_cards;
+#[cfg(any(test, feature = "simulation"))]
+pub mod simulation;
+pub mod witness;
 
 pub trait Mask<Scalar: Field, Enc: HomomorphicEncryptionScheme<Scalar>> {
     fn mask(
@@ -35,10 +50,10 @@ pub trait Reveal<F: Field, Enc: HomomorphicEncryptionScheme<F>> {
     fn reveal(&self, cipher: &Enc::Ciphertext) -> Result<Enc::Plaintext, CardProtocolError>;
 }
 
-/// Mental Poker protocol based on the one described by Barnett and Smart (2003).
-/// The protocol has been modified to make use of the argument of a correct shuffle presented
-/// by Bayer and Groth (2014).
-pub trait BarnettSmartProtocol {
+/// Associated types shared by every part of the card protocol. The protocol operations are split
+/// across several traits that all build on this one, so that a client can depend only on the parts
+/// of the protocol it actually needs (e.g. a spectator only needs the verification traits).
+pub trait CardProtocolTypes {
     // Cryptography
     type Scalar: Field;
     type Parameters;
@@ -69,20 +84,143 @@ pub trait BarnettSmartProtocol {
     type ZKProofRemasking: CanonicalDeserialize + CanonicalSerialize;
     type ZKProofReveal: CanonicalDeserialize + CanonicalSerialize;
     type ZKProofShuffle: CanonicalDeserialize + CanonicalSerialize;
+}
 
+/// Generation of the public parameters of the scheme.
+pub trait ProtocolSetup: CardProtocolTypes {
     /// Randomly produce the scheme parameters
     fn setup<R: Rng>(
         rng: &mut R,
         m: usize,
         n: usize,
     ) -> Result<Self::Parameters, CardProtocolError>;
+}
+
+/// Validation of the group elements carried by protocol objects. Valid points lie on the curve, belong to
+/// the prime-order subgroup and are different from the identity. Objects received from other players
+/// should be deserialized with the `deserialize_*` functions, which reject malformed points.
+pub trait PointValidation: CardProtocolTypes {
+    fn validate_public_key(pk: &Self::PlayerPublicKey) -> Result<(), CardProtocolError>;
+
+    fn validate_card(card: &Self::Card) -> Result<(), CardProtocolError>;
+
+    fn validate_masked_card(masked_card: &Self::MaskedCard) -> Result<(), CardProtocolError>;
+
+    fn validate_reveal_token(reveal_token: &Self::RevealToken) -> Result<(), CardProtocolError>;
+
+    fn deserialize_public_key<R: Read>(
+        reader: R,
+    ) -> Result<Self::PlayerPublicKey, CardProtocolError> {
+        let pk = Self::PlayerPublicKey::deserialize(reader).map_err(invalid_data_to_point)?;
+        Self::validate_public_key(&pk)?;
+
+        Ok(pk)
+    }
+
+    fn deserialize_card<R: Read>(reader: R) -> Result<Self::Card, CardProtocolError> {
+        let card = Self::Card::deserialize(reader).map_err(invalid_data_to_point)?;
+        Self::validate_card(&card)?;
+
+        Ok(card)
+    }
+
+    fn deserialize_masked_card<R: Read>(reader: R) -> Result<Self::MaskedCard, CardProtocolError> {
+        let masked_card = Self::MaskedCard::deserialize(reader).map_err(invalid_data_to_point)?;
+        Self::validate_masked_card(&masked_card)?;
+
+        Ok(masked_card)
+    }
+
+    /// Deserialize a whole deck of masked cards. An invalid card is reported with its index in the deck.
+    fn deserialize_deck<R: Read>(reader: R) -> Result<Vec<Self::MaskedCard>, CardProtocolError> {
+        let deck = Vec::<Self::MaskedCard>::deserialize(reader).map_err(invalid_data_to_point)?;
+        for (i, masked_card) in deck.iter().enumerate() {
+            Self::validate_masked_card(masked_card).map_err(|e| e.with_card(i))?;
+        }
+
+        Ok(deck)
+    }
+
+    fn deserialize_reveal_token<R: Read>(
+        reader: R,
+    ) -> Result<Self::RevealToken, CardProtocolError> {
+        let reveal_token = Self::RevealToken::deserialize(reader).map_err(invalid_data_to_point)?;
+        Self::validate_reveal_token(&reveal_token)?;
+
+        Ok(reveal_token)
+    }
+}
+
+/// Checked deserialization fails with `InvalidData` on points that are not on the curve.
+fn invalid_data_to_point(err: SerializationError) -> CardProtocolError {
+    match err {
+        SerializationError::InvalidData => CardProtocolError::InvalidPoint {
+            context: ErrorContext::default(),
+        },
+        err => err.into(),
+    }
+}
+
+/// Verification of key ownership proofs and aggregation of the players' public keys.
+pub trait KeyOwnershipVerification: CardProtocolTypes {
+    /// Verify a proof od key ownership
+    fn verify_key_ownership<B: ToBytes>(
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        player_public_info: &B,
+        proof: &Self::ZKProofKeyOwnership,
+    ) -> Result<(), CardProtocolError>;
+
+    /// Compute a digest binding together the public keys and public information of all the participants
+    /// of a game. The digest does not depend on the order in which the participants are listed.
+    fn participants_digest<'b, B: ToBytes + 'b>(
+        participants: impl IntoIterator<Item = (&'b Self::PlayerPublicKey, &'b B)>,
+    ) -> Result<Vec<u8>, CardProtocolError>
+    where
+        Self::PlayerPublicKey: 'b;
+
+    /// Verify a proof of key ownership bound to the set of participants summarised by `participants_digest`
+    fn verify_key_ownership_in_game<B: ToBytes>(
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        player_public_info: &B,
+        participants_digest: &[u8],
+        proof: &Self::ZKProofKeyOwnership,
+    ) -> Result<(), CardProtocolError>;
 
+    /// Use all the public keys and zk-proofs to compute a verified aggregate public key.
+    /// At least one player is needed, keys must be valid (non-zero) and unique, players must have
+    /// distinct public information and every proof must be bound to the full set of participants (see
+    /// `prove_key_ownership_in_game`).
+    fn compute_aggregate_key<'b, B: ToBytes + 'b>(
+        pp: &Self::Parameters,
+        player_keys_proof_info: impl IntoIterator<
+            Item = &'b (Self::PlayerPublicKey, Self::ZKProofKeyOwnership, B),
+        >,
+    ) -> Result<Self::AggregatePublicKey, CardProtocolError>
+    where
+        Self::PlayerPublicKey: 'b,
+        Self::ZKProofKeyOwnership: 'b;
+}
+
+/// Player key generation and proofs of key ownership.
+pub trait KeyManagement: KeyOwnershipVerification {
     /// Generate keys for a player.
     fn player_keygen<R: Rng>(
         rng: &mut R,
         pp: &Self::Parameters,
     ) -> Result<(Self::PlayerPublicKey, Self::PlayerSecretKey), CardProtocolError>;
 
+    /// Deterministically derive the keys of a player for the game identified by `game_id`. Deriving twice
+    /// for the same game yields the same keys, while keys derived for different games are unlinkable.
+    /// Derived keys are ordinary player keys: they are proven with `prove_key_ownership_in_game` and
+    /// aggregated with `compute_aggregate_key` like any other key.
+    fn derive_player_keys(
+        pp: &Self::Parameters,
+        master_seed: &MasterSeed,
+        game_id: &[u8],
+    ) -> Result<(Self::PlayerPublicKey, Self::PlayerSecretKey), CardProtocolError>;
+
     /// Prove in zero knowledge that the owner of a public key `pk` knows the corresponding secret key `sk`
     fn prove_key_ownership<B: ToBytes, R: Rng>(
         rng: &mut R,
@@ -90,22 +228,43 @@ pub trait BarnettSmartProtocol {
         pk: &Self::PlayerPublicKey,
         sk: &Self::PlayerSecretKey,
         player_public_info: &B,
-    ) -> Result<Self::ZKProofKeyOwnership, CryptoError>;
+    ) -> Result<Self::ZKProofKeyOwnership, CardProtocolError>;
 
-    /// Verify a proof od key ownership
-    fn verify_key_ownership<B: ToBytes>(
+    /// Prove knowledge of `sk` for a specific game, identified by the digest of all of its participants.
+    /// Such a proof cannot be replayed in a game with a different set of players.
+    fn prove_key_ownership_in_game<B: ToBytes, R: Rng>(
+        rng: &mut R,
         pp: &Self::Parameters,
         pk: &Self::PlayerPublicKey,
+        sk: &Self::PlayerSecretKey,
         player_public_info: &B,
-        proof: &Self::ZKProofKeyOwnership,
-    ) -> Result<(), CryptoError>;
+        participants_digest: &[u8],
+    ) -> Result<Self::ZKProofKeyOwnership, CardProtocolError>;
+}
+
+/// Verification of masking and remasking proofs.
+pub trait MaskingVerification: CardProtocolTypes {
+    /// Verify a proof of masking
+    fn verify_mask(
+        pp: &Self::Parameters,
+        shared_key: &Self::AggregatePublicKey,
+        card: &Self::Card,
+        masked_card: &Self::MaskedCard,
+        proof: &Self::ZKProofMasking,
+    ) -> Result<(), CardProtocolError>;
 
-    /// Use all the public keys and zk-proofs to compute a verified aggregate public key
-    fn compute_aggregate_key<B: ToBytes>(
+    /// Verify a proof of remasking
+    fn verify_remask(
         pp: &Self::Parameters,
-        player_keys_proof_info: &Vec<(Self::PlayerPublicKey, Self::ZKProofKeyOwnership, B)>,
-    ) -> Result<Self::AggregatePublicKey, CardProtocolError>;
+        shared_key: &Self::AggregatePublicKey,
+        original_masked: &Self::MaskedCard,
+        remasked: &Self::MaskedCard,
+        proof: &Self::ZKProofRemasking,
+    ) -> Result<(), CardProtocolError>;
+}
 
+/// Masking and remasking of cards under the aggregate key.
+pub trait CardMasking: MaskingVerification {
     /// Use the shared public key and a (private) random scalar `alpha` to mask a card.
     /// Returns a masked card and a zk-proof that the masking operation was applied correctly.
     fn mask<R: Rng>(
@@ -113,18 +272,9 @@ pub trait BarnettSmartProtocol {
         pp: &Self::Parameters,
         shared_key: &Self::AggregatePublicKey,
         original_card: &Self::Card,
-        alpha: &Self::Scalar,
+        alpha: &Witness<Self::Scalar>,
     ) -> Result<(Self::MaskedCard, Self::ZKProofMasking), CardProtocolError>;
 
-    /// Verify a proof of masking
-    fn verify_mask(
-        pp: &Self::Parameters,
-        shared_key: &Self::AggregatePublicKey,
-        card: &Self::Card,
-        masked_card: &Self::MaskedCard,
-        proof: &Self::ZKProofMasking,
-    ) -> Result<(), CryptoError>;
-
     /// Use the shared public key and a (private) random scalar `alpha` to remask a masked card.
     /// Returns a masked card and a zk-proof that the remasking operation was applied correctly.
     fn remask<R: Rng>(
@@ -132,18 +282,42 @@ pub trait BarnettSmartProtocol {
         pp: &Self::Parameters,
         shared_key: &Self::AggregatePublicKey,
         original_masked: &Self::MaskedCard,
-        alpha: &Self::Scalar,
+        alpha: &Witness<Self::Scalar>,
     ) -> Result<(Self::MaskedCard, Self::ZKProofRemasking), CardProtocolError>;
+}
 
-    /// Verify a proof of remasking
-    fn verify_remask(
+/// Verification of reveal tokens and unmasking of cards from verified tokens.
+pub trait RevealVerification: CardProtocolTypes {
+    /// Verify a proof of correctly computed reveal token
+    fn verify_reveal(
         pp: &Self::Parameters,
-        shared_key: &Self::AggregatePublicKey,
-        original_masked: &Self::MaskedCard,
-        remasked: &Self::MaskedCard,
-        proof: &Self::ZKProofRemasking,
-    ) -> Result<(), CryptoError>;
+        pk: &Self::PlayerPublicKey,
+        reveal_token: &Self::RevealToken,
+        masked_card: &Self::MaskedCard,
+        proof: &Self::ZKProofReveal,
+    ) -> Result<(), CardProtocolError>;
 
+    /// After collecting all the necessary reveal tokens and proofs that these are correctly issued,
+    /// players can unmask a masked card to recover the underlying card.
+    fn unmask<'b>(
+        pp: &Self::Parameters,
+        decryption_key: impl IntoIterator<
+            Item = &'b (
+                Self::RevealToken,
+                Self::ZKProofReveal,
+                Self::PlayerPublicKey,
+            ),
+        >,
+        masked_card: &Self::MaskedCard,
+    ) -> Result<Self::Card, CardProtocolError>
+    where
+        Self::RevealToken: 'b,
+        Self::ZKProofReveal: 'b,
+        Self::PlayerPublicKey: 'b;
+}
+
+/// Computation of reveal tokens by a player holding a secret key.
+pub trait CardReveal: RevealVerification {
     /// Players can use this function to compute their reveal token for a given masked card.
     /// The token is accompanied by a proof that it is a valid reveal for the specified card issued
     /// by the player who ran the computation.
@@ -154,45 +328,118 @@ pub trait BarnettSmartProtocol {
         pk: &Self::PlayerPublicKey,
         masked_card: &Self::MaskedCard,
     ) -> Result<(Self::RevealToken, Self::ZKProofReveal), CardProtocolError>;
+}
 
-    /// Verify a proof of correctly computed reveal token
-    fn verify_reveal(
-        pp: &Self::Parameters,
-        pk: &Self::PlayerPublicKey,
-        reveal_token: &Self::RevealToken,
-        masked_card: &Self::MaskedCard,
-        proof: &Self::ZKProofReveal,
-    ) -> Result<(), CryptoError>;
-
-    /// After collecting all the necessary reveal tokens and proofs that these are correctly issued,
-    /// players can unmask a masked card to recover the underlying card.
-    fn unmask(
+/// Verification of shuffle arguments.
+pub trait ShuffleVerification: CardProtocolTypes {
+    /// Verify a proof of correct shuffle
+    fn verify_shuffle(
         pp: &Self::Parameters,
-        decryption_key: &Vec<(
-            Self::RevealToken,
-            Self::ZKProofReveal,
-            Self::PlayerPublicKey,
-        )>,
-        masked_card: &Self::MaskedCard,
-    ) -> Result<Self::Card, CardProtocolError>;
+        shared_key: &Self::AggregatePublicKey,
+        original_deck: &[Self::MaskedCard],
+        shuffled_deck: &[Self::MaskedCard],
+        proof: &Self::ZKProofShuffle,
+    ) -> Result<(), CardProtocolError>;
+}
 
+/// Shuffling and remasking of a whole deck.
+pub trait DeckShuffle: ShuffleVerification {
     /// Shuffle and remask a deck of masked cards using a player-chosen permutation and vector of
     /// masking factors.
     fn shuffle_and_remask<R: Rng>(
         rng: &mut R,
         pp: &Self::Parameters,
         shared_key: &Self::AggregatePublicKey,
-        deck: &Vec<Self::MaskedCard>,
-        masking_factors: &Vec<Self::Scalar>,
+        deck: &[Self::MaskedCard],
+        masking_factors: &Witness<Vec<Self::Scalar>>,
         permutation: &Permutation,
     ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofShuffle), CardProtocolError>;
+}
 
-    /// Verify a proof of correct shuffle
-    fn verify_shuffle(
+/// Verification of key switches, which move a masked deck to another aggregate key without changing
+/// its cards. Key switching is an extension of the protocol: it is not part of `ProtocolVerification`
+/// nor of `BarnettSmartProtocol`, and implementations opt in by implementing this trait and
+/// `KeySwitching`.
+pub trait KeySwitchVerification: CardProtocolTypes {
+    type ZKProofKeySwitch: CanonicalDeserialize + CanonicalSerialize;
+
+    /// Verify that `switched_deck` holds the cards of `deck`, masked under the aggregate key of `deck`
+    /// extended with `pk`
+    fn verify_key_addition(
         pp: &Self::Parameters,
-        shared_key: &Self::AggregatePublicKey,
-        original_deck: &Vec<Self::MaskedCard>,
-        shuffled_deck: &Vec<Self::MaskedCard>,
-        proof: &Self::ZKProofShuffle,
-    ) -> Result<(), CryptoError>;
+        pk: &Self::PlayerPublicKey,
+        deck: &[Self::MaskedCard],
+        switched_deck: &[Self::MaskedCard],
+        proof: &Self::ZKProofKeySwitch,
+    ) -> Result<(), CardProtocolError>;
+
+    /// Verify that `switched_deck` holds the cards of `deck`, masked under the aggregate key of `deck`
+    /// without `pk`
+    fn verify_key_removal(
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        deck: &[Self::MaskedCard],
+        switched_deck: &[Self::MaskedCard],
+        proof: &Self::ZKProofKeySwitch,
+    ) -> Result<(), CardProtocolError>;
+}
+
+/// Key switches of a masked deck, for players joining or leaving a game in progress.
+pub trait KeySwitching: KeySwitchVerification {
+    /// A player joining the game uses this function to add their key to the aggregate key under which
+    /// `deck` is masked. The cards are unchanged and can then only be opened with the joining player's
+    /// reveal tokens. Returns the switched deck and a single proof for the whole deck.
+    fn add_key_to_deck<R: Rng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        sk: &Self::PlayerSecretKey,
+        deck: &[Self::MaskedCard],
+    ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofKeySwitch), CardProtocolError>;
+
+    /// A player leaving the game uses this function to remove their key from the aggregate key under
+    /// which `deck` is masked, by applying their partial decryption to every card. The cards are
+    /// unchanged and can then be opened without the leaving player. Returns the switched deck and a
+    /// single proof for the whole deck.
+    fn remove_key_from_deck<R: Rng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        pk: &Self::PlayerPublicKey,
+        sk: &Self::PlayerSecretKey,
+        deck: &[Self::MaskedCard],
+    ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofKeySwitch), CardProtocolError>;
+}
+
+/// Everything needed to only check the work of other players, without access to any secret key.
+pub trait ProtocolVerification:
+    PointValidation
+    + KeyOwnershipVerification
+    + MaskingVerification
+    + RevealVerification
+    + ShuffleVerification
+{
+}
+
+impl<T> ProtocolVerification for T where
+    T: PointValidation
+        + KeyOwnershipVerification
+        + MaskingVerification
+        + RevealVerification
+        + ShuffleVerification
+{
+}
+
+/// Mental Poker protocol based on the one described by Barnett and Smart (2003).
+/// The protocol has been modified to make use of the argument of a correct shuffle presented
+/// by Bayer and Groth (2014).
+///
+/// This is an umbrella trait which is implemented for any type implementing all parts of the protocol.
+pub trait BarnettSmartProtocol:
+    ProtocolSetup + PointValidation + KeyManagement + CardMasking + CardReveal + DeckShuffle
+{
+}
+
+impl<T> BarnettSmartProtocol for T where
+    T: ProtocolSetup + PointValidation + KeyManagement + CardMasking + CardReveal + DeckShuffle
+{
 }
@@ -0,0 +1,369 @@
+//! Reference transport for players running in separate processes, over TCP.
+//!
+//! A [`Relay`] acts as a bulletin board: it appends every frame posted by a client to a log and forwards
+//! it to all connected clients, its sender included, so that every client sees the same frames in the
+//! same order. Clients connecting late first receive the whole log. Players post and read frames with a
+//! [`RelayClient`]; the relay never looks inside them, players are expected to exchange signed protocol
+//! messages (see [`MessageContext::sign`](crate::discrete_log_cards::message::MessageContext::sign)).
+//!
+//! Every client is written to by its own thread, from a bounded queue: a client falling too far behind
+//! is disconnected instead of holding up the others. The log is bounded as well, a client posting a
+//! frame that does not fit in it any more is disconnected and the frame is dropped.
+//!
+//! Frames are length-delimited: a 4-byte little-endian length, followed by that many bytes.
+
+use crate::error::CardProtocolError;
+
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Largest frame accepted, in bytes
+pub const MAX_FRAME_LEN: usize = 1 << 24;
+
+/// Default bound on the total size of the frames logged by a relay, in bytes
+pub const MAX_LOG_LEN: usize = 1 << 28;
+
+/// Number of frames a client may fall behind before the relay disconnects it
+pub const CLIENT_QUEUE_LEN: usize = 64;
+
+/// Write a length-delimited frame
+pub fn write_frame<W: Write>(writer: &mut W, frame: &[u8]) -> Result<(), CardProtocolError> {
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(CardProtocolError::InvalidMessage(format!(
+            "frame of {} bytes exceeds the maximum of {}",
+            frame.len(),
+            MAX_FRAME_LEN
+        )));
+    }
+
+    writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Read a length-delimited frame. Returns `None` if the stream ends before a new frame starts.
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, CardProtocolError> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(CardProtocolError::InvalidMessage(format!(
+            "frame of {} bytes exceeds the maximum of {}",
+            len, MAX_FRAME_LEN
+        )));
+    }
+
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame)?;
+
+    Ok(Some(frame))
+}
+
+type Frame = Arc<Vec<u8>>;
+
+/// A connected client and the queue of the frames to write to it
+struct Client {
+    id: u64,
+    queue: SyncSender<Frame>,
+    stream: TcpStream,
+}
+
+/// Frames posted so far and the clients to forward new ones to
+struct Board {
+    log: Vec<Frame>,
+    /// Total size of the logged frames
+    log_len: usize,
+    max_log_len: usize,
+    next_client: u64,
+    clients: Vec<Client>,
+}
+
+/// Bulletin board forwarding the frames posted by its clients.
+pub struct Relay {
+    listener: TcpListener,
+    board: Arc<Mutex<Board>>,
+}
+
+impl Relay {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, CardProtocolError> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            board: Arc::new(Mutex::new(Board {
+                log: Vec::new(),
+                log_len: 0,
+                max_log_len: MAX_LOG_LEN,
+                next_client: 0,
+                clients: Vec::new(),
+            })),
+        })
+    }
+
+    /// Bound the total size of the logged frames to `max_log_len` bytes instead of [`MAX_LOG_LEN`]
+    pub fn with_max_log_len(self, max_log_len: usize) -> Self {
+        self.board
+            .lock()
+            .expect("relay board lock poisoned")
+            .max_log_len = max_log_len;
+        self
+    }
+
+    /// Address the relay listens on, e.g. to find the port picked by the system when binding to port 0
+    pub fn local_addr(&self) -> Result<SocketAddr, CardProtocolError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve clients, each on its own thread, until accepting a connection fails
+    pub fn run(&self) -> Result<(), CardProtocolError> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let board = Arc::clone(&self.board);
+
+            // A failing client only loses its own connection
+            thread::spawn(move || serve(&board, stream));
+        }
+
+        Ok(())
+    }
+}
+
+fn serve(board: &Mutex<Board>, stream: TcpStream) -> Result<(), CardProtocolError> {
+    stream.set_nodelay(true)?;
+    let reader = BufReader::new(stream.try_clone()?);
+
+    // The client is sent the log and then every new frame, registering it under the lock ensures that
+    // it misses none
+    let (queue, frames) = sync_channel(CLIENT_QUEUE_LEN);
+    let client_stream = stream.try_clone()?;
+    let (id, backlog) = {
+        let mut board = board.lock().expect("relay board lock poisoned");
+        let id = board.next_client;
+        board.next_client += 1;
+        board.clients.push(Client {
+            id,
+            queue,
+            stream: client_stream,
+        });
+        (id, board.log.clone())
+    };
+    thread::spawn(move || forward(stream, backlog, frames));
+
+    let result = receive(board, reader);
+
+    // Closing the queue stops the writer thread, which closes the connection
+    board
+        .lock()
+        .expect("relay board lock poisoned")
+        .clients
+        .retain(|client| client.id != id);
+
+    result
+}
+
+/// Log and forward the frames posted by a client
+fn receive(
+    board: &Mutex<Board>,
+    mut reader: BufReader<TcpStream>,
+) -> Result<(), CardProtocolError> {
+    while let Some(frame) = read_frame(&mut reader)? {
+        let frame = Arc::new(frame);
+
+        // Frames are logged and queued under the lock, which orders them for all clients. Queuing never
+        // blocks: clients whose queue is full or closed are disconnected, which also unblocks their
+        // writer thread.
+        let mut board = board.lock().expect("relay board lock poisoned");
+        if board.log_len + frame.len() > board.max_log_len {
+            return Err(CardProtocolError::InvalidMessage(format!(
+                "the relay log is full ({} bytes)",
+                board.max_log_len
+            )));
+        }
+        board.clients.retain(|client| {
+            let queued = client.queue.try_send(Arc::clone(&frame)).is_ok();
+            if !queued {
+                let _ = client.stream.shutdown(Shutdown::Both);
+            }
+            queued
+        });
+        board.log_len += frame.len();
+        board.log.push(frame);
+    }
+
+    Ok(())
+}
+
+/// Write the log, then the queued frames, to a client until its queue is closed or writing fails
+fn forward(stream: TcpStream, backlog: Vec<Frame>, frames: Receiver<Frame>) {
+    let mut writer = &stream;
+    let _ = backlog
+        .into_iter()
+        .chain(frames)
+        .try_for_each(|frame| write_frame(&mut writer, &frame));
+
+    // A client dropped for falling behind has missed frames and must not go on reading
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Connection of a player to a [`Relay`].
+pub struct RelayClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl RelayClient {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self, CardProtocolError> {
+        let writer = TcpStream::connect(addr)?;
+        writer.set_nodelay(true)?;
+
+        Ok(Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        })
+    }
+
+    /// Post a frame on the board
+    pub fn send(&mut self, frame: &[u8]) -> Result<(), CardProtocolError> {
+        write_frame(&mut self.writer, frame)
+    }
+
+    /// Wait for the next frame of the board
+    pub fn recv(&mut self) -> Result<Vec<u8>, CardProtocolError> {
+        read_frame(&mut self.reader)?.ok_or_else(|| {
+            CardProtocolError::IoError(String::from("connection closed by the relay"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_frame, write_frame, Relay, RelayClient, CLIENT_QUEUE_LEN, MAX_FRAME_LEN};
+    use crate::error::CardProtocolError;
+
+    use std::io::BufReader;
+    use std::net::TcpStream;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn framing() {
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, b"first").unwrap();
+        write_frame(&mut bytes, b"").unwrap();
+        write_frame(&mut bytes, b"third").unwrap();
+
+        let mut reader = &bytes[..];
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(b"first".to_vec()));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(Vec::new()));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(b"third".to_vec()));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+
+        // Truncated frame
+        let mut reader = &bytes[..bytes.len() - 1];
+        read_frame(&mut reader).unwrap();
+        read_frame(&mut reader).unwrap();
+        assert!(read_frame(&mut reader).is_err());
+
+        // Oversized frame
+        let oversized = ((MAX_FRAME_LEN + 1) as u32).to_le_bytes();
+        assert!(matches!(
+            read_frame(&mut &oversized[..]),
+            Err(CardProtocolError::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn relay_orders_frames_for_all_clients() {
+        let relay = Relay::bind("127.0.0.1:0").unwrap();
+        let addr = relay.local_addr().unwrap();
+        thread::spawn(move || relay.run());
+
+        let mut first = RelayClient::connect(addr).unwrap();
+        first.send(b"one").unwrap();
+        assert_eq!(first.recv().unwrap(), b"one");
+
+        // A late client receives the log first
+        let mut second = RelayClient::connect(addr).unwrap();
+        assert_eq!(second.recv().unwrap(), b"one");
+
+        second.send(b"two").unwrap();
+        first.send(b"three").unwrap();
+        let frames: Vec<Vec<u8>> = (0..2).map(|_| first.recv().unwrap()).collect();
+        assert_eq!(
+            (0..2).map(|_| second.recv().unwrap()).collect::<Vec<_>>(),
+            frames
+        );
+    }
+
+    #[test]
+    fn stalled_client_does_not_hold_up_the_relay() {
+        let relay = Relay::bind("127.0.0.1:0").unwrap();
+        let addr = relay.local_addr().unwrap();
+        thread::spawn(move || relay.run());
+
+        // This client never reads what the relay sends
+        let stalled = TcpStream::connect(addr).unwrap();
+
+        // Enough data to fill the socket buffers and the queue of the stalled client
+        let num_frames = 8 * CLIENT_QUEUE_LEN;
+        let frame = vec![0x5a; 1 << 16];
+        let (done, finished) = channel();
+        let expected = frame.clone();
+        thread::spawn(move || {
+            let mut client = RelayClient::connect(addr).unwrap();
+            for _ in 0..num_frames {
+                client.send(&expected).unwrap();
+                assert_eq!(client.recv().unwrap(), expected);
+            }
+            done.send(()).unwrap();
+        });
+        finished
+            .recv_timeout(Duration::from_secs(60))
+            .expect("the relay stalled");
+
+        // The stalled client was disconnected after missing frames
+        stalled
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut reader = BufReader::new(stalled);
+        let mut received = 0;
+        while let Ok(Some(received_frame)) = read_frame(&mut reader) {
+            assert_eq!(received_frame, frame);
+            received += 1;
+        }
+        assert!(received < num_frames);
+    }
+
+    #[test]
+    fn bounded_log() {
+        let relay = Relay::bind("127.0.0.1:0").unwrap().with_max_log_len(8);
+        let addr = relay.local_addr().unwrap();
+        thread::spawn(move || relay.run());
+
+        let mut first = RelayClient::connect(addr).unwrap();
+        first.send(b"12345").unwrap();
+        assert_eq!(first.recv().unwrap(), b"12345");
+
+        // A frame overflowing the log is dropped and its sender disconnected
+        let mut second = RelayClient::connect(addr).unwrap();
+        assert_eq!(second.recv().unwrap(), b"12345");
+        second.send(b"6789").unwrap();
+        assert!(second.recv().is_err());
+
+        first.send(b"678").unwrap();
+        assert_eq!(first.recv().unwrap(), b"678");
+
+        let mut late = RelayClient::connect(addr).unwrap();
+        assert_eq!(late.recv().unwrap(), b"12345");
+        assert_eq!(late.recv().unwrap(), b"678");
+    }
+}
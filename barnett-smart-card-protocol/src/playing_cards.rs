@@ -0,0 +1,143 @@
+//! Classic 52-card deck, used to give a meaning to unmasked cards.
+//!
+//! Card ids follow the order of [`ClassicPlayingCard::deck`]: values in increasing order, and the four
+//! suites of each value in the order of [`Suite::VALUES`].
+
+use std::fmt;
+
+#[derive(PartialEq, Clone, Copy, Eq, Debug, Hash)]
+pub enum Suite {
+    Club,
+    Diamond,
+    Heart,
+    Spade,
+}
+
+impl Suite {
+    pub const VALUES: [Self; 4] = [Self::Club, Self::Diamond, Self::Heart, Self::Spade];
+}
+
+#[derive(PartialEq, PartialOrd, Ord, Clone, Copy, Eq, Debug, Hash)]
+pub enum Value {
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+impl Value {
+    pub const VALUES: [Self; 13] = [
+        Self::Two,
+        Self::Three,
+        Self::Four,
+        Self::Five,
+        Self::Six,
+        Self::Seven,
+        Self::Eight,
+        Self::Nine,
+        Self::Ten,
+        Self::Jack,
+        Self::Queen,
+        Self::King,
+        Self::Ace,
+    ];
+}
+
+#[derive(PartialEq, Clone, Eq, Copy, Hash)]
+pub struct ClassicPlayingCard {
+    value: Value,
+    suite: Suite,
+}
+
+impl ClassicPlayingCard {
+    pub fn new(value: Value, suite: Suite) -> Self {
+        Self { value, suite }
+    }
+
+    pub fn value(&self) -> Value {
+        self.value
+    }
+
+    pub fn suite(&self) -> Suite {
+        self.suite
+    }
+
+    /// All 52 cards, indexed by card id
+    pub fn deck() -> Vec<Self> {
+        Value::VALUES
+            .iter()
+            .flat_map(|&value| {
+                Suite::VALUES
+                    .iter()
+                    .map(move |&suite| Self::new(value, suite))
+            })
+            .collect()
+    }
+
+    /// Card with the given id, if it is smaller than 52
+    pub fn from_id(id: usize) -> Option<Self> {
+        let value = *Value::VALUES.get(id / Suite::VALUES.len())?;
+        let suite = Suite::VALUES[id % Suite::VALUES.len()];
+
+        Some(Self::new(value, suite))
+    }
+
+    pub fn id(&self) -> usize {
+        self.value as usize * Suite::VALUES.len() + self.suite as usize
+    }
+}
+
+impl fmt::Debug for ClassicPlayingCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let suite = match self.suite {
+            Suite::Club => "♣",
+            Suite::Diamond => "♦",
+            Suite::Heart => "♥",
+            Suite::Spade => "♠",
+        };
+
+        let val = match self.value {
+            Value::Two => "2",
+            Value::Three => "3",
+            Value::Four => "4",
+            Value::Five => "5",
+            Value::Six => "6",
+            Value::Seven => "7",
+            Value::Eight => "8",
+            Value::Nine => "9",
+            Value::Ten => "10",
+            Value::Jack => "J",
+            Value::Queen => "Q",
+            Value::King => "K",
+            Value::Ace => "A",
+        };
+
+        write!(f, "{}{}", val, suite)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClassicPlayingCard;
+
+    #[test]
+    fn card_ids() {
+        let deck = ClassicPlayingCard::deck();
+        assert_eq!(deck.len(), 52);
+
+        for (id, card) in deck.iter().enumerate() {
+            assert_eq!(card.id(), id);
+            assert_eq!(ClassicPlayingCard::from_id(id), Some(*card));
+        }
+        assert_eq!(ClassicPlayingCard::from_id(52), None);
+    }
+}
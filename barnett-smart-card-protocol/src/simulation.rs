@@ -0,0 +1,739 @@
+//! In-process simulation of a game between players who only talk to each other through messages.
+//!
+//! Every [`Player`] keeps its own [`GameSession`] and receives the signed messages of the others on an
+//! in-memory [`Bus`]. A [`Simulation`] drives the players through registration, the masking of the
+//! initial deck by the dealer, the shuffle rounds and the opening of cards in public, and stops at the
+//! first message rejected by a player or by the referee listening to all the traffic on the bus.
+//!
+//! Players can be given a scripted [`Behaviour`], so that tests can check that every misbehaviour is
+//! detected and attributed to the player responsible for it. This test harness is only built for the
+//! tests of the crate or with the `simulation` feature.
+
+use crate::discrete_log_cards::message::{Message, MessageContext, DEALER};
+use crate::discrete_log_cards::{
+    Card, DLCards, GameSession, MaskedCard, MaskingProof, Parameters, PlayerSecretKey, PublicKey,
+    SessionPhase, ShuffleProof,
+};
+use crate::error::{CardProtocolError, ErrorContext};
+use crate::witness::Witness;
+use crate::{
+    CardMasking, CardReveal, DeckShuffle, KeyManagement, KeyOwnershipVerification, ProtocolSetup,
+};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::One;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::Rng;
+use proof_essentials::utils::permutation::Permutation;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Conduct of a simulated player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behaviour {
+    /// Follow the protocol
+    Honest,
+    /// Swap two cards of the shuffled deck, which no longer matches the shuffle proof
+    BadShuffle,
+    /// Provide for every card the reveal token of the next card of the deck
+    WrongRevealToken,
+    /// Never provide any reveal token
+    Withholding,
+    /// Send a different shuffle to some of the other players. Needs at least three players.
+    Equivocation,
+}
+
+/// Misbehaviour detected during a simulation.
+#[derive(Debug, PartialEq)]
+pub struct Detection {
+    /// Player who rejected the message, or `None` for the referee
+    pub detected_by: Option<usize>,
+    /// Player the misbehaviour is attributed to, if it could be
+    pub culprit: Option<usize>,
+    pub error: CardProtocolError,
+}
+
+/// In-memory broadcast medium between the players. Every delivery is also copied to an observer, which
+/// sees all the traffic of the game.
+pub struct Bus {
+    inboxes: Vec<Sender<Vec<u8>>>,
+    observer: Sender<Vec<u8>>,
+}
+
+impl Bus {
+    /// Bus between `num_players` players. Returns the inbox of each player and the feed of the observer.
+    pub fn new(num_players: usize) -> (Self, Vec<Receiver<Vec<u8>>>, Receiver<Vec<u8>>) {
+        let (inboxes, receivers) = (0..num_players).map(|_| channel()).unzip();
+        let (observer, feed) = channel();
+
+        (Self { inboxes, observer }, receivers, feed)
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.inboxes.len()
+    }
+
+    /// Deliver `bytes` to the player at index `to`
+    pub fn send(&self, to: usize, bytes: &[u8]) -> Result<(), CardProtocolError> {
+        let inbox = self
+            .inboxes
+            .get(to)
+            .ok_or_else(|| CardProtocolError::ParameterMismatch {
+                reason: format!("no player {} on the bus", to),
+                context: ErrorContext::default(),
+            })?;
+
+        // A closed channel only means that nobody is listening anymore, which the sender cannot help
+        let _ = inbox.send(bytes.to_vec());
+        let _ = self.observer.send(bytes.to_vec());
+
+        Ok(())
+    }
+
+    /// Deliver `bytes` to every player, including its sender
+    pub fn broadcast(&self, bytes: &[u8]) -> Result<(), CardProtocolError> {
+        (0..self.num_players()).try_for_each(|to| self.send(to, bytes))
+    }
+}
+
+/// A simulated player, holding their secret key and their own view of the game.
+pub struct Player<C: ProjectiveCurve> {
+    index: usize,
+    behaviour: Behaviour,
+    name: Vec<u8>,
+    pk: PublicKey<C>,
+    sk: PlayerSecretKey<C>,
+    /// Keys of all the players in turn order, as announced before the game
+    keys: Vec<PublicKey<C>>,
+    context: MessageContext,
+    session: GameSession<C>,
+    inbox: Receiver<Vec<u8>>,
+    /// Masked cards received from the dealer so far
+    initial_deck: Vec<(Card<C>, MaskedCard<C>, MaskingProof<C>)>,
+}
+
+impl<C: ProjectiveCurve> Player<C> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn behaviour(&self) -> Behaviour {
+        self.behaviour
+    }
+
+    pub fn pk(&self) -> &PublicKey<C> {
+        &self.pk
+    }
+
+    /// The game as seen by this player
+    pub fn session(&self) -> &GameSession<C> {
+        &self.session
+    }
+
+    fn sign<R: Rng>(
+        &self,
+        rng: &mut R,
+        message: &Message<C>,
+    ) -> Result<Vec<u8>, CardProtocolError> {
+        self.context.sign(
+            rng,
+            self.session.parameters(),
+            self.index as u32,
+            message,
+            &self.pk,
+            &self.sk,
+        )
+    }
+
+    fn shared_key(&self) -> Result<PublicKey<C>, CardProtocolError> {
+        self.session
+            .shared_key()
+            .copied()
+            .ok_or_else(|| CardProtocolError::WrongProtocolPhase {
+                expected: String::from("key aggregation"),
+                found: String::from(self.session.phase().name()),
+                context: ErrorContext::player(self.index),
+            })
+    }
+
+    fn announce_key<R: Rng>(
+        &self,
+        rng: &mut R,
+        bus: &Bus,
+        participants_digest: &[u8],
+    ) -> Result<(), CardProtocolError> {
+        let proof = DLCards::<C>::prove_key_ownership_in_game(
+            rng,
+            self.session.parameters(),
+            &self.pk,
+            &self.sk,
+            &self.name,
+            participants_digest,
+        )?;
+        let message = Message::KeyOwnership {
+            pk: self.pk,
+            proof,
+            player_public_info: self.name.clone(),
+        };
+
+        bus.broadcast(&self.sign(rng, &message)?)
+    }
+
+    fn mask_deck<R: Rng>(
+        &self,
+        rng: &mut R,
+        bus: &Bus,
+        encoding: &[Card<C>],
+    ) -> Result<(), CardProtocolError> {
+        let shared_key = self.shared_key()?;
+
+        for card in encoding {
+            let (masked_card, proof) = DLCards::<C>::mask(
+                rng,
+                self.session.parameters(),
+                &shared_key,
+                card,
+                &Witness::new(C::ScalarField::one()),
+            )?;
+            let message = Message::Mask {
+                card: *card,
+                masked_card,
+                proof,
+            };
+            bus.broadcast(&self.sign(rng, &message)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn shuffled<R: Rng>(
+        &self,
+        rng: &mut R,
+        shared_key: &PublicKey<C>,
+    ) -> Result<(Vec<MaskedCard<C>>, ShuffleProof<C>), CardProtocolError> {
+        let deck = self.session.deck();
+        let permutation = Permutation::new(rng, deck.len());
+        let masking_factors: Witness<Vec<C::ScalarField>> = Witness::sample(rng, deck.len());
+
+        DLCards::<C>::shuffle_and_remask(
+            rng,
+            self.session.parameters(),
+            shared_key,
+            deck,
+            &masking_factors,
+            &permutation,
+        )
+    }
+
+    fn shuffle<R: Rng>(&self, rng: &mut R, bus: &Bus) -> Result<(), CardProtocolError> {
+        let shared_key = self.shared_key()?;
+        let (mut deck, proof) = self.shuffled(rng, &shared_key)?;
+
+        match self.behaviour {
+            Behaviour::BadShuffle => {
+                deck.swap(0, 1);
+                bus.broadcast(&self.sign(rng, &Message::Shuffle { deck, proof })?)
+            }
+            Behaviour::Equivocation => {
+                let (other_deck, other_proof) = self.shuffled(rng, &shared_key)?;
+                let first = self.sign(rng, &Message::Shuffle { deck, proof })?;
+                let second = self.sign(
+                    rng,
+                    &Message::Shuffle {
+                        deck: other_deck,
+                        proof: other_proof,
+                    },
+                )?;
+
+                // Half of the other players get the second shuffle, everybody else the first one
+                let num_players = bus.num_players();
+                let deceived: Vec<usize> = (0..num_players)
+                    .filter(|&player| player != self.index)
+                    .take((num_players - 1) / 2)
+                    .collect();
+                (0..num_players).try_for_each(|to| {
+                    let bytes = if deceived.contains(&to) {
+                        &second
+                    } else {
+                        &first
+                    };
+                    bus.send(to, bytes)
+                })
+            }
+            _ => bus.broadcast(&self.sign(rng, &Message::Shuffle { deck, proof })?),
+        }
+    }
+
+    fn reveal<R: Rng>(
+        &self,
+        rng: &mut R,
+        bus: &Bus,
+        card_index: usize,
+    ) -> Result<(), CardProtocolError> {
+        let deck = self.session.deck();
+        let target = match self.behaviour {
+            Behaviour::Withholding => return Ok(()),
+            Behaviour::WrongRevealToken => (card_index + 1) % deck.len(),
+            _ => card_index,
+        };
+
+        let (token, proof) = DLCards::<C>::compute_reveal_token(
+            rng,
+            self.session.parameters(),
+            &self.sk,
+            &self.pk,
+            &deck[target],
+        )?;
+        let message = Message::Reveal {
+            card_index: card_index as u64,
+            token,
+            proof,
+        };
+
+        bus.broadcast(&self.sign(rng, &message)?)
+    }
+
+    /// Handle every message waiting in the inbox
+    fn process(&mut self) -> Result<(), Detection> {
+        while let Ok(bytes) = self.inbox.try_recv() {
+            self.receive(&bytes).map_err(|(culprit, error)| Detection {
+                detected_by: Some(self.index),
+                culprit,
+                error,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Check a message and apply it to the session. Failures are returned with the player they are
+    /// attributed to.
+    fn receive(&mut self, bytes: &[u8]) -> Result<(), (Option<usize>, CardProtocolError)> {
+        let (header, message) = self
+            .context
+            .open(self.session.parameters(), bytes, &self.keys)
+            .map_err(|e| (e.context().and_then(|context| context.player), e))?;
+        let sender = header.sender as usize;
+
+        self.apply(sender, message).map_err(|e| {
+            let culprit = e.context().and_then(|context| context.player);
+            (culprit.or(Some(sender)), e)
+        })
+    }
+
+    fn apply(&mut self, sender: usize, message: Message<C>) -> Result<(), CardProtocolError> {
+        let out_of_turn = |expected| CardProtocolError::OutOfTurn {
+            expected,
+            context: ErrorContext::player(sender),
+        };
+
+        match message {
+            Message::KeyOwnership {
+                pk,
+                proof,
+                player_public_info,
+            } => {
+                if sender != self.session.num_players() {
+                    return Err(out_of_turn(self.session.num_players()));
+                }
+                if pk != self.keys[sender] {
+                    return Err(CardProtocolError::ParameterMismatch {
+                        reason: String::from("key differs from the one announced before the game"),
+                        context: ErrorContext::player(sender),
+                    });
+                }
+
+                self.session
+                    .register_player(pk, proof, player_public_info)?;
+                if self.session.num_players() == self.keys.len() {
+                    self.session.aggregate_keys()?;
+                }
+            }
+            Message::Mask {
+                card,
+                masked_card,
+                proof,
+            } => {
+                if sender != DEALER {
+                    return Err(out_of_turn(DEALER));
+                }
+
+                self.initial_deck.push((card, masked_card, proof));
+                if self.initial_deck.len() == self.session.parameters().deck_size() {
+                    let initial_deck = std::mem::take(&mut self.initial_deck);
+                    self.session.submit_initial_deck(&initial_deck)?;
+                }
+            }
+            Message::Shuffle { deck, proof } => {
+                self.session.submit_shuffle(sender, deck, &proof)?;
+                if self.session.phase() == SessionPhase::Dealing {
+                    self.session.start_play()?;
+                }
+            }
+            Message::Reveal {
+                card_index,
+                token,
+                proof,
+            } => {
+                self.session
+                    .submit_reveal(card_index as usize, sender, token, proof)?;
+            }
+            message => {
+                return Err(CardProtocolError::InvalidMessage(format!(
+                    "unexpected {} message",
+                    message.kind()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open_card(&self, card_index: usize) -> Result<Card<C>, Detection> {
+        self.session
+            .open_card(card_index)
+            .map_err(|error| Detection {
+                detected_by: Some(self.index),
+                culprit: error.context().and_then(|context| context.player),
+                error,
+            })
+    }
+}
+
+/// Observer of the bus, checking that no player sends different messages for the same step of the
+/// protocol. Both conflicting messages are signed by their sender, which proves the equivocation.
+struct Referee<C: ProjectiveCurve> {
+    pp: Parameters<C>,
+    context: MessageContext,
+    keys: Vec<PublicKey<C>>,
+    feed: Receiver<Vec<u8>>,
+    /// First message seen for each sender, kind of message and card index
+    seen: HashMap<(usize, &'static str, u64), Vec<u8>>,
+}
+
+impl<C: ProjectiveCurve> Referee<C> {
+    fn watch(&mut self) -> Result<(), Detection> {
+        while let Ok(bytes) = self.feed.try_recv() {
+            // Messages that do not verify are rejected, and attributed, by the players themselves
+            let (header, message) = match self.context.open(&self.pp, &bytes, &self.keys) {
+                Ok(opened) => opened,
+                Err(_) => continue,
+            };
+            let step = match &message {
+                Message::KeyOwnership { .. }
+                | Message::Shuffle { .. }
+                | Message::KeyEscrow { .. }
+                | Message::KeyAddition { .. }
+                | Message::KeyRemoval { .. } => 0,
+                Message::Reveal { card_index, .. } => *card_index,
+                // The dealer sends one mask message per card
+                Message::Mask { .. } | Message::Remask { .. } => continue,
+                // A player may send several of these for the same card or step, one per stalled player
+                Message::EscrowedReveal { .. } | Message::StallReport { .. } => continue,
+            };
+
+            let sender = header.sender as usize;
+            let first = self
+                .seen
+                .entry((sender, message.kind(), step))
+                .or_insert_with(|| bytes.clone());
+            if *first != bytes {
+                return Err(Detection {
+                    detected_by: None,
+                    culprit: Some(sender),
+                    error: CardProtocolError::Equivocation {
+                        context: ErrorContext::player(sender),
+                    },
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Each player gets its own copy of the parameters
+fn copy_parameters<C: ProjectiveCurve>(
+    pp: &Parameters<C>,
+) -> Result<Parameters<C>, CardProtocolError> {
+    let mut bytes = Vec::new();
+    pp.serialize(&mut bytes)?;
+
+    Ok(Parameters::deserialize(&bytes[..])?)
+}
+
+/// A game between simulated players connected by a [`Bus`].
+pub struct Simulation<C: ProjectiveCurve> {
+    bus: Bus,
+    players: Vec<Player<C>>,
+    referee: Referee<C>,
+    participants_digest: Vec<u8>,
+    /// Open cards of the initial deck
+    encoding: Vec<Card<C>>,
+    /// Number of cards of the final deck opened in public
+    num_cards: usize,
+}
+
+impl<C: ProjectiveCurve> Simulation<C> {
+    /// Seat one player per entry of `behaviours`, in turn order, for a game on a deck of `m * n` cards
+    /// in which the first `num_cards` cards of the final deck are opened in public.
+    pub fn new<R: Rng>(
+        rng: &mut R,
+        m: usize,
+        n: usize,
+        behaviours: &[Behaviour],
+        num_cards: usize,
+    ) -> Result<Self, CardProtocolError> {
+        if behaviours.is_empty() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: String::from("no player"),
+                context: ErrorContext::default(),
+            });
+        }
+
+        let pp = DLCards::<C>::setup(rng, m, n)?;
+        if num_cards > pp.deck_size() {
+            return Err(CardProtocolError::ParameterMismatch {
+                reason: format!(
+                    "cannot open {} cards of a {}-card deck",
+                    num_cards,
+                    pp.deck_size()
+                ),
+                context: ErrorContext::default(),
+            });
+        }
+
+        let mut session_id = [0u8; 32];
+        rng.fill_bytes(&mut session_id);
+        let context = MessageContext::new(&pp, session_id, behaviours.len() as u32)?;
+
+        let key_pairs = behaviours
+            .iter()
+            .map(|_| DLCards::<C>::player_keygen(rng, &pp))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys: Vec<PublicKey<C>> = key_pairs.iter().map(|(pk, _)| *pk).collect();
+
+        let (bus, inboxes, feed) = Bus::new(behaviours.len());
+        let players = key_pairs
+            .into_iter()
+            .zip(behaviours)
+            .zip(inboxes)
+            .enumerate()
+            .map(|(index, (((pk, sk), &behaviour), inbox))| {
+                Ok(Player {
+                    index,
+                    behaviour,
+                    name: format!("player {}", index).into_bytes(),
+                    pk,
+                    sk,
+                    keys: keys.clone(),
+                    context,
+                    session: GameSession::new(copy_parameters(&pp)?),
+                    inbox,
+                    initial_deck: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, CardProtocolError>>()?;
+
+        let participants_digest =
+            DLCards::<C>::participants_digest(players.iter().map(|p| (&p.pk, &p.name)))?;
+        let encoding = (0..pp.deck_size()).map(|_| Card::<C>::rand(rng)).collect();
+
+        Ok(Self {
+            bus,
+            players,
+            referee: Referee {
+                pp,
+                context,
+                keys,
+                feed,
+                seen: HashMap::new(),
+            },
+            participants_digest,
+            encoding,
+            num_cards,
+        })
+    }
+
+    pub fn players(&self) -> &[Player<C>] {
+        &self.players
+    }
+
+    /// Open cards of the initial deck
+    pub fn encoding(&self) -> &[Card<C>] {
+        &self.encoding
+    }
+
+    /// Deliver the pending messages: the referee looks at the traffic first, then every player handles
+    /// its inbox in turn order.
+    fn deliver(&mut self) -> Result<(), Detection> {
+        self.referee.watch()?;
+        for player in &mut self.players {
+            player.process()?;
+        }
+
+        Ok(())
+    }
+
+    /// Play the game. Returns the cards opened by each player, or the first misbehaviour detected.
+    /// A player failing to compute their own message is held responsible for it.
+    pub fn run<R: Rng>(&mut self, rng: &mut R) -> Result<Vec<Vec<Card<C>>>, Detection> {
+        let failed = |player: usize| {
+            move |error: CardProtocolError| Detection {
+                detected_by: Some(player),
+                culprit: Some(player),
+                error,
+            }
+        };
+
+        for i in 0..self.players.len() {
+            self.players[i]
+                .announce_key(rng, &self.bus, &self.participants_digest)
+                .map_err(failed(i))?;
+            self.deliver()?;
+        }
+
+        self.players[DEALER]
+            .mask_deck(rng, &self.bus, &self.encoding)
+            .map_err(failed(DEALER))?;
+        self.deliver()?;
+
+        for i in 0..self.players.len() {
+            self.players[i].shuffle(rng, &self.bus).map_err(failed(i))?;
+            self.deliver()?;
+        }
+
+        for card_index in 0..self.num_cards {
+            for i in 0..self.players.len() {
+                self.players[i]
+                    .reveal(rng, &self.bus, card_index)
+                    .map_err(failed(i))?;
+                self.deliver()?;
+            }
+        }
+
+        self.players
+            .iter()
+            .map(|player| {
+                (0..self.num_cards)
+                    .map(|card_index| player.open_card(card_index))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Behaviour, Detection, Simulation};
+    use crate::discrete_log_cards;
+    use crate::error::{CardProtocolError, ErrorContext};
+
+    use rand::thread_rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    type Card = discrete_log_cards::Card<Curve>;
+
+    const M: usize = 2;
+    const N: usize = 4;
+    const NUM_CARDS: usize = 3;
+
+    fn run(behaviours: &[Behaviour]) -> Result<Vec<Vec<Card>>, Detection> {
+        let rng = &mut thread_rng();
+        let mut simulation = Simulation::<Curve>::new(rng, M, N, behaviours, NUM_CARDS).unwrap();
+
+        let opened = simulation.run(rng)?;
+        for cards in &opened {
+            for card in cards {
+                assert!(simulation.encoding().contains(card));
+            }
+        }
+
+        Ok(opened)
+    }
+
+    fn with_culprit(behaviour: Behaviour) -> Detection {
+        run(&[Behaviour::Honest, behaviour, Behaviour::Honest]).unwrap_err()
+    }
+
+    #[test]
+    fn honest_game() {
+        let opened = run(&[Behaviour::Honest; 4]).unwrap();
+
+        assert_eq!(opened.len(), 4);
+        for cards in &opened {
+            assert_eq!(cards, &opened[0]);
+        }
+        for (i, card) in opened[0].iter().enumerate() {
+            assert!(!opened[0][..i].contains(card));
+        }
+    }
+
+    #[test]
+    fn bad_shuffle() {
+        let detection = with_culprit(Behaviour::BadShuffle);
+
+        assert_eq!(detection.detected_by, Some(0));
+        assert_eq!(detection.culprit, Some(1));
+        assert!(matches!(
+            detection.error,
+            CardProtocolError::ShuffleVerificationError { .. }
+        ));
+    }
+
+    #[test]
+    fn wrong_reveal_token() {
+        let detection = with_culprit(Behaviour::WrongRevealToken);
+
+        assert_eq!(detection.detected_by, Some(0));
+        assert_eq!(detection.culprit, Some(1));
+        assert!(matches!(
+            detection.error,
+            CardProtocolError::RevealVerificationError {
+                context: ErrorContext {
+                    player: Some(1),
+                    card: Some(0)
+                },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn withholding() {
+        let detection = with_culprit(Behaviour::Withholding);
+
+        assert_eq!(
+            detection,
+            Detection {
+                detected_by: Some(0),
+                culprit: Some(1),
+                error: CardProtocolError::ParameterMismatch {
+                    reason: String::from("missing reveal token"),
+                    context: ErrorContext {
+                        player: Some(1),
+                        card: Some(0)
+                    },
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn equivocation() {
+        let detection = with_culprit(Behaviour::Equivocation);
+
+        assert_eq!(
+            detection,
+            Detection {
+                detected_by: None,
+                culprit: Some(1),
+                error: CardProtocolError::Equivocation {
+                    context: ErrorContext::player(1)
+                },
+            }
+        );
+    }
+}
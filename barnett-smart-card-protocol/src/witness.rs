@@ -0,0 +1,49 @@
+//! Secret scalars a player chooses when masking, remasking or shuffling cards.
+//!
+//! Anybody learning the masking factor of a card can follow it through the shuffle, so masking factors
+//! are handled like secret keys: they are wiped from memory when dropped, are redacted from `Debug`
+//! output and cannot be cloned.
+
+use ark_ff::Field;
+use ark_std::rand::Rng;
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Secret witness of a masking, remasking or shuffle proof: a single masking factor, or one masking
+/// factor per card of a deck. The value is only reachable through `expose_secret`.
+pub struct Witness<T: Zeroize>(T);
+
+impl<T: Zeroize> Witness<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<F: Field> Witness<Vec<F>> {
+    /// Sample fresh masking factors for a deck of `len` cards
+    pub fn sample<R: Rng>(rng: &mut R, len: usize) -> Self {
+        Self((0..len).map(|_| F::rand(rng)).collect())
+    }
+}
+
+impl<T: Zeroize> Zeroize for Witness<T> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> Drop for Witness<T> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Witness<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Witness(<redacted>)")
+    }
+}
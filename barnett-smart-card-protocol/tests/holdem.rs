@@ -0,0 +1,221 @@
+use barnett_smart_card_protocol::betting::{Action, Betting};
+use barnett_smart_card_protocol::discrete_log_cards::Parameters;
+use barnett_smart_card_protocol::error::{CardProtocolError, ErrorContext};
+use barnett_smart_card_protocol::holdem::{HoldemPlayer, Street, Table, DECK_SIZE};
+
+use rand::{thread_rng, Rng};
+use std::collections::HashSet;
+
+// Choose elliptic curve setting
+type Curve = starknet_curve::Projective;
+
+fn seat_players<R: Rng>(
+    rng: &mut R,
+    parameters: &Parameters<Curve>,
+    num_players: usize,
+) -> Vec<HoldemPlayer<Curve>> {
+    (0..num_players)
+        .map(|i| HoldemPlayer::new(rng, parameters, format!("player {}", i).as_bytes()).unwrap())
+        .collect()
+}
+
+/// Bet the hand with 100 chips per player, the button at seat 0 and blinds of 1 and 2
+fn betting(num_players: usize) -> Betting {
+    Betting::new(vec![100; num_players], 0, 1, 2).unwrap()
+}
+
+/// Every player checks or calls until the betting round is over
+fn call_around(table: &mut Table<Curve>) {
+    while let Some(player) = table.betting().to_act() {
+        if table.act(player, Action::Check).is_err() {
+            table.act(player, Action::Call).unwrap();
+        }
+    }
+}
+
+#[test]
+fn complete_hands() {
+    let rng = &mut thread_rng();
+
+    for num_players in 2..=9 {
+        let parameters = Table::<Curve>::setup(rng).unwrap();
+        let mut players = seat_players(rng, &parameters, num_players);
+        let mut table = Table::new(rng, parameters, &players, betting(num_players)).unwrap();
+        assert_eq!(table.street(), Street::Deal);
+
+        table.deal_hole_cards(rng, &mut players).unwrap();
+        call_around(&mut table);
+        table.deal_flop(rng, &players).unwrap();
+        assert_eq!(table.board().len(), 3);
+        call_around(&mut table);
+        table.deal_turn(rng, &players).unwrap();
+        call_around(&mut table);
+        table.deal_river(rng, &players).unwrap();
+        assert_eq!(table.board().len(), 5);
+        call_around(&mut table);
+
+        let mucked = (0..num_players)
+            .map(|_| rng.gen_bool(0.3))
+            .collect::<Vec<_>>();
+        let shown = table.showdown(rng, &players, &mucked).unwrap();
+        assert_eq!(table.street(), Street::Showdown);
+
+        let winners = table.winners(&shown).unwrap();
+        assert_eq!(winners.is_empty(), mucked.iter().all(|&mucked| mucked));
+        assert!(winners.iter().all(|&winner| !mucked[winner]));
+
+        // The pot of the big blind called by everyone goes to the winners
+        let winnings = table.settle(&shown).unwrap();
+        assert_eq!(winnings.iter().sum::<u64>(), 2 * num_players as u64);
+        for &winner in &winners {
+            assert!(winnings[winner] > 0);
+        }
+
+        // Every card comes from the deck and is dealt at most once
+        let mut seen = HashSet::new();
+        for card in players
+            .iter()
+            .flat_map(|player| player.hole_cards())
+            .chain(table.board())
+        {
+            assert!(*card < DECK_SIZE);
+            assert!(seen.insert(*card));
+        }
+        assert_eq!(seen.len(), 2 * num_players + 5);
+
+        // Players show the cards they were dealt, mucked cards stay hidden
+        for ((player, shown), mucked) in players.iter().zip(shown).zip(mucked) {
+            assert_eq!(player.hole_cards().len(), 2);
+            if mucked {
+                assert_eq!(shown, None);
+            } else {
+                assert_eq!(shown.as_deref(), Some(player.hole_cards()));
+            }
+        }
+    }
+}
+
+#[test]
+fn streets_in_order() {
+    let rng = &mut thread_rng();
+
+    let parameters = Table::<Curve>::setup(rng).unwrap();
+    let mut players = seat_players(rng, &parameters, 2);
+    let mut table = Table::new(rng, parameters, &players, betting(2)).unwrap();
+
+    let wrong_street = |expected: &str, found: &str| {
+        Some(CardProtocolError::WrongProtocolPhase {
+            expected: String::from(expected),
+            found: String::from(found),
+            context: ErrorContext::default(),
+        })
+    };
+
+    assert_eq!(
+        table.deal_flop(rng, &players).err(),
+        wrong_street("preflop", "deal")
+    );
+    assert_eq!(
+        table.act(0, Action::Call).err(),
+        wrong_street("preflop, flop, turn or river", "deal")
+    );
+    table.deal_hole_cards(rng, &mut players).unwrap();
+    assert_eq!(
+        table.deal_hole_cards(rng, &mut players).err(),
+        wrong_street("deal", "preflop")
+    );
+    assert_eq!(
+        table.deal_river(rng, &players).err(),
+        wrong_street("turn", "preflop")
+    );
+    assert_eq!(
+        table.showdown(rng, &players, &[false, false]).err(),
+        wrong_street("river", "preflop")
+    );
+
+    // The flop is only dealt once the preflop betting round is over. Heads-up, the button acts first.
+    assert!(matches!(
+        table.deal_flop(rng, &players),
+        Err(CardProtocolError::InvalidBet { context, .. }) if context == ErrorContext::player(0)
+    ));
+    assert!(matches!(
+        table.act(1, Action::Check),
+        Err(CardProtocolError::OutOfTurn { expected: 0, .. })
+    ));
+    call_around(&mut table);
+
+    // Only the players seated at the table can take part in the hand
+    let mut other_players = players;
+    other_players.swap(0, 1);
+    assert!(matches!(
+        table.deal_flop(rng, &other_players),
+        Err(CardProtocolError::ParameterMismatch { .. })
+    ));
+}
+
+#[test]
+fn folded_players_keep_revealing_community_cards() {
+    let rng = &mut thread_rng();
+
+    let parameters = Table::<Curve>::setup(rng).unwrap();
+    let mut players = seat_players(rng, &parameters, 3);
+    let mut table = Table::new(rng, parameters, &players, betting(3)).unwrap();
+
+    // Preflop betting: player 0 folds, the blinds check
+    table.deal_hole_cards(rng, &mut players).unwrap();
+    table.act(0, Action::Fold).unwrap();
+    assert!(table.session().has_folded(0));
+    table.act(1, Action::Call).unwrap();
+    table.act(2, Action::Check).unwrap();
+
+    // The folded player still takes part in opening the board
+    table.deal_flop(rng, &players).unwrap();
+    call_around(&mut table);
+    table.deal_turn(rng, &players).unwrap();
+    call_around(&mut table);
+    table.deal_river(rng, &players).unwrap();
+    call_around(&mut table);
+    assert_eq!(table.board().len(), 5);
+
+    // Their hole cards are never opened, even if they do not muck
+    let shown = table
+        .showdown(rng, &players, &[false, false, false])
+        .unwrap();
+    assert_eq!(shown[0], None);
+    assert!(shown[1].is_some() && shown[2].is_some());
+    for &card_index in &[0, 3] {
+        assert!(matches!(
+            table.session().open_card(card_index),
+            Err(CardProtocolError::ParameterMismatch { .. })
+        ));
+    }
+
+    let winners = table.winners(&shown).unwrap();
+    assert!(!winners.contains(&0));
+    assert_eq!(table.settle(&shown).unwrap()[0], 0);
+}
+
+#[test]
+fn hand_won_by_folds() {
+    let rng = &mut thread_rng();
+
+    let parameters = Table::<Curve>::setup(rng).unwrap();
+    let mut players = seat_players(rng, &parameters, 2);
+
+    // The betting must seat the players of the table
+    let other_parameters = Table::<Curve>::setup(rng).unwrap();
+    assert!(matches!(
+        Table::new(rng, other_parameters, &players, betting(3)),
+        Err(CardProtocolError::ParameterMismatch { .. })
+    ));
+
+    let mut table = Table::new(rng, parameters, &players, betting(2)).unwrap();
+    table.deal_hole_cards(rng, &mut players).unwrap();
+
+    // The button folds its small blind, the big blind wins the pot without a showdown
+    table.act(0, Action::Fold).unwrap();
+    assert!(table.betting().is_hand_over());
+    assert!(table.session().has_folded(0));
+    assert_eq!(table.settle(&[]).unwrap(), vec![0, 3]);
+    assert_eq!(table.betting().stacks(), &[99, 101]);
+}
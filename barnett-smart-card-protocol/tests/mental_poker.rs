@@ -0,0 +1,390 @@
+use rand::{thread_rng, Rng};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+/// Scratch directory holding the files of a test, removed when the test ends even if it fails
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn new() -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("mental-poker-{:016x}", thread_rng().gen::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn mental_poker(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mental-poker"))
+        .args(args)
+        .env("MENTAL_POKER_PASSPHRASE", PASSPHRASE)
+        .output()
+        .unwrap()
+}
+
+/// Run a subcommand that must succeed and return its standard output
+fn run(args: &[&str]) -> String {
+    let output = mental_poker(args);
+    assert!(
+        output.status.success(),
+        "`mental-poker {}` failed:\n{}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn play_hand(format: &str) {
+    let dir = WorkDir::new();
+    let p = |name: &str| dir.path(name);
+    let params = p("params");
+    let shared_key = p("shared.pk");
+
+    run(&[
+        "--format",
+        format,
+        "setup",
+        "--m",
+        "2",
+        "--n",
+        "4",
+        "--seed",
+        &"ab".repeat(32),
+        "--out",
+        &params,
+    ]);
+
+    let players = ["alice", "bob"];
+    for name in players {
+        run(&[
+            "--format",
+            format,
+            "keygen",
+            "--params",
+            &params,
+            "--out-pk",
+            &p(&format!("{}.pk", name)),
+            "--out-sk",
+            &p(&format!("{}.sk", name)),
+        ]);
+    }
+
+    let mut participants = Vec::new();
+    for name in players {
+        participants.extend([
+            "--participant-pk".to_string(),
+            p(&format!("{}.pk", name)),
+            "--participant-info".to_string(),
+            name.to_string(),
+        ]);
+    }
+    for name in players {
+        let mut args = vec![
+            "--format".to_string(),
+            format.to_string(),
+            "prove-key".to_string(),
+            "--params".to_string(),
+            params.clone(),
+            "--pk".to_string(),
+            p(&format!("{}.pk", name)),
+            "--sk".to_string(),
+            p(&format!("{}.sk", name)),
+            "--info".to_string(),
+            name.to_string(),
+            "--out".to_string(),
+            p(&format!("{}.proof", name)),
+        ];
+        args.extend(participants.iter().cloned());
+        run(&args.iter().map(String::as_str).collect::<Vec<_>>());
+    }
+
+    let mut args = vec![
+        "--format".to_string(),
+        format.to_string(),
+        "aggregate".to_string(),
+        "--params".to_string(),
+        params.clone(),
+        "--out".to_string(),
+        shared_key.clone(),
+    ];
+    for name in players {
+        args.extend([
+            "--pk".to_string(),
+            p(&format!("{}.pk", name)),
+            "--proof".to_string(),
+            p(&format!("{}.proof", name)),
+            "--info".to_string(),
+            name.to_string(),
+        ]);
+    }
+    run(&args.iter().map(String::as_str).collect::<Vec<_>>());
+
+    run(&[
+        "--format",
+        format,
+        "mask-deck",
+        "--params",
+        &params,
+        "--shared-key",
+        &shared_key,
+        "--out-cards",
+        &p("cards"),
+        "--out",
+        &p("deck.0"),
+        "--out-proofs",
+        &p("masking.proofs"),
+    ]);
+
+    // Every player shuffles the deck in turn and the shuffle is checked
+    for (i, name) in players.iter().enumerate() {
+        let (deck, shuffled) = (p(&format!("deck.{}", i)), p(&format!("deck.{}", i + 1)));
+        let proof = p(&format!("{}.shuffle", name));
+        run(&[
+            "--format",
+            format,
+            "shuffle",
+            "--params",
+            &params,
+            "--shared-key",
+            &shared_key,
+            "--deck",
+            &deck,
+            "--out",
+            &shuffled,
+            "--out-proof",
+            &proof,
+        ]);
+
+        let verify: [&str; 13] = [
+            "--format",
+            format,
+            "verify-shuffle",
+            "--params",
+            &params,
+            "--shared-key",
+            &shared_key,
+            "--deck",
+            &deck,
+            "--shuffled",
+            &shuffled,
+            "--proof",
+            &proof,
+        ];
+        assert!(run(&verify).contains("Shuffle is valid"));
+
+        // The proof does not hold for a deck the player did not shuffle
+        if i > 0 {
+            let initial_deck = p("deck.0");
+            let mut forged = verify;
+            forged[8] = initial_deck.as_str();
+            assert!(!mental_poker(&forged).status.success());
+        }
+    }
+
+    let final_deck = p(&format!("deck.{}", players.len()));
+    let mut tokens = Vec::new();
+    for name in players {
+        let token = p(&format!("{}.token", name));
+        run(&[
+            "--format",
+            format,
+            "reveal-token",
+            "--params",
+            &params,
+            "--pk",
+            &p(&format!("{}.pk", name)),
+            "--sk",
+            &p(&format!("{}.sk", name)),
+            "--deck",
+            &final_deck,
+            "--index",
+            "3",
+            "--out",
+            &token,
+        ]);
+        tokens.push(token);
+    }
+
+    let output = run(&[
+        "--format",
+        format,
+        "unmask",
+        "--params",
+        &params,
+        "--deck",
+        &final_deck,
+        "--index",
+        "3",
+        "--token",
+        &tokens[0],
+        "--token",
+        &tokens[1],
+        "--cards",
+        &p("cards"),
+    ]);
+    assert!(output.contains("Card: "));
+    assert!(output.contains("Card number "));
+
+    // A single token is not enough to open the card
+    let output = mental_poker(&[
+        "--format",
+        format,
+        "unmask",
+        "--params",
+        &params,
+        "--deck",
+        &final_deck,
+        "--index",
+        "3",
+        "--token",
+        &tokens[0],
+        "--cards",
+        &p("cards"),
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn hand_in_every_format() {
+    for format in ["bin", "hex", "json"] {
+        play_hand(format);
+    }
+}
+
+#[test]
+fn keystore_rejects_wrong_passphrase() {
+    let dir = WorkDir::new();
+    let params = dir.path("params");
+    run(&["setup", "--m", "2", "--n", "4", "--out", &params]);
+    run(&[
+        "keygen",
+        "--params",
+        &params,
+        "--out-pk",
+        &dir.path("pk"),
+        "--out-sk",
+        &dir.path("sk"),
+    ]);
+
+    let args: [&str; 13] = [
+        "reveal-token",
+        "--params",
+        &params,
+        "--pk",
+        &dir.path("pk"),
+        "--sk",
+        &dir.path("sk"),
+        "--deck",
+        &dir.path("deck"),
+        "--index",
+        "0",
+        "--out",
+        &dir.path("token"),
+    ];
+    let output = Command::new(env!("CARGO_BIN_EXE_mental-poker"))
+        .args(args)
+        .env("MENTAL_POKER_PASSPHRASE", "wrong passphrase")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("opening keystore"));
+    assert!(!Path::new(&dir.path("token")).exists());
+}
+
+#[test]
+fn json_objects_record_curve_and_kind() {
+    let dir = WorkDir::new();
+    let params = dir.path("params");
+    run(&[
+        "--format", "json", "setup", "--m", "2", "--n", "4", "--out", &params,
+    ]);
+
+    let envelope: serde_json::Value = serde_json::from_slice(&fs::read(&params).unwrap()).unwrap();
+    assert_eq!(envelope["curve"], "starknet");
+    assert_eq!(envelope["kind"], "parameters");
+
+    // Objects over one curve are refused when running over another
+    let output = mental_poker(&[
+        "--format",
+        "json",
+        "--curve",
+        "bls12-377",
+        "keygen",
+        "--params",
+        &params,
+        "--out-pk",
+        &dir.path("pk"),
+        "--out-sk",
+        &dir.path("sk"),
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn files_are_read_in_the_selected_format() {
+    let dir = WorkDir::new();
+    let params = dir.path("params");
+    run(&[
+        "--format", "hex", "setup", "--m", "2", "--n", "4", "--out", &params,
+    ]);
+
+    let keygen = |format: &str| {
+        mental_poker(&[
+            "--format",
+            format,
+            "keygen",
+            "--params",
+            &params,
+            "--out-pk",
+            &dir.path("pk"),
+            "--out-sk",
+            &dir.path("sk"),
+        ])
+    };
+    assert!(!keygen("bin").status.success());
+    assert!(!keygen("json").status.success());
+    assert!(keygen("hex").status.success());
+}
+
+#[test]
+fn setup_rejects_unsupported_deck_sizes() {
+    let dir = WorkDir::new();
+    let params = dir.path("params");
+    for (m, n) in [("0", "4"), ("4096", "2"), ("4294967296", "4294967296")] {
+        let output = mental_poker(&["setup", "--m", m, "--n", n, "--out", &params]);
+        assert!(!output.status.success());
+        assert!(!Path::new(&params).exists());
+    }
+
+    // A parameters file asking for a huge deck is refused before deriving the parameters
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+    bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+    bytes.extend_from_slice(&[0; 32]);
+    fs::write(&params, bytes).unwrap();
+    let output = mental_poker(&[
+        "keygen",
+        "--params",
+        &params,
+        "--out-pk",
+        &dir.path("pk"),
+        "--out-sk",
+        &dir.path("sk"),
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not supported"));
+}
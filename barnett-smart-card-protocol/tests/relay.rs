@@ -0,0 +1,117 @@
+use rand::{thread_rng, Rng};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+const NUM_PLAYERS: usize = 3;
+
+/// Relay process, killed when the test ends even if it fails
+struct Relay(Child);
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn start_relay() -> (Relay, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_relay"))
+        .args(["--listen", "127.0.0.1:0"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let addr = line
+        .trim()
+        .strip_prefix("Listening on ")
+        .unwrap()
+        .to_string();
+
+    (Relay(child), addr)
+}
+
+/// Rest of the line of `output` starting with `key`
+fn field<'a>(output: &'a str, key: &str) -> &'a str {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix(key))
+        .unwrap_or_else(|| panic!("no `{}` line in:\n{}", key, output))
+        .trim()
+}
+
+fn ids(field: &str) -> Vec<usize> {
+    field
+        .split_whitespace()
+        .map(|id| id.parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn complete_hand_between_processes() {
+    let (_relay, addr) = start_relay();
+    let seed: String = thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let players = (0..NUM_PLAYERS)
+        .map(|index| {
+            Command::new(env!("CARGO_BIN_EXE_holdem-player"))
+                .args(["--relay", &addr, "--seed", &seed])
+                .args(["--players", &NUM_PLAYERS.to_string()])
+                .args(["--index", &index.to_string()])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let outputs = players
+        .into_iter()
+        .map(|player| {
+            let output = player.wait_with_output().unwrap();
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            String::from_utf8(output.stdout).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    // Everybody agrees on the public outcome of the hand
+    let shows = |player: usize| format!("player {} shows:", player);
+    for output in &outputs {
+        assert_eq!(field(output, "board:"), field(&outputs[0], "board:"));
+        for player in 0..NUM_PLAYERS {
+            assert_eq!(
+                field(output, &shows(player)),
+                field(&outputs[0], &shows(player))
+            );
+        }
+        assert_eq!(field(output, "winners:"), field(&outputs[0], "winners:"));
+    }
+
+    // Players show the cards they saw in private, and no card is dealt twice
+    let board = ids(field(&outputs[0], "board:"));
+    assert_eq!(board.len(), 5);
+    let mut cards: HashSet<usize> = board.into_iter().collect();
+    for (player, output) in outputs.iter().enumerate() {
+        let hole_cards = ids(field(output, "hole cards:"));
+        assert_eq!(hole_cards.len(), 2);
+        assert_eq!(hole_cards, ids(field(output, &shows(player))));
+        cards.extend(hole_cards);
+    }
+    assert_eq!(cards.len(), 5 + 2 * NUM_PLAYERS);
+    assert!(cards.iter().all(|&id| id < 52));
+
+    let winners = ids(field(&outputs[0], "winners:"));
+    assert!(!winners.is_empty());
+    assert!(winners.iter().all(|&player| player < NUM_PLAYERS));
+}